@@ -0,0 +1,174 @@
+//! Scrubbing of network identifiers (IP addresses and enode public keys) from parsed entries.
+//!
+//! Each value is replaced by a placeholder derived from a keyed hash, with a random key drawn once
+//! per [`Anonymizer`]: the same address always maps to the same placeholder within a run, but the
+//! placeholder can't be reversed, and differs between runs.
+
+// --- IMPORTS ---
+// Standard library imports
+use std::collections::HashSet;
+use std::hash::{BuildHasher, RandomState};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+// Third-party libraries
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+
+// Local crate
+use crate::LogEntry;
+
+// --- DATA STRUCTURES ---
+/// Replaces IP addresses and enode public keys with stable, salted placeholders.
+#[derive(Debug, Default)]
+pub struct Anonymizer {
+    salt: RandomState,  // Randomly keyed hasher, fixed for the lifetime of the anonymizer
+    seen: HashSet<u64>, // Hashes of every distinct value replaced so far
+}
+
+/// The kinds of identifier that get placeholders, kept apart so an IP and a key never collide.
+#[derive(Debug, Clone, Copy, Hash)]
+enum Identifier {
+    Ip,         // IPv4 or IPv6 address, hashed in canonical form
+    EnodeKey,   // Hex public key of an `enode://` URL
+}
+
+// --- GLOBAL VARIABLES ---
+lazy_static! {
+    /// The public key of an `enode://` URL, captured without the scheme.
+    static ref ENODE_KEY_REGEX: Regex = Regex::new(r"(?P<scheme>enode://)(?P<key>[0-9a-fA-F]+)").unwrap();
+
+    /// A run of hex digits, dots and at least two colons: a candidate IPv6 address, perhaps with a port.
+    static ref IPV6_CANDIDATE_REGEX: Regex = Regex::new(r"[0-9A-Fa-f.:]*:[0-9A-Fa-f.:]*:[0-9A-Fa-f.:]*").unwrap();
+
+    /// A dotted quad: a candidate IPv4 address.
+    static ref IPV4_CANDIDATE_REGEX: Regex = Regex::new(r"\b\d{1,3}(?:\.\d{1,3}){3}\b").unwrap();
+}
+
+// --- IMPLEMENTATIONS ---
+impl Anonymizer {
+    /// Creates an anonymizer with a fresh random salt.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How many distinct IPs and enode keys have been replaced so far.
+    pub fn distinct_values(&self) -> usize {
+        self.seen.len()
+    }
+
+    /// Scrubs `message`, `details` values, `peer_id`, `enode`, `raw` and `continuation` in place.
+    pub fn anonymize_entry(&mut self, entry: &mut LogEntry) {
+        self.scrub_in_place(&mut entry.message);
+        for value in entry.details.values_mut() {
+            self.scrub_in_place(value);
+        }
+        for value in entry.peer_id.iter_mut().chain(entry.enode.iter_mut()) {
+            self.scrub_in_place(value);
+        }
+        self.scrub_in_place(&mut entry.raw);
+        for line in entry.continuation.iter_mut() {
+            self.scrub_in_place(line);
+        }
+    }
+
+    /// Returns `text` with every IP address and enode public key replaced by its placeholder.
+    pub fn scrub(&mut self, text: &str) -> String {
+        let text = ENODE_KEY_REGEX.replace_all(text, |caps: &Captures| {
+            format!("{}{}", &caps["scheme"], self.placeholder(Identifier::EnodeKey, &caps["key"].to_lowercase()))
+        });
+        let text = IPV6_CANDIDATE_REGEX.replace_all(&text, |caps: &Captures| {
+            let candidate = &caps[0];
+            match split_ipv6(candidate) {
+                Some((address, rest)) => format!("{}{}", self.placeholder(Identifier::Ip, &address.to_string()), rest),
+                None => candidate.to_string(),
+            }
+        });
+        IPV4_CANDIDATE_REGEX
+            .replace_all(&text, |caps: &Captures| match caps[0].parse::<Ipv4Addr>() {
+                Ok(address) => self.placeholder(Identifier::Ip, &IpAddr::V4(address).to_string()),
+                Err(_) => caps[0].to_string(),
+            })
+            .into_owned()
+    }
+
+    /// Scrubs `text` in place, leaving it untouched when there's nothing to replace.
+    fn scrub_in_place(&mut self, text: &mut String) {
+        if !text.is_empty() {
+            *text = self.scrub(text);
+        }
+    }
+
+    /// The placeholder for `value`, e.g. `ip-3f9a0c1d22b7e4a5`, recording it as seen.
+    fn placeholder(&mut self, kind: Identifier, value: &str) -> String {
+        let hash = self.salt.hash_one((kind, value));
+        self.seen.insert(hash);
+        match kind {
+            Identifier::Ip => format!("ip-{:016x}", hash),
+            Identifier::EnodeKey => format!("pubkey-{:016x}", hash),
+        }
+    }
+}
+
+// --- HELPER FUNCTIONS ---
+/// Splits an IPv6 candidate into the address and whatever trails it (a `:port`, or a final `.`).
+fn split_ipv6(candidate: &str) -> Option<(Ipv6Addr, &str)> {
+    let trimmed = candidate.trim_end_matches('.');
+    if let Ok(address) = trimmed.parse::<Ipv6Addr>() {
+        return Some((address, &candidate[trimmed.len()..]));
+    }
+    let (address, _) = trimmed.rsplit_once(':')?;
+    Some((address.parse().ok()?, &candidate[address.len()..]))
+}
+
+// --- UNIT TESTS ---
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_line;
+
+    const PUBKEY: &str = "a979fb575495b8d6db44f750317d0f4622bf4c2aa3365d6af7c284339968eef29b69ad0dce72a4d8db5ebb4968de0e3bec910127f134779fbcb0cb6d3331163c";
+
+    #[test]
+    fn test_scrub_replaces_ips_and_enode_keys_consistently() {
+        let mut anonymizer = Anonymizer::new();
+        let enode = format!("enode://{}@10.0.0.7:30303", PUBKEY);
+
+        let first = anonymizer.scrub(&format!("Adding peer {} from 10.0.0.7", enode));
+        let second = anonymizer.scrub("Dropped 10.0.0.7, kept 192.168.1.20 and [2001:db8::1]:30303");
+
+        let ip = anonymizer.scrub("10.0.0.7");
+        assert!(ip.starts_with("ip-") && ip.len() == 19);
+        assert!(first.starts_with("Adding peer enode://pubkey-"));
+        assert!(first.ends_with(&format!("@{}:30303 from {}", ip, ip)));
+        assert!(second.starts_with(&format!("Dropped {}, kept ip-", ip)));
+        assert!(second.ends_with("]:30303"));
+        assert!(!second.contains("2001:db8") && !first.contains(PUBKEY));
+        assert_eq!(anonymizer.distinct_values(), 4);
+    }
+
+    #[test]
+    fn test_scrub_leaves_non_addresses_alone() {
+        let mut anonymizer = Anonymizer::new();
+        let text = "Geth/v1.13.5-stable at 10:49:09.123, elapsed=2.5s, value 300.1.2.3, key deadbeef::1";
+
+        assert_eq!(anonymizer.scrub(text), text);
+        assert!(anonymizer.seen.is_empty());
+    }
+
+    #[test]
+    fn test_anonymize_entry_scrubs_every_field() {
+        let mut anonymizer = Anonymizer::new();
+        let line = format!("DEBUG [11-08|10:49:09] Adding p2p peer peer=10.0.0.7:30303 enode=enode://{}@10.0.0.7:30303", PUBKEY);
+        let mut entry = parse_line(&line, 2025).unwrap();
+        let mut again = parse_line(&line, 2025).unwrap();
+
+        anonymizer.anonymize_entry(&mut entry);
+        anonymizer.anonymize_entry(&mut again);
+
+        assert_eq!(anonymizer.distinct_values(), 2);
+        assert_eq!(entry.details, again.details);
+        assert!(!entry.message.contains("10.0.0.7"));
+        assert!(!entry.enode.unwrap().contains(PUBKEY));
+        assert!(!entry.peer_id.unwrap().contains("10.0.0.7"));
+    }
+}
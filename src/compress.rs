@@ -0,0 +1,54 @@
+//! Gzip compression of `--output` files.
+
+// --- IMPORTS ---
+// Standard library imports
+use std::io::Write;
+
+// Third-party libraries
+use clap::ValueEnum;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+// --- DATA STRUCTURES ---
+/// How `--compress-output` compresses output files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputCompression {
+    Gzip,  // Gzip, as `gunzip` and `--input` decompression read it
+}
+
+// --- HELPER FUNCTIONS ---
+/// Gzips everything written to `out` as a single gzip member. A `flush` pushes the compressed
+/// data written so far through to `out` (a sync flush, so a reader following the file can
+/// decompress every flushed entry) without ending the member; the trailer is written when the
+/// encoder is dropped at the end of the run.
+pub fn gzip_writer<W: Write>(out: W) -> GzEncoder<W> {
+    GzEncoder::new(out, Compression::default())
+}
+
+// --- UNIT TESTS ---
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    #[test]
+    fn test_flush_keeps_a_single_member() {
+        let mut writer = gzip_writer(Vec::new());
+
+        for level in ["INFO", "WARN", "INFO"] {
+            writeln!(writer, "{{\"level\":\"{}\"}}", level).unwrap();
+            writer.flush().unwrap();
+        }
+        let flushed = writer.get_ref().clone();
+        let finished = writer.finish().unwrap();
+
+        // A single-member decoder stops after the first member, so it sees every line only if
+        // the flushes didn't start new members.
+        let mut text = String::new();
+        GzDecoder::new(finished.as_slice()).read_to_string(&mut text).unwrap();
+        assert_eq!(text, "{\"level\":\"INFO\"}\n{\"level\":\"WARN\"}\n{\"level\":\"INFO\"}\n");
+        assert!(finished.starts_with(&flushed));
+        assert_eq!(finished.windows(3).filter(|window| *window == [0x1f, 0x8b, 0x08]).count(), 1);
+    }
+}
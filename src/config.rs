@@ -0,0 +1,109 @@
+//! `--config` file support: TOML defaults for frequently repeated flags.
+
+// --- IMPORTS ---
+// Standard library imports
+use std::fs;
+
+// Third-party libraries
+use clap::ValueEnum;
+use serde::Deserialize;
+
+// Local crate
+use crate::{parse_output_spec, CommonArgs};
+use geth_log_cruncher::output::OutputFormat;
+
+// --- DATA STRUCTURES ---
+/// Flag defaults read from a `--config` file. Every field mirrors the flag of the same name.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileConfig {
+    year: Option<i32>,                  // Default for `--year`
+    level: Option<String>,              // Default for `--level` (e.g. "WARN")
+    format: Option<String>,             // Default for `--format` (e.g. "human")
+    output: Option<String>,             // Default for `--output`
+    numeric_keys: Option<Vec<String>>,  // Default for `--numeric-keys`
+}
+
+// --- IMPLEMENTATIONS ---
+impl FileConfig {
+    /// Reads and parses a TOML config file. A missing or malformed file is an error.
+    pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Error: Could not read config file '{}': {}", path, e))?;
+        toml::from_str(&contents).map_err(|e| format!("Error: Invalid config file '{}': {}", path, e).into())
+    }
+
+    /// Fills in every flag not given on the command line from this config.
+    ///
+    /// `output` is skipped under `--split-by`, which writes to `--out-dir` instead, and under `--dry-run`.
+    pub fn apply(self, args: &mut CommonArgs) -> Result<(), Box<dyn std::error::Error>> {
+        args.year = args.year.or(self.year);
+        if args.level.is_none()
+            && let Some(level) = self.level
+        {
+            args.level = Some(parse_value_enum(&level, "level")?);
+        }
+        if args.format.is_none()
+            && let Some(format) = self.format
+        {
+            args.format = Some(parse_value_enum::<OutputFormat>(&format, "format")?);
+        }
+        if args.output.is_empty()
+            && args.split_by.is_none()
+            && !args.dry_run
+            && !args.invert
+            && let Some(output) = self.output
+        {
+            let spec = parse_output_spec(&output).map_err(|e| format!("Error: Invalid 'output' value in config file: {}", e))?;
+            args.output.push(spec);
+        }
+        args.numeric_keys = args.numeric_keys.take().or(self.numeric_keys);
+        Ok(())
+    }
+}
+
+// --- HELPER FUNCTIONS ---
+/// Parses a config value the same way clap parses the matching flag (case-insensitively).
+fn parse_value_enum<T: ValueEnum>(value: &str, key: &str) -> Result<T, String> {
+    T::from_str(value, true).map_err(|_| format!("Error: Invalid '{}' value '{}' in config file", key, value))
+}
+
+// --- UNIT TESTS ---
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Cli;
+    use clap::Parser;
+    use geth_log_cruncher::LogLevel;
+
+    #[test]
+    fn test_command_line_overrides_config() {
+        let config: FileConfig = toml::from_str(
+            r#"
+            year = 2023
+            level = "warn"
+            format = "human"
+            numeric_keys = ["gas", "txs"]
+            "#,
+        )
+        .unwrap();
+        let mut args = Cli::parse_from(["geth-log-cruncher", "geth.log", "--year", "2024"]).parse.common;
+
+        config.apply(&mut args).unwrap();
+
+        assert_eq!(args.year, Some(2024));
+        assert_eq!(args.level, Some(LogLevel::Warn));
+        assert_eq!(args.format, Some(OutputFormat::Human));
+        assert!(args.output.is_empty());
+        assert_eq!(args.numeric_keys, Some(vec!["gas".to_string(), "txs".to_string()]));
+    }
+
+    #[test]
+    fn test_config_rejects_unknown_keys_and_values() {
+        assert!(toml::from_str::<FileConfig>("colour = true").is_err());
+
+        let config: FileConfig = toml::from_str(r#"level = "loud""#).unwrap();
+        let mut args = Cli::parse_from(["geth-log-cruncher", "geth.log"]).parse.common;
+        assert!(config.apply(&mut args).is_err());
+    }
+}
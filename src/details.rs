@@ -0,0 +1,197 @@
+//! The `details` map of a parsed entry, and how keys given more than once in a line are kept.
+
+// --- IMPORTS ---
+// Standard library imports
+use std::collections::BTreeMap;
+use std::ops::{Deref, DerefMut};
+
+// Third-party libraries
+use clap::ValueEnum;
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+// --- DATA STRUCTURES ---
+/// Which value `details` keeps when a line repeats a key, e.g. `peer=a ... peer=b`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum DuplicateKeyPolicy {
+    #[default]
+    Last,   // The last value wins
+    First,  // The first value wins
+    Array,  // Every value is kept, and the key serializes as a JSON array
+}
+
+/// Key-value pairs extracted from a message, sorted by key.
+///
+/// Reads go through the one-value-per-key map it derefs to, which holds the last value of a
+/// repeated key. Values collected by `DuplicateKeyPolicy::Array` are only visible when
+/// serializing (as a JSON array) and through [`Details::repeated`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Details {
+    values: BTreeMap<String, String>,        // One value per key, as filters and scripts see them
+    repeated: BTreeMap<String, Vec<String>>, // Every value, in line order, of keys collected as arrays
+}
+
+/// A `details` value as read from entry JSON: a string, or an array for a repeated key.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DetailRepr {
+    One(String),
+    Many(Vec<String>),
+}
+
+// --- IMPLEMENTATIONS ---
+impl Details {
+    /// Creates an empty map.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a pair parsed from a line, resolving a repeated key by `policy`.
+    pub fn insert_parsed(&mut self, key: String, value: String, policy: DuplicateKeyPolicy) {
+        let Some(previous) = self.values.get(&key) else {
+            self.values.insert(key, value);
+            return;
+        };
+        match policy {
+            DuplicateKeyPolicy::Last => {
+                self.values.insert(key, value);
+            }
+            DuplicateKeyPolicy::First => {}
+            DuplicateKeyPolicy::Array => {
+                let previous = previous.clone();
+                self.repeated.entry(key.clone()).or_insert_with(|| vec![previous]).push(value.clone());
+                self.values.insert(key, value);
+            }
+        }
+    }
+
+    /// Sets `key` to a single value, replacing any repeated values it had.
+    pub fn insert(&mut self, key: String, value: String) -> Option<String> {
+        self.repeated.remove(&key);
+        self.values.insert(key, value)
+    }
+
+    /// Removes `key` and any repeated values it had, returning its value.
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        self.repeated.remove(key);
+        self.values.remove(key)
+    }
+
+    /// Keeps only the keys for which `keep` returns true.
+    pub fn retain(&mut self, mut keep: impl FnMut(&String, &mut String) -> bool) {
+        self.values.retain(|key, value| keep(key, value));
+        let values = &self.values;
+        self.repeated.retain(|key, _| values.contains_key(key));
+    }
+
+    /// Every stored value, including each value of a repeated key, for rewriting in place.
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut String> {
+        self.values.values_mut().chain(self.repeated.values_mut().flatten())
+    }
+
+    /// Every value of `key` in line order, if the line repeated it under `DuplicateKeyPolicy::Array`.
+    pub fn repeated(&self, key: &str) -> Option<&[String]> {
+        self.repeated.get(key).map(Vec::as_slice)
+    }
+}
+
+impl Deref for Details {
+    type Target = BTreeMap<String, String>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.values
+    }
+}
+
+impl DerefMut for Details {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.values
+    }
+}
+
+impl From<BTreeMap<String, String>> for Details {
+    fn from(values: BTreeMap<String, String>) -> Self {
+        Details { values, repeated: BTreeMap::new() }
+    }
+}
+
+impl Serialize for Details {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.values.len()))?;
+        for (key, value) in &self.values {
+            match self.repeated.get(key) {
+                Some(all) => map.serialize_entry(key, all)?,
+                None => map.serialize_entry(key, value)?,
+            }
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Details {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut details = Details::new();
+        for (key, value) in BTreeMap::<String, DetailRepr>::deserialize(deserializer)? {
+            match value {
+                DetailRepr::One(value) => {
+                    details.values.insert(key, value);
+                }
+                DetailRepr::Many(all) => {
+                    let Some(last) = all.last() else { continue };
+                    details.values.insert(key.clone(), last.clone());
+                    details.repeated.insert(key, all);
+                }
+            }
+        }
+        Ok(details)
+    }
+}
+
+// --- UNIT TESTS ---
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_line, LineParser};
+
+    const LINE: &str = "WARN [11-08|10:49:09] Dropping peers peer=a reason=timeout peer=b peer=c";
+
+    fn parse_with(policy: DuplicateKeyPolicy) -> Details {
+        let parser = LineParser { on_duplicate_key: policy, ..LineParser::new(2025) };
+        parser.parse(LINE).unwrap().details
+    }
+
+    #[test]
+    fn test_duplicate_key_last_and_first() {
+        let last = parse_with(DuplicateKeyPolicy::Last);
+        let first = parse_with(DuplicateKeyPolicy::First);
+
+        assert_eq!(last["peer"], "c");
+        assert_eq!(first["peer"], "a");
+        assert_eq!(serde_json::to_value(&last).unwrap()["peer"], "c");
+        assert_eq!(last.repeated("peer"), None);
+        assert_eq!(parse_line(LINE, 2025).unwrap().details, last);
+    }
+
+    #[test]
+    fn test_duplicate_key_array_round_trips() {
+        let details = parse_with(DuplicateKeyPolicy::Array);
+
+        let json = serde_json::to_value(&details).unwrap();
+        let reparsed: Details = serde_json::from_value(json.clone()).unwrap();
+
+        assert_eq!(json, serde_json::json!({ "peer": ["a", "b", "c"], "reason": "timeout" }));
+        assert_eq!(details["peer"], "c");
+        assert_eq!(details.repeated("peer"), Some(["a", "b", "c"].map(String::from).as_slice()));
+        assert_eq!(reparsed, details);
+    }
+
+    #[test]
+    fn test_overwriting_a_repeated_key_drops_its_array() {
+        let mut details = parse_with(DuplicateKeyPolicy::Array);
+
+        details.insert("peer".to_string(), "d".to_string());
+
+        assert_eq!(serde_json::to_value(&details).unwrap()["peer"], "d");
+        assert_eq!(details.repeated("peer"), None);
+    }
+}
@@ -0,0 +1,182 @@
+//! Network destinations for `--output`: `tcp://host:port` and `unix:/path/to.sock`.
+
+// --- IMPORTS ---
+// Standard library imports
+use std::fmt;
+use std::io::{self, Write};
+use std::net::TcpStream;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+// --- DATA STRUCTURES ---
+/// A stream socket that entries can be written to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Endpoint {
+    Tcp(String),    // `host:port` to connect to
+    Unix(PathBuf),  // Path of a Unix domain socket
+}
+
+/// A connected endpoint. On a write error it either fails, or reconnects and retries when
+/// `reconnect` is set.
+pub struct EndpointWriter {
+    endpoint: Endpoint,           // Where to (re)connect
+    stream: Box<dyn Write>,       // The current connection
+    reconnect: bool,              // Reconnect on write errors instead of failing (`--reconnect`)
+}
+
+// --- GLOBAL VARIABLES ---
+/// Connection attempts made after a write error under `--reconnect`, before giving up.
+const RECONNECT_ATTEMPTS: u32 = 5;
+
+/// Pause before each reconnection attempt, doubled after every failed one.
+const RECONNECT_DELAY: Duration = Duration::from_millis(500);
+
+// --- IMPLEMENTATIONS ---
+impl Endpoint {
+    /// Recognizes a `tcp://host:port` or `unix:/path` output destination; anything else is a file path.
+    pub fn parse(destination: &str) -> Option<Endpoint> {
+        if let Some(address) = destination.strip_prefix("tcp://") {
+            return Some(Endpoint::Tcp(address.trim_end_matches('/').to_string()));
+        }
+        destination.strip_prefix("unix:").map(|path| Endpoint::Unix(PathBuf::from(path)))
+    }
+
+    /// Opens a new connection.
+    fn connect(&self) -> io::Result<Box<dyn Write>> {
+        match self {
+            Endpoint::Tcp(address) => Ok(Box::new(TcpStream::connect(address)?)),
+            #[cfg(unix)]
+            Endpoint::Unix(path) => Ok(Box::new(UnixStream::connect(path)?)),
+            #[cfg(not(unix))]
+            Endpoint::Unix(_) => Err(io::Error::new(io::ErrorKind::Unsupported, "Unix sockets need a Unix platform")),
+        }
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Endpoint::Tcp(address) => write!(f, "tcp://{}", address),
+            Endpoint::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+impl EndpointWriter {
+    /// Connects to `endpoint`. Failing to connect is always an error, even with `reconnect`.
+    pub fn connect(endpoint: Endpoint, reconnect: bool) -> Result<Self, Box<dyn std::error::Error>> {
+        let stream = endpoint.connect().map_err(|e| format!("Error: Could not connect to '{}': {}", endpoint, e))?;
+        Ok(EndpointWriter { endpoint, stream, reconnect })
+    }
+
+    /// Replaces a broken connection, retrying with a growing delay. Returns the last error if
+    /// every attempt fails, or `error` itself when reconnecting is off.
+    fn recover(&mut self, error: io::Error) -> io::Result<()> {
+        if !self.reconnect {
+            return Err(error);
+        }
+        eprintln!("Warning: Lost connection to '{}' ({}); reconnecting", self.endpoint, error);
+        let mut delay = RECONNECT_DELAY;
+        let mut last_error = error;
+        for _ in 0..RECONNECT_ATTEMPTS {
+            thread::sleep(delay);
+            match self.endpoint.connect() {
+                Ok(stream) => {
+                    self.stream = stream;
+                    return Ok(());
+                }
+                Err(e) => last_error = e,
+            }
+            delay *= 2;
+        }
+        Err(io::Error::new(
+            last_error.kind(),
+            format!("could not reconnect to '{}' after {} attempts: {}", self.endpoint, RECONNECT_ATTEMPTS, last_error),
+        ))
+    }
+}
+
+impl Write for EndpointWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.stream.write(buf) {
+            Ok(written) => Ok(written),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => Err(e),
+            Err(e) => {
+                self.recover(e)?;
+                self.stream.write(buf)
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.stream.flush() {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                self.recover(e)?;
+                self.stream.flush()
+            }
+        }
+    }
+}
+
+// --- UNIT TESTS ---
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_parse_recognizes_socket_destinations() {
+        assert_eq!(Endpoint::parse("tcp://127.0.0.1:9000"), Some(Endpoint::Tcp("127.0.0.1:9000".to_string())));
+        assert_eq!(Endpoint::parse("unix:/run/collector.sock"), Some(Endpoint::Unix(PathBuf::from("/run/collector.sock"))));
+        assert_eq!(Endpoint::parse("parsed.ndjson"), None);
+        assert_eq!(Endpoint::Tcp("localhost:9000".to_string()).to_string(), "tcp://localhost:9000");
+    }
+
+    #[test]
+    fn test_tcp_writer_delivers_lines() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+
+        let mut writer = EndpointWriter::connect(Endpoint::Tcp(address), false).unwrap();
+        let (connection, _) = listener.accept().unwrap();
+        writer.write_all(b"{\"level\":\"INFO\"}\n").unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+
+        let lines: Vec<String> = BufReader::new(connection).lines().map(Result::unwrap).collect();
+        assert_eq!(lines, ["{\"level\":\"INFO\"}"]);
+    }
+
+    #[test]
+    fn test_connect_failure_names_the_endpoint() {
+        // Bind then drop a listener so the port is very likely closed.
+        let address = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().to_string();
+
+        let error = EndpointWriter::connect(Endpoint::Tcp(address.clone()), true).err().unwrap();
+
+        assert!(error.to_string().starts_with(&format!("Error: Could not connect to 'tcp://{}'", address)));
+    }
+
+    #[test]
+    fn test_recover_reconnects_only_when_enabled() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = Endpoint::Tcp(listener.local_addr().unwrap().to_string());
+        let mut strict = EndpointWriter::connect(endpoint.clone(), false).unwrap();
+        let mut resilient = EndpointWriter::connect(endpoint, true).unwrap();
+        drop((listener.accept().unwrap(), listener.accept().unwrap()));
+
+        assert!(strict.recover(io::Error::other("connection reset")).is_err());
+        resilient.recover(io::Error::other("connection reset")).unwrap();
+        let (connection, _) = listener.accept().unwrap();
+        resilient.write_all(b"after reconnect\n").unwrap();
+        drop(resilient);
+
+        let lines: Vec<String> = BufReader::new(connection).lines().map(Result::unwrap).collect();
+        assert_eq!(lines, ["after reconnect"]);
+    }
+}
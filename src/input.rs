@@ -0,0 +1,346 @@
+//! Opening log inputs: files (plain, memory-mapped, gzip or zstd), stdin, and growing files
+//! followed with `--follow`.
+
+// --- IMPORTS ---
+// Standard library imports
+use std::path::{Path, PathBuf};
+use std::fs::{self, File};
+use std::io::{self, BufRead, Read};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+// Third-party libraries
+use chrono::Datelike;
+use memmap2::Mmap;
+use flate2::read::MultiGzDecoder;
+
+// Local crate
+use geth_log_cruncher::{LineParser, TimestampVariant};
+use crate::STDIN_PATH;
+
+// --- DATA STRUCTURES ---
+/// An opened log source along with its size in bytes, when known.
+pub struct LogInput<'a> {
+    pub reader: Box<dyn BufRead + 'a>, // Line reader over the (possibly decompressed) input
+    pub total_bytes: Option<u64>,  // Byte total for the progress bar; `None` for streams
+}
+
+/// A reader over a growing log file for `--follow`, which waits for more data at end of file.
+///
+/// End of file is only reported once `stop` is set (by Ctrl-C). If the file shrinks below what
+/// has been read, it was truncated or rotated, so it is reopened and read from the start.
+pub struct FollowReader {
+    path: PathBuf,            // Path reopened after truncation or rotation
+    file: File,               // Currently open handle
+    position: u64,            // Bytes read from the current handle
+    stop: Arc<AtomicBool>,    // Set to end the stream at the next end of file
+}
+
+/// Retries failed reads, such as transient errors on a network filesystem, with a growing pause.
+/// After `retries` consecutive failures the last error is returned; any successful read resets
+/// the count.
+pub struct RetryReader<R: Read> {
+    inner: R,           // The reader whose errors are retried
+    name: String,       // Input name for the retry warnings
+    retries: u32,       // Consecutive failures retried before giving up
+    delay: Duration,    // Pause before the first retry
+    quiet: bool,        // Don't warn about retries (`--quiet`)
+}
+
+// --- GLOBAL VARIABLES ---
+/// Leading bytes that identify a gzip stream.
+pub const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Leading bytes that identify a zstd frame.
+pub const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// How long `--follow` waits at the end of the file before checking for new lines.
+pub const FOLLOW_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Pause before retrying a failed `--follow` read, doubled after every further failure.
+pub const FOLLOW_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Longest pause between `--follow` read retries.
+pub const FOLLOW_RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
+
+// --- IMPLEMENTATIONS ---
+impl FollowReader {
+    /// Opens `path` for following. Compressed files are rejected since they can't be appended to.
+    pub fn open(path: &Path, stop: Arc<AtomicBool>) -> Result<Self, Box<dyn std::error::Error>> {
+        if path.extension().is_some_and(|ext| ext == "gz" || ext == "zst" || ext == "zstd") {
+            return Err(format!("Error: Cannot --follow compressed file '{}'", path.display()).into());
+        }
+        let file = File::open(path)?;
+        Ok(FollowReader { path: path.to_path_buf(), file, position: 0, stop })
+    }
+}
+
+impl Read for FollowReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let bytes_read = self.file.read(buf)?;
+            if bytes_read > 0 {
+                self.position += bytes_read as u64;
+                return Ok(bytes_read);
+            }
+            if self.stop.load(Ordering::Relaxed) {
+                return Ok(0);
+            }
+
+            // The file may be briefly missing mid-rotation; keep waiting on the old handle.
+            let shrunk = fs::metadata(&self.path).is_ok_and(|metadata| metadata.len() < self.position);
+            if shrunk {
+                self.file = File::open(&self.path)?;
+                self.position = 0;
+                continue;
+            }
+            thread::sleep(FOLLOW_POLL_INTERVAL);
+        }
+    }
+}
+
+impl<R: Read> RetryReader<R> {
+    /// Wraps `inner`, pausing `FOLLOW_RETRY_DELAY` before the first retry.
+    pub fn new(inner: R, name: &str, retries: u32, quiet: bool) -> Self {
+        RetryReader { inner, name: name.to_string(), retries, delay: FOLLOW_RETRY_DELAY, quiet }
+    }
+}
+
+impl<R: Read> Read for RetryReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut delay = self.delay;
+        let mut failures = 0;
+        loop {
+            match self.inner.read(buf) {
+                Ok(bytes_read) => return Ok(bytes_read),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => return Err(e),
+                Err(e) if failures == self.retries => return Err(e),
+                Err(e) => {
+                    failures += 1;
+                    if !self.quiet {
+                        eprintln!(
+                            "Warning: Could not read '{}' ({}); retry {} of {} in {:?}",
+                            self.name, e, failures, self.retries, delay
+                        );
+                    }
+                    thread::sleep(delay);
+                    delay = (delay * 2).min(FOLLOW_RETRY_MAX_DELAY);
+                }
+            }
+        }
+    }
+}
+
+// --- HELPER FUNCTIONS ---
+/// Opens one input path, reading stdin for `-`.
+///
+/// When `stdin_buffer` is given, stdin is replayed from it instead of read live. `mmap` maps
+/// plain files into memory (`--mmap`); stdin is always read as a stream.
+pub fn open_input<'a>(
+    path: &str,
+    stdin_buffer: Option<&'a [u8]>,
+    mmap: bool,
+) -> Result<LogInput<'a>, Box<dyn std::error::Error>> {
+    if path != STDIN_PATH {
+        return open_log_file(Path::new(path), mmap);
+    }
+
+    match stdin_buffer {
+        Some(buffer) => Ok(LogInput { reader: Box::new(buffer), total_bytes: Some(buffer.len() as u64) }),
+        None => Ok(LogInput { reader: Box::new(io::stdin().lock()), total_bytes: None }),
+    }
+}
+
+/// Reads up to `lines` lines of `input` (`--year-probe`) looking for a timestamp with a full date,
+/// returning the input, still positioned at its start, and that timestamp's year, or `fallback` if
+/// none turns up. Without `lines`, the input is returned as it is.
+pub fn probe_year<'a>(
+    input: LogInput<'a>,
+    parser: &LineParser,
+    lines: Option<usize>,
+    fallback: i32,
+) -> io::Result<(LogInput<'a>, i32)> {
+    let Some(lines) = lines else {
+        return Ok((input, fallback));
+    };
+    let LogInput { mut reader, total_bytes } = input;
+    let mut probed = Vec::new();
+    let mut year = None;
+    for _ in 0..lines {
+        let start = probed.len();
+        if reader.read_until(b'\n', &mut probed)? == 0 {
+            break;
+        }
+        if let Some(entry) = parser.parse(&String::from_utf8_lossy(&probed[start..]))
+            && entry.timestamp_variant.is_some_and(TimestampVariant::has_year)
+        {
+            year = Some(entry.timestamp.year());
+            break;
+        }
+    }
+    // The probed lines are replayed before the rest, so they're still processed.
+    let reader = Box::new(io::Cursor::new(probed).chain(reader));
+    Ok((LogInput { reader, total_bytes }, year.unwrap_or(fallback)))
+}
+
+/// Opens a log file for reading, transparently decompressing gzip and zstd input.
+///
+/// The format is detected by extension (`.gz`, `.zst`) or leading magic bytes. Every member of a
+/// concatenated gzip file is read. Compressed files have an unknown decompressed size, so they
+/// report no byte total and get a spinner instead of a percentage bar.
+///
+/// With `mmap`, an uncompressed regular file is memory-mapped, so lines are found in the mapped
+/// bytes without a `read` call per buffer. Compressed and special files are read as usual.
+pub fn open_log_file(path: &Path, mmap: bool) -> Result<LogInput<'static>, Box<dyn std::error::Error>> {
+    let file = File::open(path)?;
+    let total_bytes = file.metadata()?.len();
+    let mut reader = io::BufReader::new(file);
+
+    let extension = path.extension().and_then(|ext| ext.to_str());
+    let head = reader.fill_buf()?;
+    let is_gzip = extension == Some("gz") || head.starts_with(&GZIP_MAGIC);
+    let is_zstd = matches!(extension, Some("zst" | "zstd")) || head.starts_with(&ZSTD_MAGIC);
+    if is_gzip {
+        let reader = Box::new(io::BufReader::new(MultiGzDecoder::new(reader)));
+        return Ok(LogInput { reader, total_bytes: None });
+    }
+    if is_zstd {
+        let reader = Box::new(io::BufReader::new(zstd::Decoder::with_buffer(reader)?));
+        return Ok(LogInput { reader, total_bytes: None });
+    }
+    if mmap && reader.get_ref().metadata()?.is_file() {
+        // SAFETY: the map is only read. A file truncated by another process while mapped can
+        // fault the read, which is why mapping is opt-in.
+        let map = unsafe { Mmap::map(reader.get_ref())? };
+        return Ok(LogInput { reader: Box::new(io::Cursor::new(map)), total_bytes: Some(total_bytes) });
+    }
+
+    Ok(LogInput { reader: Box::new(reader), total_bytes: Some(total_bytes) })
+}
+
+// --- UNIT TESTS ---
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use std::process;
+
+    #[test]
+    fn test_multi_member_gzip_and_zstd_inputs_decompress_fully() {
+        let first = "INFO [11-08|10:49:09] Imported new block headers count=1\n";
+        let second = "WARN [11-08|10:49:10] Stale peer id=abc\n";
+        let dir = std::env::temp_dir();
+        let gz_path = dir.join(format!("glc-test-{}-members.log.gz", process::id()));
+        let zst_path = dir.join(format!("glc-test-{}.log.zst", process::id()));
+        let unlabeled_zst_path = dir.join(format!("glc-test-{}-zst.log", process::id()));
+
+        // Two gzip members back to back, as `cat a.gz b.gz` produces.
+        let mut members = Vec::new();
+        for part in [first, second] {
+            let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(part.as_bytes()).unwrap();
+            members.extend(encoder.finish().unwrap());
+        }
+        std::fs::write(&gz_path, members).unwrap();
+        let compressed = zstd::encode_all(format!("{}{}", first, second).as_bytes(), 0).unwrap();
+        std::fs::write(&zst_path, &compressed).unwrap();
+        std::fs::write(&unlabeled_zst_path, &compressed).unwrap();
+
+        let read_all = |path: &Path| {
+            let mut contents = String::new();
+            let mut input = open_log_file(path, false).unwrap();
+            assert_eq!(input.total_bytes, None);
+            input.reader.read_to_string(&mut contents).unwrap();
+            contents
+        };
+        let decoded = [read_all(&gz_path), read_all(&zst_path), read_all(&unlabeled_zst_path)];
+        for path in [&gz_path, &zst_path, &unlabeled_zst_path] {
+            std::fs::remove_file(path).unwrap();
+        }
+
+        for contents in decoded {
+            assert_eq!(contents, format!("{}{}", first, second));
+        }
+    }
+
+    #[test]
+    fn test_follow_reader_reads_appended_and_truncated_data() {
+        let path = std::env::temp_dir().join(format!("glc-follow-{}.log", std::process::id()));
+        fs::write(&path, "first line\n").unwrap();
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut reader = io::BufReader::new(FollowReader::open(&path, Arc::clone(&stop)).unwrap());
+        let mut line = String::new();
+
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(line, "first line\n");
+
+        OpenOptions::new().append(true).open(&path).unwrap().write_all(b"appended\n").unwrap();
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(line, "appended\n");
+
+        fs::write(&path, "rotated\n").unwrap();
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(line, "rotated\n");
+
+        stop.store(true, Ordering::Relaxed);
+        line.clear();
+        assert_eq!(reader.read_line(&mut line).unwrap(), 0);
+        fs::remove_file(&path).unwrap();
+    }
+
+    /// Fails its first `failures` reads, then reads from `data`.
+    struct FlakyReader<'a> {
+        failures: u32,
+        data: &'a [u8],
+    }
+
+    impl Read for FlakyReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.failures > 0 {
+                self.failures -= 1;
+                return Err(io::Error::other("stale NFS file handle"));
+            }
+            self.data.read(buf)
+        }
+    }
+
+    #[test]
+    fn test_retry_reader_recovers_from_transient_errors_then_gives_up() {
+        let line = b"INFO [11-08|10:00:01] Imported block\n";
+        let retrying = |failures, retries| {
+            let flaky = FlakyReader { failures, data: line };
+            let mut reader = RetryReader { delay: Duration::from_millis(1), ..RetryReader::new(flaky, "geth.log", retries, true) };
+            let mut contents = Vec::new();
+            reader.read_to_end(&mut contents).map(|_| contents)
+        };
+
+        assert_eq!(retrying(3, 3).unwrap(), line);
+        assert_eq!(retrying(0, 0).unwrap(), line);
+        assert_eq!(retrying(4, 3).unwrap_err().to_string(), "stale NFS file handle");
+    }
+
+    #[test]
+    fn test_year_probe_finds_full_date_in_mixed_formats() {
+        let input = "INFO [11-08|10:00:01] Starting Geth\n\
+                     INFO [11-08|10:00:02] Maximum peer count\n\
+                     WARN [2023-11-08 10:00:03] Snapshot extension registration failed\n\
+                     INFO [11-08|10:00:04] Imported block\n";
+        let parser = LineParser::new(2025);
+        let probe = |lines| {
+            let input = LogInput { reader: Box::new(input.as_bytes()), total_bytes: None };
+            let (mut input, year) = probe_year(input, &parser, lines, 2025).unwrap();
+            let mut replayed = String::new();
+            input.reader.read_to_string(&mut replayed).unwrap();
+            (year, replayed)
+        };
+
+        assert_eq!(probe(Some(10)), (2023, input.to_string()));
+        assert_eq!(probe(Some(2)), (2025, input.to_string()));
+        assert_eq!(probe(None).0, 2025);
+    }
+}
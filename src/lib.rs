@@ -0,0 +1,1536 @@
+//! Parsing core for Geth Log Cruncher.
+//!
+//! Turns raw Geth log lines into structured [`LogEntry`] values that serialize to JSON.
+//!
+//! ```
+//! let entry = geth_log_cruncher::parse_line(
+//!     "INFO [11-08|10:49:09.123] Imported new block headers count=1 elapsed=2.5s",
+//!     2025,
+//! )
+//! .expect("valid geth log line");
+//!
+//! assert_eq!(entry.level, "INFO");
+//! assert_eq!(entry.details["count"], "1");
+//! ```
+
+// --- MODULES ---
+pub mod anonymize;
+pub mod details;
+pub mod output;
+pub mod script;
+pub mod stats;
+
+// --- IMPORTS ---
+// Standard library imports
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+// Third-party libraries
+use chrono::{DateTime, Datelike, FixedOffset, Local, NaiveDateTime, Offset, TimeZone, Utc};
+use clap::ValueEnum;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+// Local crate
+use details::{Details, DuplicateKeyPolicy};
+
+// --- DATA STRUCTURES ---
+/// Represents a structured log entry parsed from the input file.
+///
+/// Deserializing accepts this crate's own JSON output, so crunched logs can be re-processed.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LogEntry {
+    #[serde(serialize_with = "serialize_level", deserialize_with = "deserialize_level")]
+    pub level: String,                     // Log level (e.g., INFO, WARN, ERROR), serialized per `set_level_format`
+    #[serde(serialize_with = "serialize_timestamp", deserialize_with = "deserialize_timestamp")]
+    pub timestamp: DateTime<Local>,        // Log timestamp, serialized per `set_time_format` and `set_output_offset`
+    pub message: String,                   // Main log message
+    #[serde(default, skip_serializing_if = "omit_details")]
+    pub details: Details,                  // Key-value pairs extracted from the message, sorted by key
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metrics: BTreeMap<String, f64>,    // Numeric values of the parser's `numeric_keys` found in `details`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_number: Option<u64>,         // Block number from a `number=` pair, when numeric
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peer_id: Option<String>,           // Peer identifier from a `peer=` or `id=` pair
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,              // `hash=` value, lowercased, when it's a well-formed 32-byte hex hash
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub elapsed_ms: Option<u64>,           // `elapsed=` duration in whole milliseconds, when it parses
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub age_sec: Option<f64>,              // `age=` duration in seconds, when it parses
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub progress_pct: Option<f64>,         // Sync percentage from a `synced=` or `progress=` pair, without the `%`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pending: Option<u64>,              // Txpool `pending=` transaction count, when numeric
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub queued: Option<u64>,               // Txpool `queued=` transaction count, when numeric
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event: Option<String>,             // Classification of a notable message, e.g. `reorg` (see [`classify_event`])
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reorg_depth: Option<u64>,          // Blocks dropped by a reorg, from its `drop=` pair
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enode: Option<String>,             // First `enode://` URL mentioned in the message
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub details_truncated: bool,           // More `key=value` pairs followed than the parser's `max_details`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repeat_count: Option<usize>,       // Consecutive duplicates collapsed into this entry (`--dedup`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub continuation: Vec<String>,         // Following non-log lines, e.g. a stack trace (`--join-continuations`)
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub raw: String,                       // Trimmed source line this entry was parsed from (`--keep-raw`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,               // 1-based number of the source line this entry was parsed from (`--with-line-number`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,              // Input the entry came from, when there are several (`--with-line-number`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weekday: Option<String>,           // Day of the week of `timestamp`, e.g. `Mon` (`--enrich-time`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iso_week: Option<u32>,             // ISO 8601 week number of `timestamp` (`--enrich-time`)
+    #[serde(skip)]
+    pub timestamp_variant: Option<TimestampVariant>, // Which timestamp format the line used; `None` for JSON input
+}
+
+/// The timestamp formats [`parse_timestamp`] accepts, told apart to spot logs stitched together
+/// from different geth versions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TimestampVariant {
+    Bracket,        // `MM-DD|HH:MM:SS`, as older geth versions print it
+    BracketMillis,  // `MM-DD|HH:MM:SS.fff`
+    Rfc3339,        // `2024-07-04T15:23:01.123+02:00`
+    LogfmtOffset,   // RFC3339 with a colon-less offset (`+0000`), as geth's logfmt backend writes it
+    DateTime,       // `YYYY-MM-DD HH:MM:SS[.fff]` in local time
+}
+
+/// How each input line is interpreted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum InputFormat {
+    #[default]
+    Raw,   // Geth log text, matched against the line regex
+    Json,  // One `LogEntry` JSON object per line, as emitted by this tool
+}
+
+/// How `LogEntry.timestamp` is serialized (see [`set_time_format`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum TimeFormat {
+    #[default]
+    Rfc3339,  // RFC3339 string with the local offset, e.g. `2025-11-08T10:49:09.123+01:00`
+    EpochMs,  // Integer milliseconds since the Unix epoch
+    EpochS,   // Integer seconds since the Unix epoch, truncating any fraction
+}
+
+/// Letter case of `LogEntry.level` when serialized (see [`set_level_format`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum LevelCase {
+    #[default]
+    Upper,  // `INFO`, as geth writes it
+    Lower,  // `info`
+}
+
+/// Length of `LogEntry.level` when serialized (see [`set_level_format`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum LevelStyle {
+    #[default]
+    Full,   // The whole name, e.g. `WARN`
+    Short,  // Its first letter, e.g. `W`
+}
+
+/// Timestamp as read from entry JSON: an RFC3339 string or an integer epoch.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TimestampRepr {
+    Text(DateTime<Local>),
+    Epoch(i64),
+}
+
+/// A configurable log line parser. [`parse_line`] is shorthand for the default configuration.
+#[derive(Debug, Clone)]
+pub struct LineParser {
+    pub year: i32,                    // Year used to complete bracketed `MM-DD|HH:MM:SS` timestamps
+    pub pattern: Option<Regex>,       // Replacement for the built-in line regex (see `compile_log_pattern`)
+    pub input_format: InputFormat,    // Raw geth text or previously crunched JSON
+    pub numeric_keys: Vec<String>,    // `details` keys also parsed into `metrics` (see `DEFAULT_NUMERIC_KEYS`)
+    pub utc: bool,                    // Read timestamps without an offset as UTC instead of local time
+    pub tz: Option<FixedOffset>,      // Read timestamps without an offset at this offset; overrides `utc`
+    pub on_duplicate_key: DuplicateKeyPolicy, // Which value of a key repeated within a line is kept
+    pub max_details: Option<usize>,   // Most `key=value` pairs extracted per line; `None` for no limit
+}
+
+/// Why [`LineParser::parse`] rejected a line, as reported by [`LineParser::diagnose`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseFailure {
+    NoMatch,                       // Neither the line regex nor the logfmt fallback recognized the line
+    BadTimestamp(String),          // The line matched, but its extracted timestamp didn't parse
+    MissingLogfmtKey(&'static str), // A logfmt line lacked one of the required `t`, `lvl` or `msg` keys
+    InvalidJson(String),           // `InputFormat::Json` line that isn't entry JSON, with serde's message
+}
+
+/// Tracks the month across consecutive entries so a log spanning New Year gets the right year.
+///
+/// Year-less `MM-DD|HH:MM:SS` timestamps are all completed with one starting year; whenever the
+/// month goes backwards (e.g. December to January), later entries are moved into the next year.
+/// Timestamps carrying their own year are left alone and re-anchor the year-less ones after them.
+#[derive(Debug, Default, Clone)]
+pub struct YearRollover {
+    last_month: Option<u32>,  // Month of the previous entry
+    years_added: i32,         // Rollovers seen since the anchor (or the start), added to later year-less entries
+    anchor_year: Option<i32>, // Year of the last entry that carried one, replacing the starting year
+}
+
+/// Log severity levels, ordered from least to most severe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+#[value(rename_all = "UPPER")]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    /// Maps a level string as it appears in a log line to a `LogLevel`.
+    pub fn from_level_str(level: &str) -> Option<LogLevel> {
+        match level {
+            "TRACE" => Some(LogLevel::Trace),
+            "DEBUG" => Some(LogLevel::Debug),
+            "INFO" => Some(LogLevel::Info),
+            "WARN" => Some(LogLevel::Warn),
+            "ERROR" => Some(LogLevel::Error),
+            _ => None,
+        }
+    }
+
+    /// Returns the level name as it appears in log lines.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LogLevel::Trace => "TRACE",
+            LogLevel::Debug => "DEBUG",
+            LogLevel::Info => "INFO",
+            LogLevel::Warn => "WARN",
+            LogLevel::Error => "ERROR",
+        }
+    }
+}
+
+impl TimestampVariant {
+    /// Returns the variant's name, as reported in the run summary.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TimestampVariant::Bracket => "bracket",
+            TimestampVariant::BracketMillis => "bracket-millis",
+            TimestampVariant::Rfc3339 => "rfc3339",
+            TimestampVariant::LogfmtOffset => "logfmt-offset",
+            TimestampVariant::DateTime => "datetime",
+        }
+    }
+
+    /// Returns true if the format carries its own year, unlike geth's `MM-DD|HH:MM:SS`.
+    pub fn has_year(self) -> bool {
+        !matches!(self, TimestampVariant::Bracket | TimestampVariant::BracketMillis)
+    }
+}
+
+impl LineParser {
+    /// Creates a parser using the built-in line regex and the given year.
+    pub fn new(year: i32) -> Self {
+        LineParser {
+            year,
+            pattern: None,
+            input_format: InputFormat::Raw,
+            numeric_keys: DEFAULT_NUMERIC_KEYS.iter().map(|key| key.to_string()).collect(),
+            utc: false,
+            tz: None,
+            on_duplicate_key: DuplicateKeyPolicy::Last,
+            max_details: None,
+        }
+    }
+
+    /// Parses a single log line into a `LogEntry` struct.
+    ///
+    /// See [`parse_timestamp`] for the accepted timestamp formats; `self.year` completes the
+    /// year-less `MM-DD|HH:MM:SS` form. Returns `None` if the line is not a recognizable log line.
+    ///
+    /// A message that starts with a `msg=` pair has that pair's value promoted to the message,
+    /// and removed from `details`.
+    ///
+    /// ANSI color codes, as in a log captured from geth's colored terminal output, are stripped first.
+    ///
+    /// With `InputFormat::Json`, the line is instead deserialized as a `LogEntry`, and `None`
+    /// means it wasn't valid entry JSON.
+    pub fn parse(&self, line: &str) -> Option<LogEntry> {
+        if self.input_format == InputFormat::Json {
+            return serde_json::from_str(line.trim()).ok();
+        }
+        let line = &*strip_ansi(line);
+
+        let log_regex = self.pattern.as_ref().unwrap_or(&LOG_REGEX);
+        if let Some(caps) = log_regex.captures(line) {
+            let (local_dt, variant) = parse_timestamp_as(&caps["timestamp"], self.year, self.naive_offset())?;
+
+            let mut message = caps["message"].to_string();
+            let mut details = Details::new();
+            let mut pairs = KV_REGEX.captures_iter(&message);
+            for kv_caps in pairs.by_ref().take(self.max_details.unwrap_or(usize::MAX)) {
+                details.insert_parsed(kv_caps["key"].to_string(), unquote_kv_value(&kv_caps["value"]), self.on_duplicate_key);
+            }
+            let truncated = pairs.next().is_some();
+
+            let enode = ENODE_REGEX.find(&message).map(|m| m.as_str().to_string());
+            // Some builds put the message itself in a leading `msg=` pair, e.g. `msg="Imported block" number=5`.
+            if message.starts_with("msg=")
+                && let Some(msg) = details.remove("msg")
+            {
+                message = msg;
+            }
+            let mut entry = self.build_entry(caps["level"].to_string(), local_dt, message, details, enode);
+            entry.details_truncated = truncated;
+            entry.timestamp_variant = Some(variant);
+            return Some(entry);
+        }
+
+        self.parse_logfmt(line).ok()
+    }
+
+    /// Explains why `parse` rejects `line`, or returns `None` if it parses.
+    ///
+    /// This repeats the parse, so it's meant for reporting on dropped lines, not the hot path.
+    pub fn diagnose(&self, line: &str) -> Option<ParseFailure> {
+        if self.input_format == InputFormat::Json {
+            return serde_json::from_str::<LogEntry>(line.trim()).err().map(|e| ParseFailure::InvalidJson(e.to_string()));
+        }
+        let line = &*strip_ansi(line);
+
+        let log_regex = self.pattern.as_ref().unwrap_or(&LOG_REGEX);
+        if let Some(caps) = log_regex.captures(line) {
+            let timestamp = &caps["timestamp"];
+            return parse_timestamp_as(timestamp, self.year, self.naive_offset())
+                .is_none()
+                .then(|| ParseFailure::BadTimestamp(timestamp.to_string()));
+        }
+
+        self.parse_logfmt(line).err()
+    }
+
+    /// The offset timestamps without one are read at: `tz`, else UTC when `utc` is set, else
+    /// `None` for the local timezone.
+    pub fn naive_offset(&self) -> Option<FixedOffset> {
+        self.tz.or_else(|| self.utc.then(|| Utc.fix()))
+    }
+
+    /// Parses a logfmt line such as `t=2024-07-04T15:23:01.123Z lvl=info msg="..." key=value`,
+    /// as written by some geth logging backends.
+    ///
+    /// `t`, `lvl` and `msg` are required and become the timestamp, level and message; every
+    /// other pair goes into `details`, up to `max_details` of them. A line with none of the three
+    /// is `ParseFailure::NoMatch`.
+    fn parse_logfmt(&self, line: &str) -> Result<LogEntry, ParseFailure> {
+        let mut details = Details::new();
+        let mut remaining = self.max_details.unwrap_or(usize::MAX);
+        let mut truncated = false;
+        for kv_caps in LOGFMT_REGEX.captures_iter(line) {
+            let key = &kv_caps["key"];
+            if !matches!(key, "t" | "lvl" | "msg") {
+                if remaining == 0 {
+                    truncated = true;
+                    continue;
+                }
+                remaining -= 1;
+            }
+            let value = &kv_caps["value"];
+            let value = match value.strip_prefix('"').and_then(|quoted| quoted.strip_suffix('"')) {
+                Some(quoted) => unescape_quoted(quoted),
+                None => value.to_string(),
+            };
+            details.insert_parsed(key.to_string(), value, self.on_duplicate_key);
+        }
+
+        if !["t", "lvl", "msg"].iter().any(|key| details.contains_key(*key)) {
+            return Err(ParseFailure::NoMatch);
+        }
+        let raw_timestamp = details.remove("t").ok_or(ParseFailure::MissingLogfmtKey("t"))?;
+        let level = normalize_logfmt_level(&details.remove("lvl").ok_or(ParseFailure::MissingLogfmtKey("lvl"))?);
+        let message = details.remove("msg").ok_or(ParseFailure::MissingLogfmtKey("msg"))?;
+        let (timestamp, variant) = parse_timestamp_as(&raw_timestamp, self.year, self.naive_offset())
+            .or_else(|| {
+                // Geth's logfmt offsets have no colon (`+0000`), which RFC3339 parsing rejects.
+                DateTime::parse_from_str(&raw_timestamp, "%Y-%m-%dT%H:%M:%S%.f%z")
+                    .ok()
+                    .map(|dt| (dt.with_timezone(&Local), TimestampVariant::LogfmtOffset))
+            })
+            .ok_or(ParseFailure::BadTimestamp(raw_timestamp))?;
+
+        let enode = ENODE_REGEX.find(line).map(|m| m.as_str().to_string());
+        let mut entry = self.build_entry(level, timestamp, message, details, enode);
+        entry.details_truncated = truncated;
+        entry.timestamp_variant = Some(variant);
+        Ok(entry)
+    }
+
+    /// Assembles a `LogEntry`, deriving the typed fields from `details`.
+    fn build_entry(
+        &self,
+        level: String,
+        timestamp: DateTime<Local>,
+        message: String,
+        details: Details,
+        enode: Option<String>,
+    ) -> LogEntry {
+        let metrics = self
+            .numeric_keys
+            .iter()
+            .filter_map(|key| Some((key.clone(), parse_metric(details.get(key)?)?)))
+            .collect();
+        let block_number = details.get("number").and_then(|number| number.parse().ok());
+        let peer_id = details.get("peer").or_else(|| details.get("id")).cloned();
+        let elapsed_ms = details
+            .get("elapsed")
+            .and_then(|elapsed| parse_go_duration(elapsed))
+            .map(|elapsed| elapsed.as_millis() as u64);
+        let age_sec = details.get("age").and_then(|age| parse_go_duration(age)).map(|age| age.as_secs_f64());
+        let progress_pct = details
+            .get("synced")
+            .or_else(|| details.get("progress"))
+            .and_then(|progress| parse_progress(progress));
+        let pending = details.get("pending").and_then(|pending| pending.parse().ok());
+        let queued = details.get("queued").and_then(|queued| queued.parse().ok());
+        let hash = details.get("hash").and_then(|hash| normalize_hash(hash));
+        let event = classify_event(&message);
+        let reorg_depth = (event == Some("reorg")).then(|| details.get("drop")?.parse().ok()).flatten();
+
+        LogEntry {
+            level,
+            timestamp,
+            message,
+            details,
+            metrics,
+            block_number,
+            peer_id,
+            hash,
+            elapsed_ms,
+            age_sec,
+            progress_pct,
+            pending,
+            queued,
+            event: event.map(str::to_string),
+            reorg_depth,
+            enode,
+            details_truncated: false,
+            repeat_count: None,
+            continuation: Vec::new(),
+            raw: String::new(),
+            line: None,
+            file: None,
+            weekday: None,
+            iso_week: None,
+            timestamp_variant: None,
+        }
+    }
+}
+
+impl LogEntry {
+    /// Returns true if the entry has a `hash=` value that is neither a full hash nor one of
+    /// geth's abbreviated `0x1234..abcd` forms, which usually points at corruption.
+    pub fn has_malformed_hash(&self) -> bool {
+        self.hash.is_none() && self.details.get("hash").is_some_and(|hash| !hash.contains(".."))
+    }
+
+    /// Sets `weekday` and `iso_week` from the timestamp, at the offset it is written with (see
+    /// [`output_timestamp`]).
+    pub fn enrich_time(&mut self) {
+        let date = output_timestamp(&self.timestamp).date_naive();
+        self.weekday = Some(date.weekday().to_string());
+        self.iso_week = Some(date.iso_week().week());
+    }
+}
+
+impl fmt::Display for ParseFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseFailure::NoMatch => write!(f, "no log line format matched"),
+            ParseFailure::BadTimestamp(timestamp) => write!(f, "timestamp '{}' did not parse", timestamp),
+            ParseFailure::MissingLogfmtKey(key) => write!(f, "logfmt line has no `{}` key", key),
+            ParseFailure::InvalidJson(error) => write!(f, "not a valid entry JSON object ({})", error),
+        }
+    }
+}
+
+impl YearRollover {
+    /// Moves `entry` into the working year, starting a new year first if its month went backwards.
+    ///
+    /// Returns true if this entry started a new year. An entry whose timestamp carries a year
+    /// (or JSON input, which always does) keeps it, and later year-less entries continue from it.
+    pub fn adjust(&mut self, entry: &mut LogEntry) -> bool {
+        let month = entry.timestamp.month();
+        if entry.timestamp_variant.is_none_or(|variant| variant.has_year()) {
+            self.anchor_year = Some(entry.timestamp.year());
+            self.years_added = 0;
+            self.last_month = Some(month);
+            return false;
+        }
+
+        let rolled_over = self.last_month.is_some_and(|last_month| month < last_month);
+        if rolled_over {
+            self.years_added += 1;
+        }
+        self.last_month = Some(month);
+
+        let year = self.anchor_year.unwrap_or(entry.timestamp.year()) + self.years_added;
+        if year != entry.timestamp.year()
+            && let Some(timestamp) = entry.timestamp.with_year(year)
+        {
+            entry.timestamp = timestamp;
+        }
+        rolled_over
+    }
+}
+
+impl Default for LineParser {
+    /// Uses the built-in line regex and the current year.
+    fn default() -> Self {
+        LineParser::new(Local::now().year())
+    }
+}
+
+// --- GLOBAL VARIABLES ---
+/// `details` keys parsed into `metrics` unless the parser is given its own list.
+pub const DEFAULT_NUMERIC_KEYS: [&str; 4] = ["gas", "gasused", "gasprice", "fees"];
+
+/// Message prefixes of notable geth events and the `event` they are classified as, checked in order.
+const EVENT_PREFIXES: [(&str, &str); 2] = [
+    ("Chain reorg detected", "reorg"),
+    ("Large chain reorg detected", "reorg"),
+];
+
+/// Integer timestamps at or above this are read as epoch milliseconds, below it as seconds.
+/// As seconds it would be the year 5138, so no real log is ambiguous.
+const EPOCH_MS_THRESHOLD: i64 = 100_000_000_000;
+
+thread_local! {
+    // Timestamp serialization format, per thread so concurrent writers (and tests) don't interfere
+    static TIME_FORMAT: Cell<TimeFormat> = const { Cell::new(TimeFormat::Rfc3339) };
+
+    // Offset timestamps are written at; `None` for the local offset
+    static OUTPUT_OFFSET: Cell<Option<FixedOffset>> = const { Cell::new(None) };
+
+    // Case and length levels are written with
+    static LEVEL_FORMAT: Cell<(LevelCase, LevelStyle)> = const { Cell::new((LevelCase::Upper, LevelStyle::Full)) };
+
+    // Whether an empty `details` object is left out of serialized entries
+    static OMIT_EMPTY_DETAILS: Cell<bool> = const { Cell::new(false) };
+}
+
+// Precompiled regex patterns for efficient log parsing.
+lazy_static! {
+    // Regex to capture the main components of a log line
+    static ref LOG_REGEX: Regex = Regex::new(
+        r"^(?P<level>INFO|WARN|ERROR|DEBUG|TRACE)\s*\[(?P<timestamp>.+?)\]\s+(?P<message>.*)"
+    ).unwrap();
+
+    // Regex to capture key-value pairs in the log message. Values may be double-quoted (with
+    // backslash escapes), single-quoted, backtick-quoted, or bare with backslash-escaped spaces.
+    static ref KV_REGEX: Regex =
+        Regex::new(r#"(?P<key>\w+)=(?P<value>"(?:[^"\\]|\\.)*"|'[^']*'|`[^`]*`|(?:\\\s|\S)+)"#).unwrap();
+
+    // Regex to capture logfmt pairs; quoted values may contain backslash-escaped characters
+    static ref LOGFMT_REGEX: Regex = Regex::new(r#"(?P<key>[\w.-]+)=(?P<value>"(?:[^"\\]|\\.)*"|\S*)"#).unwrap();
+
+    // Regex for a year in a file name: 4 digits not part of a longer number, except a `YYYYMMDD` date
+    static ref FILENAME_YEAR_REGEX: Regex = Regex::new(r"(?:^|\D)((?:19|20)\d{2})(?:\d{4})?(?:\D|$)").unwrap();
+
+    // Regex for a full 32-byte hash: `0x` followed by 64 hex digits
+    static ref HASH_REGEX: Regex = Regex::new(r"^0x[0-9a-fA-F]{64}$").unwrap();
+
+    // Regex to find enode URLs (`enode://<node id>@<host>:<port>[?discport=...]`) anywhere in a message
+    static ref ENODE_REGEX: Regex = Regex::new(r#"enode://[0-9a-fA-F]+@[^\s",]+"#).unwrap();
+
+    // Regex for an ANSI CSI escape sequence, e.g. the `\x1b[32m` color codes of a colored terminal log
+    static ref ANSI_CSI_REGEX: Regex = Regex::new(r"\x1b\[[0-?]*[ -/]*[@-~]").unwrap();
+}
+
+// --- PUBLIC API ---
+/// Returns the compiled regex for a full log line, with `level`, `timestamp` and `message` groups.
+pub fn log_regex() -> &'static Regex {
+    &LOG_REGEX
+}
+
+/// Returns the compiled regex for `key=value` pairs, with `key` and `value` groups.
+pub fn kv_regex() -> &'static Regex {
+    &KV_REGEX
+}
+
+/// Extracts a year from the file name of `path` (directories are ignored), e.g. `geth-2023-11.log`,
+/// `geth.2024.log.gz` or `20231105-geth.log`.
+///
+/// Returns the first match, or `None` if the name carries no plausible year.
+pub fn year_from_filename(path: &str) -> Option<i32> {
+    let file_name = std::path::Path::new(path).file_name()?.to_str()?;
+    FILENAME_YEAR_REGEX.captures(file_name)?[1].parse().ok()
+}
+
+/// Builds a representative `LogEntry` with every optional field populated, documenting the
+/// output format (`--print-schema`).
+pub fn sample_entry() -> LogEntry {
+    let line = format!(
+        "INFO [07-04|15:23:01.123] Imported new chain segment number=19876543 hash=0x{} \
+         peer=3f4a2b9c txs=12 gas=21000 fees=0.0021 elapsed=4.521s age=3m20s synced=62.31% pending=123 queued=45 \
+         enode=enode://3f4a2b9c@10.0.0.1:30303",
+        "5a".repeat(32)
+    );
+    let mut entry = LineParser::new(2024).parse(&line).expect("sample line matches the built-in regex");
+    entry.event = Some("reorg".to_string());
+    entry.reorg_depth = Some(2);
+    entry.details_truncated = true;
+    entry.repeat_count = Some(3);
+    entry.continuation = vec!["goroutine 1 [running]:".to_string(), "main.main()".to_string()];
+    entry.raw = line;
+    entry.line = Some(1042);
+    entry.file = Some("geth.log".to_string());
+    entry.enrich_time();
+    entry
+}
+
+/// Sets how `LogEntry.timestamp` is serialized on the current thread; the default is RFC3339.
+///
+/// Call this on the thread that writes the output.
+pub fn set_time_format(format: TimeFormat) {
+    TIME_FORMAT.with(|current| current.set(format));
+}
+
+/// Sets whether timestamps are written in UTC on the current thread; the default is local time.
+///
+/// Call this on the thread that writes the output.
+pub fn set_utc_output(utc: bool) {
+    set_output_offset(utc.then(|| Utc.fix()));
+}
+
+/// Sets the offset timestamps are written at on the current thread; `None` is local time.
+///
+/// Call this on the thread that writes the output.
+pub fn set_output_offset(offset: Option<FixedOffset>) {
+    OUTPUT_OFFSET.with(|current| current.set(offset));
+}
+
+/// Sets whether an empty `LogEntry.details` is left out when serializing on the current thread;
+/// by default it is written as `{}`.
+///
+/// Call this on the thread that writes the output.
+pub fn set_omit_empty_details(omit: bool) {
+    OMIT_EMPTY_DETAILS.with(|current| current.set(omit));
+}
+
+/// Sets how `LogEntry.level` is written on the current thread; the default is uppercase, full names.
+///
+/// Call this on the thread that writes the output. Filters always see the name as parsed.
+pub fn set_level_format(case: LevelCase, style: LevelStyle) {
+    LEVEL_FORMAT.with(|current| current.set((case, style)));
+}
+
+/// Returns `level` as it is written under [`set_level_format`], e.g. `WARN` as `w` for lower and short.
+pub fn output_level(level: &str) -> Cow<'_, str> {
+    let (case, style) = LEVEL_FORMAT.with(Cell::get);
+    let level = match style {
+        LevelStyle::Full => level,
+        LevelStyle::Short => level.char_indices().nth(1).map_or(level, |(end, _)| &level[..end]),
+    };
+    match case {
+        LevelCase::Upper => Cow::Borrowed(level),
+        LevelCase::Lower => Cow::Owned(level.to_lowercase()),
+    }
+}
+
+/// Converts a timestamp to the offset it is written with: the one set with [`set_output_offset`]
+/// (or UTC under [`set_utc_output`]), else local.
+pub fn output_timestamp(timestamp: &DateTime<Local>) -> DateTime<FixedOffset> {
+    match OUTPUT_OFFSET.with(Cell::get) {
+        Some(offset) => timestamp.with_timezone(&offset),
+        None => timestamp.fixed_offset(),
+    }
+}
+
+/// Serializes a timestamp in the current thread's [`TimeFormat`] and offset.
+pub fn serialize_timestamp<S: Serializer>(timestamp: &DateTime<Local>, serializer: S) -> Result<S::Ok, S::Error> {
+    match TIME_FORMAT.with(Cell::get) {
+        TimeFormat::Rfc3339 => output_timestamp(timestamp).serialize(serializer),
+        TimeFormat::EpochMs => serializer.serialize_i64(timestamp.timestamp_millis()),
+        TimeFormat::EpochS => serializer.serialize_i64(timestamp.timestamp()),
+    }
+}
+
+/// Parses a single log line into a `LogEntry` struct using the built-in line regex.
+///
+/// See [`LineParser::parse`] for details.
+pub fn parse_line(line: &str, year: i32) -> Option<LogEntry> {
+    LineParser::new(year).parse(line)
+}
+
+/// Compiles a custom log line regex, checking it has the named groups the parser relies on:
+/// `level`, `timestamp` and `message`.
+///
+/// The `timestamp` group is parsed with the same formats as the built-in regex (see [`parse_timestamp`]).
+pub fn compile_log_pattern(pattern: &str) -> Result<Regex, Box<dyn std::error::Error>> {
+    let regex = Regex::new(pattern).map_err(|e| format!("Error: Invalid log pattern: {}", e))?;
+
+    let group_names: Vec<&str> = regex.capture_names().flatten().collect();
+    let missing: Vec<&str> = ["level", "timestamp", "message"]
+        .into_iter()
+        .filter(|required| !group_names.contains(required))
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!(
+            "Error: Log pattern must define the named groups (?P<level>...), (?P<timestamp>...) and (?P<message>...); missing: {}",
+            missing.join(", ")
+        )
+        .into());
+    }
+
+    Ok(regex)
+}
+
+/// Removes ANSI CSI escape sequences (colors, cursor movement) from `line`, borrowing it when it has none.
+pub fn strip_ansi(line: &str) -> Cow<'_, str> {
+    if line.contains('\x1b') {
+        ANSI_CSI_REGEX.replace_all(line, "")
+    } else {
+        Cow::Borrowed(line)
+    }
+}
+
+/// Blanks out the values of `key=value` pairs so messages differing only in values compare equal.
+///
+/// For example, `Imported block number=5 txs=2` becomes `Imported block number= txs=`.
+pub fn normalize_message(message: &str) -> String {
+    KV_REGEX.replace_all(message, "${key}=").into_owned()
+}
+
+/// Removes the `key=value` pairs from a message, leaving its text.
+///
+/// For example, `Imported block                 number=5 txs=2` becomes `Imported block`.
+pub fn message_text(message: &str) -> String {
+    KV_REGEX.replace_all(message, "").trim_end().to_string()
+}
+
+/// Parses a log timestamp, trying each format geth is known to emit in order:
+///
+/// 1. `MM-DD|HH:MM:SS[.fff]`, completed with `year`
+/// 2. RFC3339 (e.g. `2024-07-04T15:23:01.123+02:00`), which carries its own year and offset
+/// 3. `YYYY-MM-DD HH:MM:SS[.fff]` in local time
+pub fn parse_timestamp(raw: &str, year: i32) -> Option<DateTime<Local>> {
+    parse_timestamp_as(raw, year, None).map(|(timestamp, _)| timestamp)
+}
+
+/// Maps a logfmt `lvl` value to the level names used in geth's terminal format.
+///
+/// Geth's 4-letter forms (`eror`, `dbug`, `trce`) are expanded; anything else is uppercased.
+pub fn normalize_logfmt_level(lvl: &str) -> String {
+    match lvl.to_ascii_lowercase().as_str() {
+        "trce" | "trace" => "TRACE".to_string(),
+        "dbug" | "debug" => "DEBUG".to_string(),
+        "info" => "INFO".to_string(),
+        "warn" | "warning" => "WARN".to_string(),
+        "eror" | "error" => "ERROR".to_string(),
+        other => other.to_ascii_uppercase(),
+    }
+}
+
+/// Parses a numeric `details` value, ignoring geth's thousands separators (e.g. `1,234,567`).
+///
+/// Returns `None` for non-numeric values, which stay only in `details`.
+pub fn parse_metric(raw: &str) -> Option<f64> {
+    raw.replace(',', "").parse::<f64>().ok().filter(|value| value.is_finite())
+}
+
+/// Returns `raw` lowercased if it is `0x` followed by exactly 64 hex digits, otherwise `None`.
+pub fn normalize_hash(raw: &str) -> Option<String> {
+    HASH_REGEX.is_match(raw).then(|| raw.to_ascii_lowercase())
+}
+
+/// Classifies a message as a notable event by its prefix, e.g. `Chain reorg detected` as `reorg`.
+pub fn classify_event(message: &str) -> Option<&'static str> {
+    EVENT_PREFIXES.iter().find(|(prefix, _)| message.starts_with(prefix)).map(|&(_, event)| event)
+}
+
+/// Parses a percentage as geth prints sync progress, e.g. `62.31%`; the `%` is optional.
+pub fn parse_progress(raw: &str) -> Option<f64> {
+    let value = raw.strip_suffix('%').unwrap_or(raw);
+    value.parse::<f64>().ok().filter(|value| value.is_finite())
+}
+
+/// Parses a Go-formatted duration as geth prints it, e.g. `340ms`, `4.521s` or `2m3.5s`.
+///
+/// Accepts one or more `<decimal><unit>` parts with units `ns`, `us`/`µs`, `ms`, `s`, `m` and `h`.
+/// Returns `None` for anything else, including an empty string.
+pub fn parse_go_duration(raw: &str) -> Option<Duration> {
+    if raw.is_empty() {
+        return None;
+    }
+
+    let mut total_nanos = 0.0;
+    let mut rest = raw;
+    while !rest.is_empty() {
+        let number_len = rest.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(rest.len());
+        let value: f64 = rest[..number_len].parse().ok()?;
+        rest = &rest[number_len..];
+
+        let unit_len = rest.find(|c: char| c.is_ascii_digit() || c == '.').unwrap_or(rest.len());
+        let nanos_per_unit = match &rest[..unit_len] {
+            "ns" => 1.0,
+            "us" | "µs" | "μs" => 1e3,
+            "ms" => 1e6,
+            "s" => 1e9,
+            "m" => 60e9,
+            "h" => 3600e9,
+            _ => return None,
+        };
+        total_nanos += value * nanos_per_unit;
+        rest = &rest[unit_len..];
+    }
+    Some(Duration::from_nanos(total_nanos.round() as u64))
+}
+
+/// Parses a bracketed `MM-DD|HH:MM:SS[.fff]` timestamp, completing it with `year`.
+///
+/// Returns `None` if the string doesn't match the format or is ambiguous in the local timezone.
+pub fn parse_bracket_timestamp(raw: &str, year: i32) -> Option<DateTime<Local>> {
+    parse_bracket_timestamp_as(raw, year, None)
+}
+
+/// Like [`parse_bracket_timestamp`], but reads the time as UTC, so it is never ambiguous.
+pub fn parse_bracket_timestamp_utc(raw: &str, year: i32) -> Option<DateTime<Local>> {
+    parse_bracket_timestamp_at(raw, year, Utc.fix())
+}
+
+/// Like [`parse_bracket_timestamp`], but reads the time at a fixed `offset`, so it is never ambiguous.
+pub fn parse_bracket_timestamp_at(raw: &str, year: i32, offset: FixedOffset) -> Option<DateTime<Local>> {
+    parse_bracket_timestamp_as(raw, year, Some(offset))
+}
+
+// --- HELPER FUNCTIONS ---
+/// [`parse_timestamp`], reading offset-less forms at `offset` when given, and also returning
+/// which format matched.
+fn parse_timestamp_as(raw: &str, year: i32, offset: Option<FixedOffset>) -> Option<(DateTime<Local>, TimestampVariant)> {
+    if let Some(local_dt) = parse_bracket_timestamp_as(raw, year, offset) {
+        let variant = if raw.contains('.') { TimestampVariant::BracketMillis } else { TimestampVariant::Bracket };
+        return Some((local_dt, variant));
+    }
+
+    if let Ok(dt) = DateTime::parse_from_rfc3339(raw) {
+        return Some((dt.with_timezone(&Local), TimestampVariant::Rfc3339));
+    }
+
+    let naive_dt = NaiveDateTime::parse_from_str(raw, "%Y-%m-%d %H:%M:%S%.f").ok()?;
+    Some((resolve_naive(naive_dt, offset)?, TimestampVariant::DateTime))
+}
+
+/// [`parse_bracket_timestamp`], reading the time at `offset` when given.
+fn parse_bracket_timestamp_as(raw: &str, year: i32, offset: Option<FixedOffset>) -> Option<DateTime<Local>> {
+    let with_year = format!("{}-{}", year, raw);
+    let naive_dt = NaiveDateTime::parse_from_str(&with_year, "%Y-%m-%d|%H:%M:%S%.f").ok()?;
+    resolve_naive(naive_dt, offset)
+}
+
+/// Places a timestamp without an offset at a fixed offset, or in the local timezone.
+///
+/// Local times skipped or repeated by a DST change give `None`; a fixed offset has no such gaps.
+fn resolve_naive(naive_dt: NaiveDateTime, offset: Option<FixedOffset>) -> Option<DateTime<Local>> {
+    match offset {
+        Some(offset) => Some(naive_dt.and_local_timezone(offset).single()?.with_timezone(&Local)),
+        None => naive_dt.and_local_timezone(Local).single(),
+    }
+}
+
+/// True if `details` is empty and [`set_omit_empty_details`] is on, so the field is skipped.
+fn omit_details(details: &Details) -> bool {
+    details.is_empty() && OMIT_EMPTY_DETAILS.with(Cell::get)
+}
+
+/// Serializes a level in the current thread's [`LevelCase`] and [`LevelStyle`].
+fn serialize_level<S: Serializer>(level: &str, serializer: S) -> Result<S::Ok, S::Error> {
+    output_level(level).serialize(serializer)
+}
+
+/// Deserializes a level written in any [`LevelCase`] and [`LevelStyle`] back to its full uppercase
+/// name, so filters work on re-read output. Unknown short names are only uppercased.
+fn deserialize_level<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let level = String::deserialize(deserializer)?.to_uppercase();
+    let full = [LogLevel::Trace, LogLevel::Debug, LogLevel::Info, LogLevel::Warn, LogLevel::Error]
+        .into_iter()
+        .map(LogLevel::as_str)
+        .find(|name| level.len() == 1 && name.starts_with(level.as_str()));
+    Ok(full.map_or(level, str::to_string))
+}
+
+/// Deserializes a timestamp written in any [`TimeFormat`], so every output can be re-read.
+///
+/// Integers are told apart as milliseconds or seconds by size (see `EPOCH_MS_THRESHOLD`).
+fn deserialize_timestamp<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Local>, D::Error> {
+    let epoch = match TimestampRepr::deserialize(deserializer)? {
+        TimestampRepr::Text(timestamp) => return Ok(timestamp),
+        TimestampRepr::Epoch(epoch) => epoch,
+    };
+    let timestamp = if epoch.abs() >= EPOCH_MS_THRESHOLD {
+        Local.timestamp_millis_opt(epoch)
+    } else {
+        Local.timestamp_opt(epoch, 0)
+    };
+    timestamp.single().ok_or_else(|| serde::de::Error::custom(format!("epoch timestamp {} is out of range", epoch)))
+}
+
+/// Strips the quotes from a `key=value` value: double quotes (resolving escapes), single quotes
+/// or backticks. Bare values only have backslash-escaped whitespace resolved, e.g. `x\ y`.
+fn unquote_kv_value(raw: &str) -> String {
+    if let Some(quoted) = raw.strip_prefix('"').and_then(|quoted| quoted.strip_suffix('"')) {
+        return unescape_quoted(quoted);
+    }
+    for quote in ['\'', '`'] {
+        if let Some(quoted) = raw.strip_prefix(quote).and_then(|quoted| quoted.strip_suffix(quote)) {
+            return quoted.to_string();
+        }
+    }
+    if !raw.contains('\\') {
+        return raw.to_string();
+    }
+    let mut unescaped = String::with_capacity(raw.len());
+    let mut chars = raw.chars().peekable();
+    while let Some(c) = chars.next() {
+        match chars.peek() {
+            Some(&next) if c == '\\' && next.is_whitespace() => {}
+            _ => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+/// Resolves backslash escapes (`\"`, `\\`, `\n`, `\t`) in a double-quoted value.
+fn unescape_quoted(quoted: &str) -> String {
+    let mut unescaped = String::with_capacity(quoted.len());
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some(escaped) => unescaped.push(escaped),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+// --- UNIT TESTS ---
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_line_happy_path() {
+        let line = "INFO [11-08|10:49:09.123] Imported new block headers count=1 elapsed=2.5s";
+        let year = 2025;
+
+        let result = parse_line(line, year);
+
+        let log_entry = result.unwrap();
+
+        assert_eq!(log_entry.level, "INFO");
+        assert_eq!(log_entry.details.get("count"), Some(&"1".to_string()));
+    }
+
+    #[test]
+    fn test_parse_line_quoted_kv_values() {
+        let line = r#"WARN [11-08|10:49:41] Quoting a="x y" b='x y' c=x\ y d=`x y` e="say \"hi\"" f=plain g=C:\geth"#;
+
+        let entry = parse_line(line, 2025).unwrap();
+        let details = &entry.details;
+
+        for key in ["a", "b", "c", "d"] {
+            assert_eq!(details[key], "x y", "{}", key);
+        }
+        assert_eq!(details["e"], r#"say "hi""#);
+        assert_eq!(details["f"], "plain");
+        assert_eq!(details["g"], r"C:\geth");
+        assert_eq!(normalize_message(&entry.message), "Quoting a= b= c= d= e= f= g=");
+    }
+
+    #[test]
+    fn test_parse_line_strips_ansi_colors() {
+        // As geth writes to a color terminal: colored level, dimmed keys.
+        let line = "\x1b[32mINFO\x1b[0m [11-08|10:49:09.123] Imported new chain segment \x1b[32mnumber\x1b[0m=42 \x1b[32mtxs\x1b[0m=3";
+
+        let entry = parse_line(line, 2025).unwrap();
+
+        assert_eq!(entry.level, "INFO");
+        assert_eq!(entry.message, "Imported new chain segment number=42 txs=3");
+        assert_eq!(entry.block_number, Some(42));
+        assert_eq!(strip_ansi("\x1b[1;31mERROR\x1b[K"), "ERROR");
+        assert!(matches!(strip_ansi("plain"), Cow::Borrowed("plain")));
+    }
+
+    #[test]
+    fn test_parse_line_no_kv_pairs() {
+        let line = "INFO [11-08|10:49:41] Block synchronisation started ";
+        let year = 2025;
+        let result = parse_line(line, year);
+
+        assert!(result.is_some());
+        let log_entry = result.unwrap();
+        assert!(log_entry.details.is_empty());
+    }
+
+    #[test]
+    fn test_parse_line_no_milliseconds_or_space() {
+        let line = "DEBUG[11-08|10:49:09] Recalculated downloader QoS values";
+        let year = 2025;
+        let result = parse_line(line, year);
+
+        assert!(result.is_some());
+        assert_eq!(result.unwrap().level, "DEBUG");
+    }
+
+    #[test]
+    fn test_parse_line_garbage_input() {
+        let line = "this is not a valid log line";
+        let year = 2025;
+        let result = parse_line(line, year);
+
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn test_parse_line_bracket_timestamp_uses_year() {
+        let entry = parse_line("INFO [07-04|15:23:01.123] Imported new chain segment", 2023).unwrap();
+
+        let expected = NaiveDateTime::parse_from_str("2023-07-04 15:23:01.123", "%Y-%m-%d %H:%M:%S%.f").unwrap();
+        assert_eq!(entry.timestamp.naive_local(), expected);
+    }
+
+    #[test]
+    fn test_parse_line_rfc3339_timestamp_ignores_year() {
+        let entry = parse_line("INFO [2024-07-04T15:23:01.123+02:00] Imported new chain segment", 2025).unwrap();
+
+        let expected = DateTime::parse_from_rfc3339("2024-07-04T13:23:01.123Z").unwrap();
+        assert_eq!(entry.timestamp, expected);
+        assert_eq!(entry.message, "Imported new chain segment");
+    }
+
+    #[test]
+    fn test_parse_line_plain_datetime_timestamp() {
+        let entry = parse_line("WARN [2024-07-04 15:23:01] Stale peer", 2025).unwrap();
+
+        let expected = NaiveDateTime::parse_from_str("2024-07-04 15:23:01", "%Y-%m-%d %H:%M:%S").unwrap();
+        assert_eq!(entry.timestamp.naive_local(), expected);
+    }
+
+    #[test]
+    fn test_parse_line_unknown_timestamp_format() {
+        assert!(parse_line("INFO [yesterday at noon] Stale peer", 2025).is_none());
+    }
+
+    #[test]
+    fn test_parse_line_reports_timestamp_variant() {
+        let variant = |line: &str| parse_line(line, 2025).unwrap().timestamp_variant;
+
+        assert_eq!(variant("INFO [11-08|10:49:09] Started"), Some(TimestampVariant::Bracket));
+        assert_eq!(variant("INFO [11-08|10:49:09.123] Started"), Some(TimestampVariant::BracketMillis));
+        assert_eq!(variant("INFO [2025-11-08T10:49:09Z] Started"), Some(TimestampVariant::Rfc3339));
+        assert_eq!(variant("t=2025-11-08T10:49:09+0000 lvl=info msg=Started"), Some(TimestampVariant::LogfmtOffset));
+        assert!(serde_json::from_str::<LogEntry>(&serde_json::to_string(&sample_entry()).unwrap()).unwrap().timestamp_variant.is_none());
+    }
+
+    #[test]
+    fn test_parse_line_promotes_leading_msg_pair() {
+        let promoted = parse_line(r#"INFO [07-04|15:23:01.123] msg="Chain reorg detected" number=5 drop=3"#, 2025).unwrap();
+        let normal = parse_line("INFO [07-04|15:23:01.123] Served eth_call msg=reverted", 2025).unwrap();
+
+        assert_eq!(promoted.message, "Chain reorg detected");
+        assert!(!promoted.details.contains_key("msg"));
+        assert_eq!(promoted.details["number"], "5");
+        assert_eq!(promoted.event.as_deref(), Some("reorg"));
+        assert_eq!(normal.message, "Served eth_call msg=reverted");
+        assert_eq!(normal.details["msg"], "reverted");
+    }
+
+    #[test]
+    fn test_parse_line_block_number() {
+        let line = "INFO [07-04|15:23:01.123] Imported new chain segment blocks=1 txs=12 mgas=1.234 elapsed=12.345ms number=123 hash=0xabc";
+        let entry = parse_line(line, 2025).unwrap();
+
+        assert_eq!(entry.block_number, Some(123));
+        assert_eq!(entry.details["number"], "123");
+        assert!(serde_json::to_string(&entry).unwrap().contains(r#""block_number":123"#));
+    }
+
+    #[test]
+    fn test_parse_line_block_number_absent_or_non_numeric() {
+        let without = parse_line("INFO [07-04|15:23:01] Stale peer", 2025).unwrap();
+        let non_numeric = parse_line("INFO [07-04|15:23:01] Stale peer number=abc", 2025).unwrap();
+
+        assert_eq!(without.block_number, None);
+        assert_eq!(non_numeric.block_number, None);
+        assert!(!serde_json::to_string(&without).unwrap().contains("block_number"));
+    }
+
+    #[test]
+    fn test_parse_line_peer_id_from_id_key() {
+        let line = "DEBUG[07-04|15:23:01.123] Adding p2p peer peercount=3 id=a2bd3f6b1c2d3e4f conn=dyndial addr=18.138.108.67:30303 name=Geth/v1.13.5-stable/linux-amd64/go1.21.4";
+        let entry = parse_line(line, 2025).unwrap();
+
+        assert_eq!(entry.peer_id.as_deref(), Some("a2bd3f6b1c2d3e4f"));
+        assert_eq!(entry.enode, None);
+        assert_eq!(entry.details["id"], "a2bd3f6b1c2d3e4f");
+    }
+
+    #[test]
+    fn test_parse_line_peer_key_takes_precedence() {
+        let line = "DEBUG[07-04|15:23:01] Removing p2p peer peer=5f3c1a2b id=ignored duration=1m2.345s req=false err=\"too many peers\"";
+        let entry = parse_line(line, 2025).unwrap();
+
+        assert_eq!(entry.peer_id.as_deref(), Some("5f3c1a2b"));
+    }
+
+    #[test]
+    fn test_parse_line_enode_url() {
+        let enode = "enode://6f8a80d14311c39f35f516fa664deaaaa13e85b2f7493f37f6144d86991ec012937307647bd3b9a82abe2974e1407241d54947bbb39763a4cac9f77166ad92a0@127.0.0.1:30303?discport=30301";
+        let line = format!("INFO [07-04|15:23:01.123] Started P2P networking self={}", enode);
+        let entry = parse_line(&line, 2025).unwrap();
+
+        assert_eq!(entry.enode.as_deref(), Some(enode));
+        assert_eq!(entry.peer_id, None);
+        assert_eq!(entry.details["self"], enode);
+
+        let json = serde_json::to_string(&parse_line("INFO [07-04|15:23:01] Stale peer", 2025).unwrap()).unwrap();
+        assert!(!json.contains("peer_id") && !json.contains("enode"));
+    }
+
+    #[test]
+    fn test_json_input_round_trips_output() {
+        let original = parse_line("WARN [07-04|15:23:01.123] Stale peer id=abc number=42", 2025).unwrap();
+        let json = serde_json::to_string(&original).unwrap();
+        let parser = LineParser { input_format: InputFormat::Json, ..LineParser::new(1999) };
+
+        let entry = parser.parse(&format!("{}\n", json)).unwrap();
+
+        assert_eq!(entry.timestamp, original.timestamp);
+        assert_eq!(entry.details, original.details);
+        assert_eq!(entry.block_number, Some(42));
+        assert_eq!(entry.peer_id.as_deref(), Some("abc"));
+        assert_eq!(serde_json::to_string(&entry).unwrap(), json);
+    }
+
+    #[test]
+    fn test_utc_parser_and_output() {
+        let parser = LineParser { utc: true, ..LineParser::new(2025) };
+
+        let entry = parser.parse("INFO [03-30|02:30:00] Started").unwrap();
+        let naive = parser.parse("INFO [2025-03-30 02:30:00] Started").unwrap();
+        set_utc_output(true);
+        let json = serde_json::to_value(&entry).unwrap();
+        set_utc_output(false);
+
+        assert_eq!(entry.timestamp.with_timezone(&Utc).to_rfc3339(), "2025-03-30T02:30:00+00:00");
+        assert_eq!(naive.timestamp, entry.timestamp);
+        assert_eq!(json["timestamp"], "2025-03-30T02:30:00Z");
+    }
+
+    #[test]
+    fn test_omit_empty_details_drops_only_empty_objects() {
+        let bare = parse_line("INFO [11-08|10:49:09] Starting Geth on Ethereum mainnet", 2025).unwrap();
+        let with_details = parse_line("INFO [11-08|10:49:09] Imported block number=42", 2025).unwrap();
+
+        let shown = serde_json::to_value(&bare).unwrap();
+        set_omit_empty_details(true);
+        let omitted = serde_json::to_value(&bare).unwrap();
+        let kept = serde_json::to_value(&with_details).unwrap();
+        set_omit_empty_details(false);
+        let reparsed: LogEntry = serde_json::from_value(omitted.clone()).unwrap();
+
+        assert_eq!(shown["details"], serde_json::json!({}));
+        assert!(omitted.get("details").is_none());
+        assert_eq!(kept["details"]["number"], "42");
+        assert!(reparsed.details.is_empty());
+    }
+
+    #[test]
+    fn test_tz_reads_naive_timestamps_at_offset() {
+        let east = FixedOffset::east_opt(2 * 3600).unwrap();
+        let at_utc = LineParser { tz: Some(Utc.fix()), ..LineParser::new(2025) };
+        let at_east = LineParser { tz: Some(east), utc: true, ..LineParser::new(2025) };
+
+        let line = "INFO [11-08|10:49:09] Started";
+        let utc_entry = at_utc.parse(line).unwrap();
+        let east_entry = at_east.parse(line).unwrap();
+        let rfc3339 = at_east.parse("INFO [2025-11-08T10:49:09Z] Started").unwrap();
+        set_output_offset(Some(east));
+        let json = serde_json::to_value(&east_entry).unwrap();
+        set_output_offset(None);
+
+        assert_eq!(utc_entry.timestamp - east_entry.timestamp, chrono::Duration::hours(2));
+        assert_eq!(json["timestamp"], "2025-11-08T10:49:09+02:00");
+        assert_eq!(rfc3339.timestamp, utc_entry.timestamp);
+    }
+
+    #[test]
+    fn test_enrich_time_sets_weekday_and_iso_week() {
+        let parser = LineParser { utc: true, ..LineParser::new(2025) };
+        let mut saturday = parser.parse("INFO [11-08|10:49:09] Started").unwrap();
+        let mut year_end = parser.parse("INFO [12-29|12:00:00] Started").unwrap();
+
+        set_utc_output(true);
+        saturday.enrich_time();
+        year_end.enrich_time();
+        set_utc_output(false);
+
+        assert_eq!((saturday.weekday.as_deref(), saturday.iso_week), (Some("Sat"), Some(45)));
+        // 2025-12-29 is a Monday in ISO week 1 of 2026.
+        assert_eq!((year_end.weekday.as_deref(), year_end.iso_week), (Some("Mon"), Some(1)));
+        let json = serde_json::to_value(&saturday).unwrap();
+        assert_eq!((json["weekday"].as_str(), json["iso_week"].as_u64()), (Some("Sat"), Some(45)));
+    }
+
+    #[test]
+    fn test_time_format_serializes_and_round_trips() {
+        let original = parse_line("WARN [07-04|15:23:01.123] Stale peer", 2025).unwrap();
+        let parser = LineParser { input_format: InputFormat::Json, ..LineParser::new(1999) };
+        let millis = original.timestamp.timestamp_millis();
+
+        for (format, expected) in [
+            (TimeFormat::Rfc3339, serde_json::to_value(original.timestamp).unwrap()),
+            (TimeFormat::EpochMs, serde_json::json!(millis)),
+            (TimeFormat::EpochS, serde_json::json!(millis / 1000)),
+        ] {
+            set_time_format(format);
+            let json = serde_json::to_string(&original).unwrap();
+            let reparsed = parser.parse(&json).unwrap();
+            set_time_format(TimeFormat::Rfc3339);
+
+            let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+            assert_eq!(value["timestamp"], expected, "{:?}", format);
+            let expected_millis = if format == TimeFormat::EpochS { millis / 1000 * 1000 } else { millis };
+            assert_eq!(reparsed.timestamp.timestamp_millis(), expected_millis, "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn test_level_format_combinations_serialize_and_round_trip() {
+        let original = parse_line("WARN [07-04|15:23:01.123] Stale peer", 2025).unwrap();
+        let parser = LineParser { input_format: InputFormat::Json, ..LineParser::new(1999) };
+
+        for (case, style, expected, custom_expected) in [
+            (LevelCase::Upper, LevelStyle::Full, "WARN", "CRIT"),
+            (LevelCase::Lower, LevelStyle::Full, "warn", "crit"),
+            (LevelCase::Upper, LevelStyle::Short, "W", "C"),
+            (LevelCase::Lower, LevelStyle::Short, "w", "c"),
+        ] {
+            set_level_format(case, style);
+            let json = serde_json::to_string(&original).unwrap();
+            let custom = output_level("CRIT").into_owned();
+            set_level_format(LevelCase::Upper, LevelStyle::Full);
+
+            let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+            assert_eq!(value["level"], expected, "{:?} {:?}", case, style);
+            assert_eq!(parser.parse(&json).unwrap().level, "WARN", "{:?} {:?}", case, style);
+            assert_eq!(custom, custom_expected, "{:?} {:?}", case, style);
+        }
+        assert_eq!(output_level(""), "");
+    }
+
+    #[test]
+    fn test_json_input_rejects_invalid_lines() {
+        let parser = LineParser { input_format: InputFormat::Json, ..LineParser::new(2025) };
+
+        assert!(parser.parse("INFO [07-04|15:23:01] Raw geth line").is_none());
+        assert!(parser.parse(r#"{"level":"INFO","message":"no timestamp"}"#).is_none());
+    }
+
+    #[test]
+    fn test_custom_pattern_accepts_prefixed_lines() {
+        let pattern = compile_log_pattern(
+            r"^\w{3} \d+ [\d:]+ \S+ geth\[\d+\]: (?P<level>[A-Z]+)\s*\[(?P<timestamp>.+?)\]\s+(?P<message>.*)",
+        )
+        .unwrap();
+        let parser = LineParser { pattern: Some(pattern), ..LineParser::new(2025) };
+        let line = "Jul 04 15:23:01 node1 geth[812]: INFO [07-04|15:23:01.123] Imported new chain segment number=7";
+
+        let entry = parser.parse(line).unwrap();
+
+        assert_eq!(entry.level, "INFO");
+        assert_eq!(entry.message, "Imported new chain segment number=7");
+        assert_eq!(entry.block_number, Some(7));
+        assert!(parse_line(line, 2025).is_none());
+    }
+
+    #[test]
+    fn test_custom_pattern_requires_named_groups() {
+        let err = compile_log_pattern(r"^(?P<level>\w+) (?P<message>.*)").unwrap_err();
+        assert!(err.to_string().contains("missing: timestamp"));
+
+        assert!(compile_log_pattern(r"(?P<level>").is_err());
+    }
+
+    #[test]
+    fn test_public_regexes_expose_named_groups() {
+        let caps = log_regex().captures("WARN [11-08|10:49:09] Stale peer id=abc").unwrap();
+        assert_eq!(&caps["level"], "WARN");
+        assert_eq!(&caps["message"], "Stale peer id=abc");
+
+        let kv = kv_regex().captures("id=abc").unwrap();
+        assert_eq!(&kv["key"], "id");
+        assert_eq!(&kv["value"], "abc");
+    }
+
+    #[test]
+    fn test_year_rollover_across_new_year() {
+        let mut rollover = YearRollover::default();
+        let mut entries: Vec<LogEntry> = ["12-31|23:59:58", "12-31|23:59:59", "01-01|00:00:00", "01-01|00:00:01"]
+            .iter()
+            .map(|timestamp| parse_line(&format!("INFO [{}] Tick", timestamp), 2024).unwrap())
+            .collect();
+
+        let rolled_over: Vec<bool> = entries.iter_mut().map(|entry| rollover.adjust(entry)).collect();
+
+        assert_eq!(rolled_over, [false, false, true, false]);
+        let years: Vec<i32> = entries.iter().map(|entry| entry.timestamp.year()).collect();
+        assert_eq!(years, [2024, 2024, 2025, 2025]);
+    }
+
+    #[test]
+    fn test_year_rollover_keeps_and_follows_explicit_years() {
+        let mut rollover = YearRollover::default();
+        let lines = [
+            "INFO [12-31|23:59:59] Before midnight",
+            "INFO [2025-01-01T12:00:00+00:00] Explicit",
+            "INFO [01-01|00:00:02] After midnight",
+            "INFO [2026-06-30T12:00:00+00:00] Much later",
+            "INFO [01-01|00:00:03] Next new year",
+        ];
+        let mut entries: Vec<LogEntry> = lines.iter().map(|line| parse_line(line, 2024).unwrap()).collect();
+
+        let rolled_over: Vec<bool> = entries.iter_mut().map(|entry| rollover.adjust(entry)).collect();
+
+        let years: Vec<i32> = entries.iter().map(|entry| entry.timestamp.year()).collect();
+        assert_eq!(years, [2024, 2025, 2025, 2026, 2027]);
+        assert_eq!(rolled_over, [false, false, false, false, true]);
+    }
+
+    #[test]
+    fn test_elapsed_promoted_to_milliseconds() {
+        let cases = [("1.2s", 1200), ("340ms", 340), ("2m3.5s", 123_500), ("4.521s", 4521)];
+        for (elapsed, expected_ms) in cases {
+            let line = format!("INFO [11-08|10:49:09] Imported new chain segment elapsed={}", elapsed);
+
+            let entry = parse_line(&line, 2025).unwrap();
+
+            assert_eq!(entry.elapsed_ms, Some(expected_ms), "elapsed={}", elapsed);
+            assert_eq!(entry.details["elapsed"], elapsed);
+        }
+    }
+
+    #[test]
+    fn test_age_promoted_to_seconds() {
+        let cases = [("90s", 90.0), ("1h2m3s", 3723.0), ("3m20.5s", 200.5)];
+        for (age, expected_sec) in cases {
+            let line = format!("DEBUG [11-08|10:49:09] Removing old pending transaction age={}", age);
+
+            let entry = parse_line(&line, 2025).unwrap();
+
+            assert_eq!(entry.age_sec, Some(expected_sec), "age={}", age);
+            assert_eq!(entry.details["age"], age);
+        }
+        assert_eq!(parse_line("DEBUG [11-08|10:49:09] Peer info age=unknown", 2025).unwrap().age_sec, None);
+    }
+
+    #[test]
+    fn test_max_details_truncates_extraction() {
+        let pairs: String = (0..200).map(|i| format!(" k{}={}", i, i)).collect();
+        let line = format!("WARN [11-08|10:49:09] Bloated line{}", pairs);
+        let logfmt = format!("t=2024-07-04T15:23:01Z lvl=warn{} msg=bloated", pairs);
+        let parser = LineParser { max_details: Some(3), ..LineParser::new(2025) };
+
+        let limited = parser.parse(&line).unwrap();
+        let limited_logfmt = parser.parse(&logfmt).unwrap();
+        let exact = LineParser { max_details: Some(200), ..parser.clone() }.parse(&line).unwrap();
+        let unlimited = parse_line(&line, 2025).unwrap();
+
+        assert_eq!(limited.details.keys().collect::<Vec<_>>(), ["k0", "k1", "k2"]);
+        assert!(limited.details_truncated);
+        assert_eq!(limited_logfmt.details.len(), 3);
+        assert_eq!(limited_logfmt.message, "bloated");
+        assert!(limited_logfmt.details_truncated);
+        assert!(!exact.details_truncated);
+        assert_eq!(unlimited.details.len(), 200);
+        assert!(!unlimited.details_truncated);
+        assert!(!serde_json::to_string(&unlimited).unwrap().contains("details_truncated"));
+    }
+
+    #[test]
+    fn test_reorg_classified_with_depth() {
+        let reorg = parse_line(
+            "INFO [03-12|09:15:42.100] Chain reorg detected                     number=19420001 hash=0x5a1b..e3c4 \
+             drop=2 dropfrom=0x7f2c..9a10 add=3 addfrom=0x1d4e..b2f8",
+            2025,
+        )
+        .unwrap();
+        let large = parse_line("WARN [03-12|09:15:43] Large chain reorg detected number=19420001 drop=64 add=65", 2025).unwrap();
+        let import = parse_line("INFO [03-12|09:15:44] Imported new chain segment number=19420002 drop=1", 2025).unwrap();
+
+        assert_eq!(reorg.event.as_deref(), Some("reorg"));
+        assert_eq!(reorg.reorg_depth, Some(2));
+        assert_eq!(reorg.details["drop"], "2");
+        assert_eq!(large.reorg_depth, Some(64));
+        assert_eq!((import.event, import.reorg_depth), (None, None));
+    }
+
+    #[test]
+    fn test_txpool_counts_promoted_to_integers() {
+        let status = parse_line("DEBUG[11-08|10:49:09.123] Transaction pool status report executable=4096 queued=45 stales=3 pending=123", 2025).unwrap();
+        let garbled = parse_line("DEBUG[11-08|10:49:10] Transaction pool status report pending=n/a", 2025).unwrap();
+
+        assert_eq!((status.pending, status.queued), (Some(123), Some(45)));
+        assert_eq!(status.details["pending"], "123");
+        assert_eq!((garbled.pending, garbled.queued), (None, None));
+        assert!(serde_json::to_value(&garbled).unwrap().get("pending").is_none());
+    }
+
+    #[test]
+    fn test_sync_progress_promoted_to_percentage() {
+        let synced = parse_line("INFO [11-08|10:49:09] Syncing beacon headers downloaded=1024 synced=62.31% eta=5m", 2025).unwrap();
+        let progress = parse_line("INFO [11-08|10:49:10] Syncing: state download in progress progress=100.00%", 2025).unwrap();
+        let garbled = parse_line("INFO [11-08|10:49:11] Syncing beacon headers synced=n/a", 2025).unwrap();
+
+        assert_eq!(synced.progress_pct, Some(62.31));
+        assert_eq!(synced.details["synced"], "62.31%");
+        assert_eq!(progress.progress_pct, Some(100.0));
+        assert_eq!(garbled.progress_pct, None);
+        assert_eq!(parse_progress("inf%"), None);
+    }
+
+    #[test]
+    fn test_parse_go_duration_rejects_malformed_values() {
+        assert_eq!(parse_go_duration("1.5µs"), Some(Duration::from_nanos(1500)));
+        assert_eq!(parse_go_duration(""), None);
+        assert_eq!(parse_go_duration("12"), None);
+        assert_eq!(parse_go_duration("3 days"), None);
+        assert_eq!(parse_line("INFO [11-08|10:49:09] Synced elapsed=soon", 2025).unwrap().elapsed_ms, None);
+    }
+
+    #[test]
+    fn test_hash_normalized_and_malformed_flagged() {
+        let full = format!("0x{}", "AB".repeat(32));
+        let valid = parse_line(&format!("INFO [11-08|10:49:09] Imported block hash={}", full), 2025).unwrap();
+        let abbreviated = parse_line("INFO [11-08|10:49:09] Imported block hash=0x5a8b..3f4a", 2025).unwrap();
+        let corrupt = parse_line("INFO [11-08|10:49:09] Imported block hash=0x5a8bzz", 2025).unwrap();
+        let no_hash = parse_line("INFO [11-08|10:49:09] Imported block", 2025).unwrap();
+
+        assert_eq!(valid.hash, Some(full.to_lowercase()));
+        assert_eq!(valid.details["hash"], full);
+        assert!(!valid.has_malformed_hash());
+        assert_eq!(abbreviated.hash, None);
+        assert!(!abbreviated.has_malformed_hash());
+        assert!(corrupt.has_malformed_hash());
+        assert!(!no_hash.has_malformed_hash());
+    }
+
+    #[test]
+    fn test_numeric_keys_parsed_into_metrics() {
+        let line = "INFO [11-08|10:49:09] Commit new sealing work gas=21000 gasused=1,234,567 fees=0.0021 gasprice=unknown";
+
+        let entry = parse_line(line, 2025).unwrap();
+
+        assert_eq!(entry.metrics["gas"], 21000.0);
+        assert_eq!(entry.metrics["gasused"], 1_234_567.0);
+        assert_eq!(entry.metrics["fees"], 0.0021);
+        assert!(!entry.metrics.contains_key("gasprice"));
+        assert_eq!(entry.details["gasprice"], "unknown");
+        assert_eq!(entry.details["gas"], "21000");
+    }
+
+    #[test]
+    fn test_custom_numeric_keys() {
+        let parser = LineParser { numeric_keys: vec!["txs".to_string()], ..LineParser::new(2025) };
+
+        let entry = parser.parse("INFO [11-08|10:49:09] Imported new chain segment txs=12 gas=21000").unwrap();
+
+        assert_eq!(entry.metrics.len(), 1);
+        assert_eq!(entry.metrics["txs"], 12.0);
+    }
+
+    #[test]
+    fn test_parse_logfmt_line() {
+        let line = r#"t=2024-07-04T15:23:01.123Z lvl=info msg="Imported new chain segment" number=42 hash=0x5a8b..3f4a"#;
+
+        let entry = parse_line(line, 2025).unwrap();
+
+        assert_eq!(entry.level, "INFO");
+        assert_eq!(entry.message, "Imported new chain segment");
+        assert_eq!(entry.timestamp.with_timezone(&chrono::Utc).to_rfc3339(), "2024-07-04T15:23:01.123+00:00");
+        assert_eq!(entry.block_number, Some(42));
+        assert_eq!(entry.details.len(), 2);
+        assert!(!entry.details.contains_key("msg"));
+    }
+
+    #[test]
+    fn test_diagnose_explains_rejected_lines() {
+        let parser = LineParser::new(2025);
+
+        assert_eq!(parser.diagnose("INFO [11-08|10:49:09] Started"), None);
+        assert_eq!(parser.diagnose("not a log line"), Some(ParseFailure::NoMatch));
+        assert_eq!(
+            parser.diagnose("INFO [13-45|10:49:09] Started"),
+            Some(ParseFailure::BadTimestamp("13-45|10:49:09".to_string()))
+        );
+        assert_eq!(parser.diagnose("t=2024-07-04T15:23:01Z msg=hi"), Some(ParseFailure::MissingLogfmtKey("lvl")));
+        assert_eq!(
+            parser.diagnose("t=yesterday lvl=info msg=hi"),
+            Some(ParseFailure::BadTimestamp("yesterday".to_string()))
+        );
+
+        let json_parser = LineParser { input_format: InputFormat::Json, ..LineParser::new(2025) };
+        assert!(matches!(json_parser.diagnose("{\"level\":"), Some(ParseFailure::InvalidJson(_))));
+    }
+
+    #[test]
+    fn test_parse_logfmt_geth_levels_offsets_and_escapes() {
+        let line = r#"t=2024-07-04T15:23:01+0200 lvl=eror msg="Failed to \"dial\" peer" err="i/o timeout""#;
+
+        let entry = parse_line(line, 2025).unwrap();
+
+        assert_eq!(entry.level, "ERROR");
+        assert_eq!(entry.message, r#"Failed to "dial" peer"#);
+        assert_eq!(entry.details["err"], "i/o timeout");
+        assert_eq!(entry.timestamp.with_timezone(&chrono::Utc).to_rfc3339(), "2024-07-04T13:23:01+00:00");
+        assert!(parse_line(r#"lvl=info msg="no timestamp""#, 2025).is_none());
+    }
+
+    #[test]
+    fn test_sample_entry_populates_every_field() {
+        let sample = serde_json::to_value(sample_entry()).unwrap();
+
+        let fields: Vec<&str> = sample.as_object().unwrap().keys().map(String::as_str).collect();
+        for field in [
+            "level", "timestamp", "message", "details", "metrics", "block_number", "peer_id", "hash",
+            "elapsed_ms", "age_sec", "progress_pct", "pending", "queued", "event", "reorg_depth", "enode",
+            "details_truncated", "repeat_count", "continuation", "raw", "line", "file", "weekday",
+            "iso_week",
+        ] {
+            assert!(fields.contains(&field), "missing {}", field);
+        }
+        assert_eq!(fields.len(), 24);
+    }
+
+    #[test]
+    fn test_year_from_filename_shapes() {
+        assert_eq!(year_from_filename("/var/log/geth-2023-11.log"), Some(2023));
+        assert_eq!(year_from_filename("geth.2024.log.gz"), Some(2024));
+        assert_eq!(year_from_filename("archive/20231105-geth.log"), Some(2023));
+        assert_eq!(year_from_filename("2019/geth.log"), None);
+        assert_eq!(year_from_filename("geth.log.1"), None);
+        assert_eq!(year_from_filename("node-12023.log"), None);
+    }
+}
@@ -1,48 +1,427 @@
+// --- MODULES ---
+mod compress;
+mod config;
+mod endpoint;
+mod input;
+mod processor;
+mod state;
+mod summary;
+
 // --- IMPORTS ---
 // Standard library imports
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process;
-use std::fs::File;
-use std::io::{self, BufRead};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, IsTerminal, Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 // Third-party libraries
-use clap::Parser;
-use lazy_static::lazy_static;
-use regex::Regex;
-use chrono::{DateTime, Datelike, Local, NaiveDateTime};
-use std::collections::HashMap;
-use serde::{Serialize};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use chrono::{DateTime, Datelike, FixedOffset, Local, Offset, Utc};
 use indicatif::{ProgressBar, ProgressStyle};
+use regex::Regex;
+use serde::Serialize;
+
+// Local crate
+use geth_log_cruncher::{
+    compile_log_pattern, output_timestamp, parse_bracket_timestamp, parse_bracket_timestamp_at, sample_entry,
+    set_level_format, set_omit_empty_details, set_output_offset, set_time_format, year_from_filename, InputFormat, LevelCase, LevelStyle, LineParser,
+    LogLevel, TimeFormat, YearRollover, DEFAULT_NUMERIC_KEYS,
+};
+use geth_log_cruncher::details::DuplicateKeyPolicy;
+use geth_log_cruncher::output::{set_csv_columns, set_flatten_details, EntryWriter, OutputFormat, SplitBy};
+use geth_log_cruncher::script::ScriptExtractor;
+use geth_log_cruncher::stats::{ErrorRateMonitor, Histogram, LatencyStats};
+use compress::{gzip_writer, OutputCompression};
+use config::FileConfig;
+use endpoint::{Endpoint, EndpointWriter};
+use input::{open_input, probe_year, FollowReader, LogInput, RetryReader};
+use processor::{latest_timestamp, DetailFilter, EntryFilters, KeepEntry, LogProcessor, ProcessOptions, ProcessStats, Sampler, SortBy, CHUNK_LINES, DEBUG_INVALID_LIMIT};
+use state::{open_resumed, save_resume_state, ResumeState};
+use summary::{print_histogram, print_latency, print_skipped, print_summary, skip_input, write_summary_json, RunSummary};
 
 // --- DATA STRUCTURES ---
-/// Represents a structured log entry parsed from the input file.
+/// An `--output` destination: a file path, with a format overriding `--format` for that file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct OutputSpec {
+    format: Option<OutputFormat>,  // Format from a `FORMAT=` prefix, if given
+    path: String,                  // File to write, created or truncated
+}
+
+/// How directory inputs expand into the log files they contain.
+#[derive(Debug)]
+struct DirectoryScan {
+    pattern: String,    // The `--glob` pattern, for messages
+    glob: Regex,        // `pattern` compiled to match whole file names
+    recursive: bool,    // Descend into subdirectories
+    order: FileOrder,   // Order of the matched files
+}
+
+/// How a run that didn't fail ended, deciding the exit code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RunOutcome {
+    Completed,    // Every input was processed (or a limit like `--max-lines` was reached)
+    Interrupted,  // Ctrl-C stopped the run; the output and summary cover what was read
+}
+
+/// Order of the files a directory input expands to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum FileOrder {
+    Name,   // By path, so `geth-2025-01.log` comes before `geth-2025-02.log`
+    Mtime,  // Least recently modified first; ties by path
+}
+
+/// Parser throughput measured by `--bench`, printed as JSON.
 #[derive(Debug, Serialize)]
-struct LogEntry {
-    level: String,                     // Log level (e.g., INFO, WARN, ERROR)
-    timestamp: DateTime<Local>,        // Log timestamp in local timezone
-    message: String,                   // Main log message
-    details: HashMap<String, String>,  // Key-value pairs extracted from the message
+struct BenchReport {
+    lines: usize,             // Lines in the inputs, each parsed once per iteration
+    valid: usize,             // Lines that parsed, per iteration
+    iterations: usize,        // Passes over every line
+    elapsed_secs: f64,        // Wall time spent parsing, excluding reading the inputs
+    lines_per_sec: f64,       // Lines parsed per second, over all iterations
+    ns_per_line: f64,         // Mean time to parse one line
 }
 
-/// Command-line arguments for the application.
+/// Command-line arguments for the application. Without a subcommand, the `parse` flags are given
+/// directly, as they were before subcommands existed.
 #[derive(Parser, Debug)]
-#[command(version, about)]
+#[command(version, about, args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Cli {
-    log_file_path: String,  // Path to the log file to process
+    #[command(subcommand)]
+    command: Option<Command>, // What to do with the logs; `parse` when omitted
+    #[command(flatten)]
+    parse: ParseArgs,         // Flags of the implicit `parse`
+}
+
+/// What a run does with the logs.
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Emit the parsed entries (the default when no subcommand is given)
+    Parse(ParseArgs),
+    /// Print an aggregate stats report instead of the entries (same as `--stats`)
+    Stats(StatsArgs),
+    /// Emit only the last N entries (same as `--tail N`)
+    Tail(TailArgs),
+    /// Keep reading a log file as it grows, like `tail -f` (same as `--follow`)
+    Follow(FollowArgs),
+}
+
+/// Flags of `parse`, which also selects the other modes by flag, for runs without a subcommand.
+#[derive(Args, Debug)]
+struct ParseArgs {
+    #[command(flatten)]
+    common: CommonArgs,
+    #[arg(long, conflicts_with_all = ["bench", "split_by", "invert", "group_by", "pivot"])]
+    stats: bool,            // Print an aggregate stats report instead of per-entry output
+    #[arg(long, default_value_t = DEFAULT_TOP)]
+    top: usize,             // Number of most frequent messages in the stats report
+    #[arg(long, value_name = "N")]
+    tail: Option<usize>,    // Emit only the last N valid entries
+    #[arg(long, short, conflicts_with_all = ["bench", "state", "sort_by", "year_probe", "mmap"])]
+    follow: bool,           // Keep reading as the log file grows, like `tail -f`, until Ctrl-C
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_FOLLOW_RETRIES, requires = "follow")]
+    follow_retries: u32,    // Consecutive failed reads `--follow` retries, with growing pauses, before giving up
+}
+
+/// Flags of the `stats` subcommand.
+#[derive(Args, Debug)]
+struct StatsArgs {
+    #[command(flatten)]
+    common: CommonArgs,
+    #[arg(long, default_value_t = DEFAULT_TOP)]
+    top: usize,             // Number of most frequent messages in the report
+}
+
+/// Flags of the `tail` subcommand.
+#[derive(Args, Debug)]
+struct TailArgs {
+    #[command(flatten)]
+    common: CommonArgs,
+    #[arg(long, short = 'n', value_name = "N", default_value_t = DEFAULT_TAIL_LINES)]
+    lines: usize,           // Number of valid entries to emit, counted from the end
+}
+
+/// Flags of the `follow` subcommand.
+#[derive(Args, Debug)]
+struct FollowArgs {
+    #[command(flatten)]
+    common: CommonArgs,
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_FOLLOW_RETRIES)]
+    retries: u32,           // Consecutive failed reads retried, with growing pauses, before giving up
+}
+
+/// Input, filtering and output flags shared by every subcommand.
+#[derive(Args, Debug)]
+struct CommonArgs {
+    #[arg(required_unless_present_any = ["print_schema", "files_from"], num_args = 1..)]
+    log_file_paths: Vec<String>, // Log files to process in order; `-` reads stdin
     #[arg(long)]
     year: Option<i32>,      // Optional year for timestamps (default: current year)
+    #[arg(long, value_enum, ignore_case = true)]
+    level: Option<LogLevel>, // Optional minimum level to emit (e.g., WARN keeps WARN and ERROR)
+    #[arg(long)]
+    from: Option<String>,   // Optional start of the time window (RFC3339 or MM-DD|HH:MM:SS)
+    #[arg(long)]
+    until: Option<String>,  // Optional end of the time window (RFC3339 or MM-DD|HH:MM:SS)
+    #[arg(long, value_name = "[FORMAT=]PATH", value_parser = parse_output_spec)]
+    output: Vec<OutputSpec>, // Files to write entries to, each optionally in its own format (default: stdout)
+    #[arg(long)]
+    pretty: bool,           // Emit indented JSON instead of one entry per line
+    #[arg(long)]
+    flatten: bool,          // Write `details` pairs as top-level `d_KEY` fields instead of a nested object
+    #[arg(long)]
+    omit_empty_details: bool, // Leave `details` out of JSON entries that have no pairs, instead of writing `{}`
+    #[arg(long, value_name = "COLUMNS", value_delimiter = ',')]
+    columns: Option<Vec<String>>, // CSV columns in order: `level`, `timestamp`, `message` or `details` keys
+    #[arg(long, value_enum)]
+    format: Option<OutputFormat>, // Output format for emitted entries (default: json)
+    #[arg(long, value_name = "REGEX")]
+    pattern: Option<String>, // Custom line regex with named groups `level`, `timestamp` and `message`
+    #[arg(long, value_name = "PATH")]
+    errors: Option<String>, // Optional file to record unparseable lines with their line numbers
+    #[arg(long)]
+    count_only: bool,       // Parse and count lines without serializing or emitting entries
+    #[arg(long, conflicts_with_all = [
+        "output", "errors", "split_by", "count_only", "dry_run", "group_by", "invert", "state",
+    ])]
+    bench: bool,            // Time the parser alone over the inputs, loaded into memory, and print the result as JSON
+    #[arg(long, value_name = "N", default_value_t = 10, value_parser = parse_positive, requires = "bench")]
+    bench_iterations: usize, // Passes over the inputs for `--bench`
+    #[arg(long, value_enum, default_value_t = InputFormat::Raw)]
+    input_format: InputFormat, // Interpret input as raw geth logs or this tool's JSON output
+    #[arg(long)]
+    dedup: bool,            // Collapse consecutive duplicate entries, adding a `repeat_count`
+    #[arg(long, value_name = "DURATION", value_parser = humantime::parse_duration)]
+    since_duration: Option<Duration>, // Keep only entries within this long of the latest timestamp (e.g. 15m, 1h30m)
+    #[arg(long)]
+    auto_year: bool,        // Increment the year when the month goes backwards, for logs spanning New Year
+    #[arg(long, short)]
+    quiet: bool,            // Suppress the progress bar and run summary on stderr
+    #[arg(long, value_name = "PATH")]
+    summary_json: Option<String>, // Optional file to write the run totals to as a JSON object
+    #[arg(long = "where", value_name = "KEY=VALUE", value_parser = parse_detail_filter)]
+    where_details: Vec<DetailFilter>, // Keep only entries with this `details` pair (`KEY=*` for any value); repeatable
+    #[arg(long, value_name = "REGEX")]
+    grep: Option<String>,   // Keep only entries whose message matches this regex
+    #[arg(long, requires = "grep")]
+    grep_invert: bool,      // Keep only entries whose message does not match `--grep`
+    #[arg(long)]
+    join_continuations: bool, // Attach non-log lines (e.g. stack traces) to the preceding entry
+    #[arg(long, value_name = "N")]
+    max_lines: Option<usize>, // Stop after reading N lines in total, valid or not
+    #[arg(long, value_name = "N", value_parser = parse_positive, conflicts_with = "sample_random")]
+    sample: Option<usize>,  // Emit only every Nth entry that passes the filters, starting with the first
+    #[arg(long, value_name = "N", value_parser = parse_positive)]
+    sample_random: Option<usize>, // Emit each entry that passes the filters with probability 1/N
+    #[arg(long, value_name = "SEED", default_value_t = 0, requires = "sample_random")]
+    sample_seed: u64,       // Seed for `--sample-random`; the same seed samples the same entries
+    #[arg(long, value_name = "KEY")]
+    unique_by: Option<String>, // Keep one entry per distinct value of this `details` key; entries without it are kept
+    #[arg(long, value_enum, default_value_t = KeepEntry::First, requires = "unique_by")]
+    keep: KeepEntry,        // Which entry `--unique-by` keeps for each value
+    #[arg(long, value_name = "PATH")]
+    state: Option<String>,  // Resume each file where the last run with this state file stopped, and record the new offsets
+    #[arg(long, value_enum, requires = "out_dir", conflicts_with_all = ["output", "count_only", "pretty"])]
+    split_by: Option<SplitBy>, // Write NDJSON into one file per partition (per day or per level) instead of one stream
+    #[arg(long, value_name = "DIR", requires = "split_by")]
+    out_dir: Option<String>, // Directory for `--split-by` files, created if missing
+    #[arg(long)]
+    strict: bool,           // Exit with an error at the first unparseable line
+    #[arg(long, value_name = "PCT", value_parser = parse_percentage)]
+    threshold: Option<f64>, // Exit with an error if more than PCT% of lines are invalid
+    #[arg(long)]
+    single_format: bool,    // Exit with an error if valid lines use more than one timestamp format
+    #[arg(long, value_name = "KEYS", value_delimiter = ',')]
+    numeric_keys: Option<Vec<String>>, // Comma-separated `details` keys to parse into `metrics` (default: gas,gasused,gasprice,fees)
+    #[arg(long, value_name = "PATH")]
+    config: Option<String>, // TOML file with defaults for flags not given on the command line
+    #[arg(long, value_enum)]
+    sort_by: Option<SortBy>, // Sort the output; buffers every matching entry in memory
+    #[arg(long, value_name = "KEYS", value_delimiter = ',')]
+    fields: Option<Vec<String>>, // Comma-separated `details` keys to keep in the output; others are dropped
+    #[arg(long)]
+    print_schema: bool,     // Print a sample entry with every field populated, then exit
+    #[arg(long)]
+    year_from_filename: bool, // Take each input's year from a 4-digit year in its file name, if present
+    #[arg(long, value_name = "LINES", value_parser = parse_positive)]
+    year_probe: Option<usize>, // Take each input's year from the first full-date timestamp in its first LINES lines
+    #[arg(long)]
+    mmap: bool,             // Memory-map plain input files instead of reading them through a buffer
+    #[arg(long, value_name = "INTERVAL", value_parser = humantime::parse_duration)]
+    histogram: Option<Duration>, // Count valid entries per time bucket of this width (e.g. 1m, 1h)
+    #[arg(long, conflicts_with_all = ["output", "errors", "split_by", "count_only"])]
+    dry_run: bool,          // Run the full pipeline, serializing entries but discarding them; only the summary is produced
+    #[arg(long, value_name = "PATH")]
+    files_from: Option<String>, // File listing more log paths, one per line, processed after the positional ones
+    #[arg(long, value_name = "PATTERN")]
+    glob: Option<String>,   // File names to read from directory inputs, with `*` and `?` wildcards (default `*.log`)
+    #[arg(long)]
+    recursive: bool,        // Also read matching files in subdirectories of directory inputs
+    #[arg(long, value_enum, default_value_t = FileOrder::Name)]
+    file_order: FileOrder,  // Order of the files a directory input expands to
+    #[arg(long)]
+    skip_missing: bool,     // Warn about and skip inputs that don't exist instead of aborting
+    #[arg(long)]
+    strict_io: bool,        // Abort on the first input that fails to open or read, instead of skipping it
+    #[arg(long)]
+    keep_raw: bool,         // Include each entry's trimmed source line as `raw` in the output
+    #[arg(long)]
+    enrich_time: bool,      // Add `weekday` (e.g. Mon) and `iso_week` fields derived from each timestamp
+    #[arg(long)]
+    with_line_number: bool, // Add each entry's 1-based source line number as `line`, plus `file` with several inputs
+    #[arg(long)]
+    debug_invalid: bool,    // Print why each unparseable line was dropped (first few only) to stderr
+    #[arg(long, value_enum, default_value_t = TimeFormat::Rfc3339)]
+    time_format: TimeFormat, // How entry timestamps are written: RFC3339 strings or integer epochs
+    #[arg(long, value_enum, default_value_t = LevelCase::Upper)]
+    level_case: LevelCase,  // Letter case levels are written in: `INFO` or `info`
+    #[arg(long, value_enum, default_value_t = LevelStyle::Full)]
+    level_style: LevelStyle, // Full level names, or only their first letter (`INFO` -> `I`)
+    #[arg(long)]
+    utc: bool,              // Read offset-less timestamps as UTC and write every timestamp in UTC
+    #[arg(long, value_name = "OFFSET", value_parser = parse_offset)]
+    tz: Option<FixedOffset>, // Read offset-less timestamps at this UTC offset (e.g. +02:00), and write them there unless --utc
+    #[arg(long, value_name = "PATH")]
+    extract_script: Option<String>, // Rhai script returning extra `details` fields for each entry
+    #[arg(long, value_name = "N")]
+    max_line_bytes: Option<usize>, // Skip lines longer than N bytes instead of reading them into memory
+    #[arg(long)]
+    anonymize: bool,        // Replace IP addresses and enode public keys with stable hashed placeholders
+    #[arg(long, value_name = "REGEX")]
+    redact: Vec<String>,    // Replace matches of this regex with `[REDACTED]` (repeatable)
+    #[arg(long, conflicts_with_all = ["output", "errors", "split_by", "count_only", "dry_run"])]
+    invert: bool,           // Write the unparseable lines to stdout, prefixed with line numbers, instead of the entries
+    #[arg(long, value_name = "WINDOW", value_parser = humantime::parse_duration, requires = "error_rate_max")]
+    error_rate_window: Option<Duration>, // Sliding window for `--error-rate-max` (e.g. 1m)
+    #[arg(long, value_name = "N", requires = "error_rate_window")]
+    error_rate_max: Option<usize>, // Fail the run if more than N ERROR entries fall within any `--error-rate-window`
+    #[arg(long, value_name = "KEY", conflicts_with_all = ["split_by", "count_only", "invert"])]
+    group_by: Option<String>, // Write one NDJSON rollup per value of this `details` key instead of the entries
+    #[arg(long, value_name = "KEY", conflicts_with_all = ["split_by", "count_only", "invert", "group_by"])]
+    pivot: Option<String>,  // Write only the timestamp and numeric value of this `details` key, for entries carrying it
+    #[arg(long, value_name = "N", value_parser = parse_positive)]
+    threads: Option<usize>, // Worker threads for parallel parsing (default: one per CPU core)
+    #[arg(long, value_name = "N", value_parser = parse_positive)]
+    chunk_lines: Option<usize>, // Lines read and parsed per batch (default: 4096)
+    #[arg(long, value_enum, default_value_t = DuplicateKeyPolicy::Last)]
+    on_duplicate_key: DuplicateKeyPolicy, // Keep the last or first value of a key repeated in a line, or an array of all
+    #[arg(long, value_name = "N")]
+    limit_details: Option<usize>, // Extract at most N `key=value` pairs per line, flagging the entry `details_truncated`
+    #[arg(long)]
+    latency_stats: bool,    // Report min/max/mean and p50/p90/p99 of `elapsed_ms` over the emitted entries
+    #[arg(long, requires = "output")]
+    reconnect: bool,        // Reconnect to a `tcp://` or `unix:` output after a write error instead of failing
+    #[arg(long, requires = "output")]
+    append: bool,           // Append to `--output` files instead of truncating them, creating missing ones
+    #[arg(long, value_enum, requires = "output")]
+    compress_output: Option<OutputCompression>, // Compress every `--output` file; files ending in `.gz` are gzipped regardless
 }
 
 // --- GLOBAL VARIABLES ---
-// Precompiled regex patterns for efficient log parsing.
-lazy_static! {
-    // Regex to capture the main components of a log line
-    static ref LOG_REGEX: Regex = Regex::new(
-        r"^(?P<level>INFO|WARN|ERROR|DEBUG|TRACE)\s*\[(?P<timestamp>.+?)\]\s+(?P<message>.*)"
-    ).unwrap();
+/// Most frequent messages in the stats report when no `--top` is given.
+const DEFAULT_TOP: usize = 10;
+
+/// Entries the `tail` subcommand emits when no `--lines` is given.
+const DEFAULT_TAIL_LINES: usize = 10;
+
+/// Consecutive failed reads retried under `--follow` when no retry count is given.
+const DEFAULT_FOLLOW_RETRIES: u32 = 5;
+
+/// Exit code for a run stopped by Ctrl-C: 128 + SIGINT, as shells report it.
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+/// Path argument that selects standard input instead of a file.
+const STDIN_PATH: &str = "-";
 
-    // Regex to capture key-value pairs in the log message
-    static ref KV_REGEX: Regex = Regex::new(r#"(?P<key>\w+)=(?P<value>"[^"]*"|\S+)"#).unwrap();
+/// File names read from a directory input when no `--glob` is given.
+const DEFAULT_DIRECTORY_GLOB: &str = "*.log";
+
+/// Most buckets `--histogram` will report, so a tiny interval over a long log can't exhaust memory.
+const MAX_HISTOGRAM_BUCKETS: usize = 100_000;
+
+/// Buffer size for the output sink, so entries are written in large batches.
+const OUTPUT_BUFFER_BYTES: usize = 256 * 1024;
+
+// --- IMPLEMENTATIONS ---
+impl DirectoryScan {
+    /// Creates a scan for file names matching the glob `pattern`.
+    fn new(pattern: &str, recursive: bool, order: FileOrder) -> Self {
+        DirectoryScan { pattern: pattern.to_string(), glob: glob_regex(pattern), recursive, order }
+    }
+
+    /// Lists the matching files under `dir`, in `order`. Subdirectories are only searched when
+    /// `recursive` is set, and their names don't need to match.
+    fn files_in(&self, dir: &Path) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+        let mut files = Vec::new();
+        let mut pending = vec![dir.to_path_buf()];
+        while let Some(dir) = pending.pop() {
+            let entries = fs::read_dir(&dir)
+                .map_err(|e| format!("Error: Could not read directory '{}': {}", dir.display(), e))?;
+            for entry in entries {
+                let path = entry?.path();
+                if path.is_dir() {
+                    if self.recursive {
+                        pending.push(path);
+                    }
+                } else if path.file_name().is_some_and(|name| self.glob.is_match(&name.to_string_lossy())) {
+                    files.push(path);
+                }
+            }
+        }
+
+        files.sort();
+        if self.order == FileOrder::Mtime {
+            // Stable, so files with equal times stay in path order
+            let mut timed = files
+                .into_iter()
+                .map(|path| Ok((fs::metadata(&path)?.modified()?, path)))
+                .collect::<io::Result<Vec<_>>>()?;
+            timed.sort_by_key(|(modified, _)| *modified);
+            files = timed.into_iter().map(|(_, path)| path).collect();
+        }
+        Ok(files)
+    }
+}
+
+impl Command {
+    /// Resolves the subcommand to the `parse` flags it stands for. Clap rejects `--stats` and
+    /// `--follow` next to the flags they can't be combined with; the `stats` and `follow`
+    /// subcommands imply those modes, so the same combinations are rejected here.
+    fn into_parse_args(self) -> Result<ParseArgs, String> {
+        match self {
+            Command::Parse(args) => Ok(args),
+            Command::Stats(StatsArgs { common, top }) => {
+                reject_flags("stats", &[
+                    ("--bench", common.bench),
+                    ("--split-by", common.split_by.is_some()),
+                    ("--invert", common.invert),
+                    ("--group-by", common.group_by.is_some()),
+                    ("--pivot", common.pivot.is_some()),
+                ])?;
+                Ok(ParseArgs { common, stats: true, top, tail: None, follow: false, follow_retries: DEFAULT_FOLLOW_RETRIES })
+            }
+            Command::Tail(TailArgs { common, lines }) => Ok(ParseArgs {
+                common,
+                stats: false,
+                top: DEFAULT_TOP,
+                tail: Some(lines),
+                follow: false,
+                follow_retries: DEFAULT_FOLLOW_RETRIES,
+            }),
+            Command::Follow(FollowArgs { common, retries }) => {
+                reject_flags("follow", &[
+                    ("--bench", common.bench),
+                    ("--state", common.state.is_some()),
+                    ("--sort-by", common.sort_by.is_some()),
+                    ("--year-probe", common.year_probe.is_some()),
+                    ("--mmap", common.mmap),
+                ])?;
+                Ok(ParseArgs { common, stats: false, top: DEFAULT_TOP, tail: None, follow: true, follow_retries: retries })
+            }
+        }
+    }
 }
 
 // --- ENTRY POINT ---
@@ -50,151 +429,772 @@ lazy_static! {
 fn main() {
     let cli_args = Cli::parse();
 
-    if let Err(e) = run(cli_args) {
-        eprintln!("Application error: {}", e);
-        process::exit(1);
+    match run(cli_args) {
+        Ok(RunOutcome::Completed) => {}
+        Ok(RunOutcome::Interrupted) => process::exit(INTERRUPTED_EXIT_CODE),
+        Err(e) => {
+            eprintln!("Application error: {}", e);
+            process::exit(1);
+        }
     }
 }
 
 // --- CORE & HELPER FUNCTIONS ---
 /// The main workflow logic orchestrator for the application.
 /// 
-/// - Validates the input file.
+/// - Validates the input files (or selects stdin for `-`).
 /// - Sets up the progress bar.
-/// - Processes the log file line by line.
+/// - Processes each log file line by line, in order.
 /// - Outputs a run summary.
-fn run(args: Cli) -> Result<(), Box<dyn std::error::Error>> {
-    let path = Path::new(&args.log_file_path);
-    validate_path(path)?;
+fn run(cli: Cli) -> Result<RunOutcome, Box<dyn std::error::Error>> {
+    let ParseArgs { common: mut args, stats, top, tail, follow, follow_retries } = match cli.command {
+        Some(command) => command.into_parse_args()?,
+        None => cli.parse,
+    };
 
+    // Entries are serialized on this thread, so these settings apply to every writer.
+    set_time_format(args.time_format);
+    set_output_offset(if args.utc { Some(Utc.fix()) } else { args.tz });
+    set_level_format(args.level_case, args.level_style);
+    set_flatten_details(args.flatten);
+    set_omit_empty_details(args.omit_empty_details);
+    if let Some(columns) = &args.columns {
+        if columns.iter().any(|column| column.is_empty()) {
+            return Err("Error: --columns has an empty column name".into());
+        }
+        set_csv_columns(columns.clone());
+    }
+    if args.print_schema {
+        println!("{}", serde_json::to_string_pretty(&sample_entry())?);
+        return Ok(RunOutcome::Completed);
+    }
+
+    // Command-line flags take precedence over the config file.
+    if let Some(config_path) = args.config.clone() {
+        FileConfig::load(&config_path)?.apply(&mut args)?;
+    }
+    if let Some(threads) = args.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+            .map_err(|e| format!("Error: Could not start {} parser threads: {}", threads, e))?;
+    }
+    let format = args.format.unwrap_or_default();
     let year = args.year.unwrap_or_else(|| Local::now().year());
 
-    let file_metadata = File::open(path)?.metadata()?;
-    let total_bytes = file_metadata.len();
+    let pattern = args.pattern.as_deref().map(compile_log_pattern).transpose()?;
+    let numeric_keys = match &args.numeric_keys {
+        Some(keys) => keys.clone(),
+        None => DEFAULT_NUMERIC_KEYS.iter().map(|key| key.to_string()).collect(),
+    };
+    let parser = LineParser {
+        year,
+        pattern,
+        input_format: args.input_format,
+        numeric_keys,
+        utc: args.utc,
+        tz: args.tz,
+        on_duplicate_key: args.on_duplicate_key,
+        max_details: args.limit_details,
+    };
+
+    // Time bounds without a year or offset are read like the log timestamps.
+    let offset = parser.naive_offset();
+    let mut filters = EntryFilters {
+        min_level: args.level,
+        from: args.from.as_deref().map(|s| parse_time_bound(s, year, offset, "--from")).transpose()?,
+        until: args.until.as_deref().map(|s| parse_time_bound(s, year, offset, "--until")).transpose()?,
+        details: args.where_details.clone(),
+        grep: args.grep.as_deref().map(compile_grep).transpose()?,
+        grep_invert: args.grep_invert,
+    };
+
+    if let Some(files_from) = &args.files_from {
+        let contents = fs::read_to_string(files_from)
+            .map_err(|e| format!("Error: Could not read file list '{}': {}", files_from, e))?;
+        args.log_file_paths.extend(parse_file_list(&contents));
+    }
+
+    // Validate every input up front so a bad path fails before any output is written.
+    let scan = DirectoryScan::new(args.glob.as_deref().unwrap_or(DEFAULT_DIRECTORY_GLOB), args.recursive, args.file_order);
+    let mut log_file_paths = Vec::with_capacity(args.log_file_paths.len());
+    for log_file_path in std::mem::take(&mut args.log_file_paths) {
+        if log_file_path == STDIN_PATH {
+            log_file_paths.push(log_file_path);
+            continue;
+        }
+        match validate_path(Path::new(&log_file_path), &scan) {
+            Ok(files) => log_file_paths.extend(files),
+            Err(e) => {
+                if !args.skip_missing {
+                    return Err(e);
+                }
+                if !args.quiet {
+                    eprintln!("Warning: {}; skipping it", e.to_string().trim_start_matches("Error: "));
+                }
+            }
+        }
+    }
+    if log_file_paths.is_empty() {
+        return Err("Error: No log files to process".into());
+    }
+    args.log_file_paths = log_file_paths;
+    if follow && (args.log_file_paths.len() != 1 || args.log_file_paths[0] == STDIN_PATH) {
+        return Err("Error: --follow needs exactly one log file path (not stdin)".into());
+    }
+    if args.state.is_some() && args.log_file_paths.iter().any(|path| path == STDIN_PATH) {
+        return Err("Error: --state needs log file paths; stdin can't be resumed".into());
+    }
+    if args.bench {
+        let report = run_bench(&parser, &args.log_file_paths, args.bench_iterations)?;
+        println!("{}", serde_json::to_string(&report)?);
+        return Ok(RunOutcome::Completed);
+    }
+
+    // Each input's year, taken from its file name under `--year-from-filename`.
+    let file_years: Vec<i32> = args
+        .log_file_paths
+        .iter()
+        .map(|log_file_path| {
+            if !args.year_from_filename || log_file_path == STDIN_PATH {
+                return year;
+            }
+            year_from_filename(log_file_path).unwrap_or_else(|| {
+                if !args.quiet {
+                    eprintln!("Warning: No year found in file name '{}'; using {}", log_file_path, year);
+                }
+                year
+            })
+        })
+        .collect();
+    let started = Instant::now();
+
+    // `--since-duration` needs a first pass over the inputs to find the latest timestamp.
+    // Stdin can only be read once, so it is buffered in memory for both passes.
+    let stdin_buffer = if args.since_duration.is_some() && args.log_file_paths.iter().any(|path| path == STDIN_PATH) {
+        let mut buffer = Vec::new();
+        io::stdin().lock().read_to_end(&mut buffer)?;
+        Some(buffer)
+    } else {
+        None
+    };
+    let mut since_cutoff = None;
+    if let Some(since_duration) = args.since_duration {
+        let since_duration = chrono::Duration::from_std(since_duration)
+            .map_err(|_| format!("Error: --since-duration '{}' is too large", humantime::format_duration(since_duration)))?;
+        let mut latest: Option<DateTime<Local>> = None;
+        let mut year_rollover = YearRollover::default();
+        for (log_file_path, &file_year) in args.log_file_paths.iter().zip(&file_years) {
+            // Inputs that fail here are reported, or abort under `--strict-io`, when opened again below.
+            let Ok((input, file_year)) = open_input(log_file_path, stdin_buffer.as_deref(), args.mmap)
+                .and_then(|input| Ok(probe_year(input, &parser, args.year_probe, file_year)?))
+            else {
+                continue;
+            };
+            let file_parser = LineParser { year: file_year, ..parser.clone() };
+            let file_latest = latest_timestamp(
+                input.reader,
+                &file_parser,
+                args.auto_year.then_some(&mut year_rollover),
+                args.max_line_bytes,
+                args.chunk_lines.unwrap_or(CHUNK_LINES),
+            );
+            match file_latest {
+                Ok(Some(file_latest)) => latest = Some(latest.map_or(file_latest, |latest| latest.max(file_latest))),
+                Ok(None) => {}
+                Err(_) if !args.strict_io => {}
+                Err(e) => return Err(format!("Error: Could not read '{}': {}", log_file_path, e).into()),
+            }
+        }
+        since_cutoff = latest.map(|latest| latest - since_duration);
+        // Combined with `--from`, the later of the two bounds wins.
+        if let Some(cutoff) = since_cutoff {
+            filters.from = Some(filters.from.map_or(cutoff, |from| from.max(cutoff)));
+        }
+    }
+    let options = ProcessOptions {
+        parser,
+        filters,
+        parallel: true,
+        tail,
+        dedup: args.dedup,
+        auto_year: args.auto_year,
+        join_continuations: args.join_continuations,
+        max_lines: args.max_lines,
+        follow,
+        strict: args.strict,
+        sort_by: args.sort_by,
+        fields: args.fields.clone(),
+        keep_raw: args.keep_raw,
+        enrich_time: args.enrich_time,
+        with_line_number: args.with_line_number,
+        debug_invalid: args.debug_invalid,
+        extract_script: args.extract_script.as_deref().map(ScriptExtractor::load).transpose()?,
+        interrupted: Arc::new(AtomicBool::new(false)),
+        max_line_bytes: args.max_line_bytes,
+        anonymize: args.anonymize,
+        redact: args.redact.iter().map(|pattern| compile_redact(pattern)).collect::<Result<_, _>>()?,
+        chunk_lines: args.chunk_lines,
+        unique_by: args.unique_by.clone().map(|key| (key, args.keep)),
+        sample: match (args.sample, args.sample_random) {
+            (Some(n), _) => Some(Sampler::every(n)),
+            (_, Some(n)) => Some(Sampler::random(n, args.sample_seed)),
+            (None, None) => None,
+        },
+    };
+
+    // Ctrl-C stops reading at the next chunk (or ends a followed stream) so the output is flushed
+    // and the summary printed; a second one exits at once.
+    let handler_interrupted = Arc::clone(&options.interrupted);
+    ctrlc::set_handler(move || {
+        if handler_interrupted.swap(true, Ordering::Relaxed) {
+            process::exit(INTERRUPTED_EXIT_CODE);
+        }
+    })?;
+
+    if let Some((key, keep)) = &options.unique_by
+        && !args.quiet
+    {
+        let held = if *keep == KeepEntry::Last { ", and --keep last holds every kept entry until the end" } else { "" };
+        eprintln!(
+            "Warning: --unique-by remembers every distinct value of '{}'{}; memory grows with the number of distinct values",
+            key, held
+        );
+    }
+
+    // Open the inputs. Stdin has no known length, so it gets no byte total. Under `--state`, each
+    // file is read from the previous run's offset to the end of its last complete line.
+    let mut inputs = Vec::with_capacity(args.log_file_paths.len());
+    let mut skipped = Vec::new();
+    let mut resume_state = args.state.as_deref().map(ResumeState::load).transpose()?;
+    let mut resumed = Vec::new();
+    if let Some(resume_state) = &resume_state {
+        // Only I/O errors are skippable; anything else (e.g. a compressed input) is a usage error.
+        for (log_file_path, &file_year) in args.log_file_paths.iter().zip(&file_years) {
+            let opened = open_resumed(log_file_path, resume_state, args.quiet).and_then(|(input, start, read_to_end)| {
+                Ok((probe_year(input, &options.parser, args.year_probe, file_year)?, start, read_to_end))
+            });
+            match opened {
+                Ok(((input, file_year), start, read_to_end)) => {
+                    inputs.push((log_file_path.as_str(), input, file_year));
+                    resumed.push((log_file_path.as_str(), start, read_to_end));
+                }
+                Err(e) if !e.is::<io::Error>() => return Err(e),
+                Err(e) if !args.strict_io => skip_input(&mut skipped, log_file_path, e.to_string(), args.quiet),
+                Err(e) => return Err(format!("Error: Could not open '{}': {}", log_file_path, e).into()),
+            }
+        }
+    } else if follow {
+        let log_file_path = &args.log_file_paths[0];
+        let reader = FollowReader::open(Path::new(log_file_path), Arc::clone(&options.interrupted))?;
+        let reader = RetryReader::new(reader, log_file_path, follow_retries, args.quiet);
+        let input = LogInput { reader: Box::new(io::BufReader::new(reader)), total_bytes: None };
+        inputs.push((log_file_path.as_str(), input, file_years[0]));
+    } else {
+        for (log_file_path, &file_year) in args.log_file_paths.iter().zip(&file_years) {
+            let opened = open_input(log_file_path, stdin_buffer.as_deref(), args.mmap)
+                .and_then(|input| Ok(probe_year(input, &options.parser, args.year_probe, file_year)?));
+            match opened {
+                Ok((input, file_year)) => inputs.push((log_file_path.as_str(), input, file_year)),
+                Err(e) if !args.strict_io => skip_input(&mut skipped, log_file_path, e.to_string(), args.quiet),
+                Err(e) => return Err(format!("Error: Could not open '{}': {}", log_file_path, e).into()),
+            }
+        }
+    }
+    if inputs.is_empty() {
+        return Err(format!("Error: None of the {} inputs could be opened", args.log_file_paths.len()).into());
+    }
+
+    // Open the output sinks; files are truncated if they already exist, unless `--append` is set.
+    // Stdout is line-buffered on its own, so it gets a large buffer too; `writer.finish` flushes
+    // it before the summary. A dry run still serializes every entry, so its timing reflects a
+    // real run, but into a sink.
+    if args.append && args.output.iter().any(|spec| spec.format.unwrap_or(format) == OutputFormat::JsonArray) {
+        return Err("Error: --append can't be used with json-array output, which must stay a single JSON array".into());
+    }
+    if stats && args.output.len() > 1 {
+        return Err("Error: --stats writes a single report; give at most one --output".into());
+    }
+    if args.group_by.is_some() && args.output.len() > 1 {
+        return Err("Error: --group-by writes a single report; give at most one --output".into());
+    }
+    if args.pivot.is_some() && args.output.len() > 1 {
+        return Err("Error: --pivot writes a single series; give at most one --output".into());
+    }
+    let mut writer = if let (Some(split_by), Some(out_dir)) = (args.split_by, &args.out_dir) {
+        EntryWriter::split(Path::new(out_dir), split_by)
+            .map_err(|e| format!("Error: Could not create output directory '{}': {}", out_dir, e))?
+    } else if args.count_only || args.invert {
+        EntryWriter::discard()
+    } else if args.output.len() > 1 {
+        let mut sinks = Vec::with_capacity(args.output.len());
+        for spec in &args.output {
+            let sink_format = spec.format.unwrap_or(format);
+            let name = format!("{}={}", sink_format.to_possible_value().unwrap().get_name(), spec.path);
+            sinks.push((name, output_writer(spec, sink_format, &args)?));
+        }
+        EntryWriter::multi(sinks)
+    } else {
+        let continuing = !args.dry_run && args.append && args.output.first().is_some_and(|spec| has_content(&spec.path));
+        let (out, format): (Box<dyn Write>, OutputFormat) = match args.output.first() {
+            _ if args.dry_run => (Box::new(io::sink()), format),
+            Some(spec) => (open_output(&spec.path, &args)?, spec.format.unwrap_or(format)),
+            None => (Box::new(BufWriter::with_capacity(OUTPUT_BUFFER_BYTES, io::stdout().lock())), format),
+        };
+        if let Some(key) = &args.group_by {
+            EntryWriter::grouped(out, key)
+        } else if let Some(key) = &args.pivot {
+            EntryWriter::pivot(out, key, format, !continuing)?
+        } else if stats {
+            EntryWriter::stats(out, top)
+        } else if format == OutputFormat::Human {
+            EntryWriter::human(out, use_color(args.output.is_empty()))
+        } else if continuing {
+            EntryWriter::continuing(out, format, args.pretty)?
+        } else {
+            EntryWriter::new(out, format, args.pretty)?
+        }
+    };
 
-    // Pass total_bytes to setup_progress_bar
-    let pb = setup_progress_bar(total_bytes); 
+    // Open the invalid-line report, if requested. With `--invert` it takes the place of the entries on stdout.
+    let mut errors_out: Option<Box<dyn Write>> = match &args.errors {
+        Some(errors_path) => {
+            let file = File::create(errors_path)
+                .map_err(|e| format!("Error: Could not create errors file '{}': {}", errors_path, e))?;
+            Some(Box::new(BufWriter::new(file)))
+        }
+        None if args.invert => Some(Box::new(BufWriter::with_capacity(OUTPUT_BUFFER_BYTES, io::stdout().lock()))),
+        None => None,
+    };
+
+    // The bar spans all inputs; any input of unknown size falls back to a spinner.
+    let total_bytes = inputs.iter().map(|(_, input, _)| input.total_bytes).sum::<Option<u64>>();
+    let pb = if args.quiet { ProgressBar::hidden() } else { setup_progress_bar(total_bytes) };
     pb.set_message("Initializing...");
 
     // Empty file check
-    if total_bytes == 0 {
+    if total_bytes == Some(0) {
         pb.finish_with_message("File is empty.");
-        eprintln!("Input file is empty. Nothing to process.");
-        return Ok(());
-    }
-
-    // Process the log file and get line counts
-    let (total_lines, valid_line_count) = process_log_file(path, year, &pb)?;
-    
-    let invalid_line_count = total_lines - valid_line_count;
-    let invalid_percentage = (invalid_line_count as f64 / total_lines as f64) * 100.0;
-
-    // Print summary
-    eprintln!("\nRun Summary");
-    eprintln!("---------------------");
-    eprintln!("Total Lines Processed: {}", total_lines);
-    eprintln!("Valid Log Entries Found: {}", valid_line_count);
-    eprintln!(
-        "Invalid Log Entries: {} ({:.2}% of total lines)",
-        invalid_line_count, invalid_percentage
+        writer.finish()?;
+        if !args.quiet {
+            match &resume_state {
+                Some(_) => eprintln!("No new lines since the last run. Nothing to process."),
+                None => eprintln!("Input file is empty. Nothing to process."),
+            }
+        }
+        if let (Some(state_path), Some(resume_state), false) = (&args.state, resume_state.as_mut(), args.dry_run) {
+            save_resume_state(state_path, resume_state, &resumed, &[])?;
+        }
+        if let Some(summary_path) = &args.summary_json {
+            write_summary_json(summary_path, &RunSummary::new(&ProcessStats::default(), year, started.elapsed()))?;
+        }
+        return Ok(RunOutcome::Completed);
+    }
+
+    // Process each log file in order, keeping per-file line counts
+    let multiple_inputs = inputs.len() > 1;
+    let mut processor = LogProcessor::new(
+        &options,
+        &mut writer,
+        errors_out.as_mut().map(|errors| errors as &mut dyn Write),
+        &pb,
     );
-    eprintln!("Year Used for Timestamps: {}", year);
-    eprintln!("---------------------");
+    if let Some(interval) = args.histogram {
+        let interval = chrono::Duration::from_std(interval).ok().and_then(Histogram::new).ok_or_else(|| {
+            format!("Error: --histogram interval '{}' must be at least 1s", humantime::format_duration(interval))
+        })?;
+        processor.histogram = Some(interval);
+    }
+    if let (Some(window), Some(max)) = (args.error_rate_window, args.error_rate_max) {
+        let monitor = chrono::Duration::from_std(window).ok().and_then(|window| ErrorRateMonitor::new(window, max));
+        processor.error_rate = Some(monitor.ok_or("Error: --error-rate-window must be longer than zero")?);
+    }
+    if args.latency_stats {
+        processor.latency = Some(LatencyStats::default());
+    }
+    let mut file_stats = Vec::with_capacity(inputs.len());
+    for (log_file_path, input, file_year) in inputs {
+        if processor.reached_max_lines() || options.was_interrupted() {
+            break;
+        }
+        let source = multiple_inputs.then_some(log_file_path);
+        processor.file_year = Some(file_year);
+        file_stats.push((log_file_path, file_year, processor.process_log_file(input.reader, source)?));
+        // Entries read before the error are kept; the rest of the input is skipped.
+        if let Some(e) = processor.read_error.take() {
+            if args.strict_io {
+                return Err(format!("Error: Could not read '{}': {}", log_file_path, e).into());
+            }
+            pb.suspend(|| skip_input(&mut skipped, log_file_path, e.to_string(), args.quiet));
+        }
+    }
+    let all_inputs_failed = skipped.len() == args.log_file_paths.len();
+    let stopped_early = processor.reached_max_lines();
+    let invalid_reported = processor.invalid_reported;
+    let histogram = processor.histogram.take();
+    let error_rate_breach = processor.error_rate.as_ref().and_then(ErrorRateMonitor::breach);
+    let latency = processor.latency.as_ref().and_then(LatencyStats::report);
+    let stage_stats = processor.finish()?;
+    if options.was_interrupted() {
+        pb.abandon_with_message("Interrupted.");
+    } else if stopped_early {
+        pb.finish_with_message("Stopped at --max-lines limit.");
+    } else {
+        pb.finish_with_message("Processing complete!");
+    }
+    writer.finish()?;
+    if let Some(errors) = errors_out.as_mut() {
+        errors.flush()?;
+    }
+    // A dry run reads from the stored offsets but leaves them for the real run.
+    if let (Some(state_path), Some(resume_state), false) = (&args.state, resume_state.as_mut(), args.dry_run) {
+        save_resume_state(state_path, resume_state, &resumed, &file_stats)?;
+    }
 
-    Ok(())
-}
+    let mut stats = stage_stats;
+    for (_, _, per_file) in &file_stats {
+        stats.merge(per_file);
+    }
 
-/// The core file processing engine. Reads a file line-by-line, parses, and prints JSON.
-fn process_log_file(path: &Path, year: i32, pb: &ProgressBar) -> Result<(usize, usize), Box<dyn std::error::Error>> {
-    let file = File::open(path)?; 
-    
-    let mut reader = io::BufReader::new(file);
-    
-    let mut valid_line_count = 0;
-    let mut total_lines = 0;
-    let mut bytes_read_so_far = 0;
+    let elapsed = started.elapsed();
+    if args.debug_invalid && stats.invalid_lines() > invalid_reported {
+        eprintln!(
+            "{} more invalid lines not shown (--debug-invalid explains the first {})",
+            stats.invalid_lines() - invalid_reported,
+            DEBUG_INVALID_LIMIT
+        );
+    }
+    let histogram_buckets = match &histogram {
+        Some(histogram) if histogram.span() > MAX_HISTOGRAM_BUCKETS => {
+            if !args.quiet {
+                eprintln!(
+                    "Warning: --histogram would need {} buckets (limit {}); use a larger interval",
+                    histogram.span(),
+                    MAX_HISTOGRAM_BUCKETS
+                );
+            }
+            None
+        }
+        Some(histogram) => Some(histogram.buckets()),
+        None => None,
+    };
 
-    let mut line_buffer = String::new(); 
-    loop {
-        line_buffer.clear(); 
-        let bytes_read_this_line = reader.read_line(&mut line_buffer)?;
-        if bytes_read_this_line == 0 {
-            break; 
+    if !args.quiet {
+        print_summary(&args, &options, &file_stats, &stats, since_cutoff, elapsed, &writer);
+        if let Some(buckets) = &histogram_buckets {
+            print_histogram(buckets);
         }
+        if let Some(latency) = &latency {
+            print_latency(latency);
+        }
+        print_skipped(&skipped);
+    }
+    if let Some(summary_path) = &args.summary_json {
+        let summary = RunSummary {
+            histogram: histogram_buckets,
+            interrupted: options.was_interrupted(),
+            anonymized: options.anonymize.then_some(stats.anonymized_values),
+            redactions: (!options.redact.is_empty()).then_some(stats.redactions),
+            sampled_out: options.sample.is_some().then_some(stats.sampled_out),
+            duplicates_dropped: options.unique_by.is_some().then_some(stats.duplicates_dropped),
+            skipped_inputs: std::mem::take(&mut skipped),
+            error_rate_breach,
+            latency,
+            timestamp_formats: stats.report_timestamp_variants(args.single_format).then(|| stats.timestamp_variants.clone()),
+            ..RunSummary::new(&stats, year, elapsed)
+        };
+        write_summary_json(summary_path, &summary)?;
+    }
+
+    if options.was_interrupted() {
+        return Ok(RunOutcome::Interrupted);
+    }
 
-        total_lines += 1;
-        bytes_read_so_far += bytes_read_this_line; 
+    // Checked last so the summary is still reported for a failing run.
+    if all_inputs_failed {
+        return Err(format!("Error: All {} inputs failed to read", args.log_file_paths.len()).into());
+    }
+    if let (Some(breach), Some(window), Some(max)) = (error_rate_breach, args.error_rate_window, args.error_rate_max) {
+        return Err(format!(
+            "Error: {} ERROR entries between {} and {}, above the --error-rate-max of {} per {}",
+            breach.count,
+            output_timestamp(&breach.start).to_rfc3339(),
+            output_timestamp(&breach.end).to_rfc3339(),
+            max,
+            humantime::format_duration(window)
+        )
+        .into());
+    }
+    if let Some(threshold) = args.threshold
+        && stats.invalid_percentage() > threshold
+    {
+        return Err(format!(
+            "Error: {:.2}% of lines were invalid, above the --threshold of {:.2}% (the run fails only when strictly greater)",
+            stats.invalid_percentage(),
+            threshold
+        )
+        .into());
+    }
+    if args.single_format && stats.timestamp_variants.len() > 1 {
+        return Err(format!(
+            "Error: Timestamps use {} different formats ({}), but --single-format allows only one",
+            stats.timestamp_variants.len(),
+            stats.describe_timestamp_variants()
+        )
+        .into());
+    }
 
-        // Update the progress bar with bytes read.
-        pb.set_position(bytes_read_so_far as u64);
-        pb.set_message(format!("Processing line {}", total_lines));
+    Ok(RunOutcome::Completed)
+}
 
+/// Opens an `--output` destination, buffered so entries are written in large batches: a
+/// `tcp://host:port` or `unix:/path` socket, connected once, or else a file, created or
+/// truncated (or, with `--append`, created or appended to). Files are gzipped under
+/// `--compress-output gzip` or when named `*.gz`; an appended one gets another gzip member.
+fn open_output(destination: &str, args: &CommonArgs) -> Result<Box<dyn Write>, Box<dyn std::error::Error>> {
+    if let Some(endpoint) = Endpoint::parse(destination) {
+        let stream = EndpointWriter::connect(endpoint, args.reconnect)?;
+        return Ok(Box::new(BufWriter::with_capacity(OUTPUT_BUFFER_BYTES, stream)));
+    }
+    let file = if args.append {
+        OpenOptions::new().create(true).append(true).open(destination)
+    } else {
+        File::create(destination)
+    };
+    let file = file.map_err(|e| format!("Error: Could not create output file '{}': {}", destination, e))?;
+    if args.compress_output == Some(OutputCompression::Gzip) || destination.ends_with(".gz") {
+        return Ok(Box::new(BufWriter::with_capacity(OUTPUT_BUFFER_BYTES, gzip_writer(file))));
+    }
+    Ok(Box::new(BufWriter::with_capacity(OUTPUT_BUFFER_BYTES, file)))
+}
 
-        // Parse the line and output JSON if valid
-        if let Some(log_entry) = parse_line(&line_buffer, year) {
-            valid_line_count += 1;
-            let json_string = serde_json::to_string(&log_entry)?;
-            println!("{}", json_string);
-        }
+/// Opens one of several `--output` sinks as an entry writer in `format`. A file appended to under
+/// `--append` that already holds output gets no second CSV header.
+fn output_writer(
+    spec: &OutputSpec,
+    format: OutputFormat,
+    args: &CommonArgs,
+) -> Result<EntryWriter<Box<dyn Write>>, Box<dyn std::error::Error>> {
+    let continuing = args.append && has_content(&spec.path);
+    let out = open_output(&spec.path, args)?;
+    if continuing { EntryWriter::continuing(out, format, args.pretty) } else { EntryWriter::new(out, format, args.pretty) }
+}
+
+/// True if `path` is an existing, non-empty file, i.e. appending to it continues earlier output.
+fn has_content(path: &str) -> bool {
+    Endpoint::parse(path).is_none() && fs::metadata(path).is_ok_and(|metadata| metadata.len() > 0)
+}
+
+/// Reads every input into memory, then parses each line `iterations` times on this thread, so
+/// the timing covers the parser alone: no I/O, filtering or serialization.
+fn run_bench(parser: &LineParser, paths: &[String], iterations: usize) -> Result<BenchReport, Box<dyn std::error::Error>> {
+    let mut lines = Vec::new();
+    for path in paths {
+        let mut input = open_input(path, None, false)?;
+        let mut contents = Vec::new();
+        input.reader.read_to_end(&mut contents)?;
+        lines.extend(String::from_utf8_lossy(&contents).lines().map(str::to_string));
     }
-    
-    pb.finish_with_message("Processing complete!");
-    Ok((total_lines, valid_line_count))
+
+    let started = Instant::now();
+    let mut valid = 0;
+    for _ in 0..iterations {
+        valid = lines.iter().filter(|line| std::hint::black_box(parser.parse(line)).is_some()).count();
+    }
+    let elapsed = started.elapsed();
+
+    let parsed = (lines.len() * iterations) as f64;
+    Ok(BenchReport {
+        lines: lines.len(),
+        valid,
+        iterations,
+        elapsed_secs: elapsed.as_secs_f64(),
+        lines_per_sec: if elapsed.is_zero() { 0.0 } else { parsed / elapsed.as_secs_f64() },
+        ns_per_line: if parsed == 0.0 { 0.0 } else { elapsed.as_nanos() as f64 / parsed },
+    })
 }
 
-/// Parses a single log line into a `LogEntry` struct.
-fn parse_line(line: &str, year: i32) -> Option<LogEntry> {
-    if let Some(caps) = LOG_REGEX.captures(line) {
-        let raw_timestamp_str = &caps["timestamp"];
-        let with_year = format!("{}-{}", year, raw_timestamp_str);
-        let naive_dt = NaiveDateTime::parse_from_str(&with_year, "%Y-%m-%d|%H:%M:%S%.f").ok()?;
-        let local_dt = naive_dt.and_local_timezone(Local).single()?;
+/// Compiles the `--grep` pattern, rejecting an empty one since it would match everything.
+fn compile_grep(pattern: &str) -> Result<Regex, Box<dyn std::error::Error>> {
+    if pattern.is_empty() {
+        return Err("Error: --grep pattern must not be empty".into());
+    }
+    Regex::new(pattern).map_err(|e| format!("Error: Invalid --grep regex '{}': {}", pattern, e).into())
+}
 
-        let message = caps["message"].to_string();
-        let mut details = HashMap::new();
-        for kv_caps in KV_REGEX.captures_iter(&message) {
-            let key = kv_caps["key"].to_string();
-            let mut value = kv_caps["value"].to_string();
-            if value.starts_with('"') && value.ends_with('"') {
-                value = value.trim_matches('"').to_string();
-            }
-            details.insert(key, value);
-        }
-            
-        Some(LogEntry {
-            level: caps["level"].to_string(),
-            timestamp: local_dt,
-            message: caps["message"].to_string(),
-            details, 
-        })
-    } else {
-        None
+/// Compiles a `--redact` pattern, rejecting one that matches the empty string since it would
+/// insert `[REDACTED]` between every character.
+fn compile_redact(pattern: &str) -> Result<Regex, Box<dyn std::error::Error>> {
+    let regex = Regex::new(pattern).map_err(|e| format!("Error: Invalid --redact regex '{}': {}", pattern, e))?;
+    if regex.is_match("") {
+        return Err(format!("Error: --redact regex '{}' must not match the empty string", pattern).into());
+    }
+    Ok(regex)
+}
+
+/// Parses a `--threshold` value as a percentage between 0 and 100.
+fn parse_percentage(value: &str) -> Result<f64, String> {
+    let percentage: f64 = value.parse().map_err(|_| format!("expected a percentage, got '{}'", value))?;
+    if !(0.0..=100.0).contains(&percentage) {
+        return Err(format!("percentage must be between 0 and 100, got {}", percentage));
+    }
+    Ok(percentage)
+}
+
+/// Fails naming the first of `flags` that was given, as one `subcommand` can't be combined with.
+fn reject_flags(subcommand: &str, flags: &[(&str, bool)]) -> Result<(), String> {
+    match flags.iter().find(|(_, given)| *given) {
+        Some((flag, _)) => Err(format!("Error: The {} subcommand can't be combined with {}", subcommand, flag)),
+        None => Ok(()),
     }
 }
 
-/// Validates that the provided path exists and is a file.
-fn validate_path(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+/// Parses a count that must be at least 1, for `--threads` and `--chunk-lines`.
+fn parse_positive(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(0) => Err("must be at least 1".to_string()),
+        Ok(count) => Ok(count),
+        Err(_) => Err(format!("expected a positive whole number, got '{}'", value)),
+    }
+}
+
+/// Parses a `--where` value of the form `key=value`, or `key=*` for any value.
+fn parse_detail_filter(value: &str) -> Result<DetailFilter, String> {
+    let (key, expected) = value
+        .split_once('=')
+        .filter(|(key, _)| !key.is_empty())
+        .ok_or_else(|| format!("expected KEY=VALUE or KEY=*, got '{}'", value))?;
+    let expected = (expected != "*").then(|| expected.to_string());
+    Ok(DetailFilter { key: key.to_string(), value: expected })
+}
+
+/// Parses a `--tz` offset such as `+02:00`, `-0530` or `Z`.
+fn parse_offset(value: &str) -> Result<FixedOffset, String> {
+    if value.eq_ignore_ascii_case("z") || value.eq_ignore_ascii_case("utc") {
+        return Ok(Utc.fix());
+    }
+    value.parse().map_err(|_| "expected a UTC offset such as +02:00 or -05:30".to_string())
+}
+
+/// Parses a `--from`/`--until` value as RFC3339, or as a geth-style `MM-DD|HH:MM:SS` completed with `year`
+/// and read at `offset` when given, else in local time.
+fn parse_time_bound(
+    value: &str,
+    year: i32,
+    offset: Option<FixedOffset>,
+    flag: &str,
+) -> Result<DateTime<Local>, Box<dyn std::error::Error>> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Ok(dt.with_timezone(&Local));
+    }
+
+    let bound = match offset {
+        Some(offset) => parse_bracket_timestamp_at(value, year, offset),
+        None => parse_bracket_timestamp(value, year),
+    };
+    bound.ok_or_else(|| {
+        format!(
+            "Error: Invalid {} timestamp '{}'. Expected RFC3339 (e.g. 2024-07-04T15:23:01Z) or MM-DD|HH:MM:SS",
+            flag, value
+        )
+        .into()
+    })
+}
+
+/// Decides whether human-readable output should be colored.
+///
+/// Color is only used when writing to stdout, stdout is a terminal, and `NO_COLOR` is unset or empty.
+fn use_color(writing_to_stdout: bool) -> bool {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    writing_to_stdout && !no_color && io::stdout().is_terminal()
+}
+
+/// Validates that the provided path exists, returning the files it stands for: the path itself
+/// for a file, or the files `scan` matches for a directory.
+fn validate_path(path: &Path, scan: &DirectoryScan) -> Result<Vec<String>, Box<dyn std::error::Error>> {
     if !path.exists() {
         return Err(format!("Error: File not found at path '{}'", path.display()).into());
     }
 
+    if path.is_dir() {
+        let files = scan.files_in(path)?;
+        if files.is_empty() {
+            return Err(format!("Error: No files matching '{}' in directory '{}'", scan.pattern, path.display()).into());
+        }
+        return Ok(files.iter().map(|file| file.display().to_string()).collect());
+    }
+
     if !path.is_file() {
-        return Err(format!("Error: The path '{}' is a directory, not a file", path.display()).into());
+        return Err(format!("Error: The path '{}' is not a file or directory", path.display()).into());
+    }
+
+    Ok(vec![path.display().to_string()])
+}
+
+/// Compiles a file name glob, where `*` matches any run of characters and `?` any one, to a regex
+/// matching whole names.
+fn glob_regex(pattern: &str) -> Regex {
+    let mut regex = String::from("^");
+    for c in pattern.chars() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            _ => regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
     }
+    regex.push('$');
+    Regex::new(&regex).expect("escaped glob is a valid regex")
+}
 
-    Ok(())
+/// Parses an `--output` value: `FORMAT=PATH` (e.g. `csv=report.csv`), or a plain path in the
+/// `--format` format. A path containing `=` whose prefix isn't a format name is taken as is.
+fn parse_output_spec(value: &str) -> Result<OutputSpec, String> {
+    if let Some((prefix, path)) = value.split_once('=')
+        && let Ok(format) = OutputFormat::from_str(prefix, true)
+    {
+        if path.is_empty() {
+            return Err(format!("expected a path after '{}='", prefix));
+        }
+        return Ok(OutputSpec { format: Some(format), path: path.to_string() });
+    }
+    Ok(OutputSpec { format: None, path: value.to_string() })
+}
+
+/// Parses a `--files-from` list: one path per line, skipping blank lines and `#` comments.
+fn parse_file_list(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect()
 }
 
 /// Sets up a bar-style progress bar for file processing, based on bytes.
-fn setup_progress_bar(total_bytes: u64) -> ProgressBar {
-    let pb = ProgressBar::new(total_bytes); // Progress bar based on bytes
-    pb.set_style(
-        ProgressStyle::default_bar()
-            .template("{spinner:.green}[{bar:40.cyan/blue}] {bytes}/{total_bytes} ({percent}%) {msg}")
-            .expect("Invalid progress bar template")
-            .progress_chars("#>-"),
-    );
-    pb
+///
+/// When the total size is unknown (e.g. stdin), falls back to a spinner.
+fn setup_progress_bar(total_bytes: Option<u64>) -> ProgressBar {
+    match total_bytes {
+        Some(total_bytes) => {
+            let pb = ProgressBar::new(total_bytes); // Progress bar based on bytes
+            pb.set_style(
+                ProgressStyle::default_bar()
+                    .template("{spinner:.green}[{bar:40.cyan/blue}] {bytes}/{total_bytes} ({percent}%) {msg}")
+                    .expect("Invalid progress bar template")
+                    .progress_chars("#>-"),
+            );
+            pb
+        }
+        None => {
+            let pb = ProgressBar::new_spinner(); // Spinner showing bytes read so far
+            pb.set_style(
+                ProgressStyle::default_spinner()
+                    .template("{spinner:.green} {bytes} read {msg}")
+                    .expect("Invalid spinner template"),
+            );
+            pb
+        }
+    }
 }
 
 // --- UNIT TESTS ---
@@ -203,45 +1203,135 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_parse_line_happy_path() {
-        let line = "INFO [11-08|10:49:09.123] Imported new block headers count=1 elapsed=2.5s";
-        let year = 2025;
+    fn test_compile_grep_rejects_empty_and_invalid_patterns() {
+        assert!(compile_grep("").unwrap_err().to_string().contains("must not be empty"));
+        assert!(compile_grep("(unclosed").unwrap_err().to_string().contains("Invalid --grep regex"));
+    }
+
+    #[test]
+    fn test_parse_file_list_skips_blanks_and_comments() {
+        let contents = "# nightly inventory\n/var/log/geth-1.log\n\n  /var/log/geth-2.log  \n   # retired\n";
 
-        let result = parse_line(line, year);
+        assert_eq!(parse_file_list(contents), ["/var/log/geth-1.log", "/var/log/geth-2.log"]);
+    }
 
-        let log_entry = result.unwrap();
+    #[test]
+    fn test_directory_scan_matches_glob_and_recurses() {
+        let dir = std::env::temp_dir().join(format!("glc-scan-{}", std::process::id()));
+        fs::create_dir_all(dir.join("rotated")).unwrap();
+        for name in ["b.log", "a.log", "notes.txt", "rotated/c.log", "rotated/c.log.1"] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+        let names = |scan: DirectoryScan| -> Vec<String> {
+            let files = scan.files_in(&dir).unwrap();
+            files.iter().map(|path| path.strip_prefix(&dir).unwrap().display().to_string()).collect()
+        };
 
-        assert_eq!(log_entry.level, "INFO");
-        assert_eq!(log_entry.details.get("count"), Some(&"1".to_string()));
+        let flat = names(DirectoryScan::new("*.log", false, FileOrder::Name));
+        let recursive = names(DirectoryScan::new("*.log*", true, FileOrder::Name));
+        let error = validate_path(&dir, &DirectoryScan::new("*.gz", true, FileOrder::Name)).unwrap_err();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(flat, ["a.log", "b.log"]);
+        assert_eq!(recursive, ["a.log", "b.log", "rotated/c.log", "rotated/c.log.1"]);
+        assert!(error.to_string().starts_with("Error: No files matching '*.gz' in directory"));
+        assert!(glob_regex("geth-????.log").is_match("geth-2025.log"));
+        assert!(!glob_regex("*.log").is_match("geth.log.gz"));
+        assert!(!glob_regex("a.log").is_match("aXlog"));
     }
 
     #[test]
-    fn test_parse_line_no_kv_pairs() {
-        let line = "INFO [11-08|10:49:41] Block synchronisation started ";
-        let year = 2025;
-        let result = parse_line(line, year);
-        
-        assert!(result.is_some()); 
-        let log_entry = result.unwrap();
-        assert!(log_entry.details.is_empty()); 
+    fn test_parse_output_spec_format_prefix() {
+        let csv = parse_output_spec("csv=report.csv").unwrap();
+        let plain = parse_output_spec("parsed.ndjson").unwrap();
+        let not_a_format = parse_output_spec("out/a=b.ndjson").unwrap();
+
+        assert_eq!((csv.format, csv.path.as_str()), (Some(OutputFormat::Csv), "report.csv"));
+        assert_eq!((plain.format, plain.path.as_str()), (None, "parsed.ndjson"));
+        assert_eq!((not_a_format.format, not_a_format.path.as_str()), (None, "out/a=b.ndjson"));
+        assert!(parse_output_spec("json=").is_err());
     }
 
     #[test]
-    fn test_parse_line_no_milliseconds_or_space() {
-        let line = "DEBUG[11-08|10:49:09] Recalculated downloader QoS values";
-        let year = 2025;
-        let result = parse_line(line, year);
-        
-        assert!(result.is_some()); 
-        assert_eq!(result.unwrap().level, "DEBUG");
+    fn test_parse_positive_rejects_zero() {
+        assert_eq!(parse_positive("4"), Ok(4));
+        assert!(parse_positive("0").is_err());
+        assert!(parse_positive("-2").is_err());
+        assert!(parse_positive("many").is_err());
     }
 
     #[test]
-    fn test_parse_line_garbage_input() {
-        let line = "this is not a valid log line";
-        let year = 2025;
-        let result = parse_line(line, year);
-        
-        assert!(result.is_none());
+    fn test_parse_percentage_bounds() {
+        assert_eq!(parse_percentage("12.5"), Ok(12.5));
+        assert_eq!(parse_percentage("0"), Ok(0.0));
+        assert!(parse_percentage("100.1").is_err());
+        assert!(parse_percentage("-1").is_err());
+        assert!(parse_percentage("ten").is_err());
+    }
+
+    #[test]
+    fn test_parse_detail_filter_forms() {
+        assert_eq!(
+            parse_detail_filter("peer=ab=cd").unwrap(),
+            DetailFilter { key: "peer".to_string(), value: Some("ab=cd".to_string()) }
+        );
+        assert_eq!(parse_detail_filter("hash=*").unwrap(), DetailFilter { key: "hash".to_string(), value: None });
+        assert!(parse_detail_filter("hash").is_err());
+        assert!(parse_detail_filter("=0xabc").is_err());
+    }
+
+    #[test]
+    fn test_parse_time_bound_formats() {
+        let rfc = parse_time_bound("2025-11-08T10:15:00Z", 2025, None, "--from").unwrap();
+        assert_eq!(rfc.with_timezone(&chrono::Utc).to_rfc3339(), "2025-11-08T10:15:00+00:00");
+
+        let bracket = parse_time_bound("11-08|10:15:00", 2023, None, "--from").unwrap();
+        assert_eq!(bracket.year(), 2023);
+
+        let utc = parse_time_bound("11-08|10:15:00", 2025, Some(Utc.fix()), "--from").unwrap();
+        assert_eq!(utc, rfc);
+
+        assert!(parse_time_bound("yesterday", 2025, None, "--from").is_err());
+
+        let east = FixedOffset::east_opt(2 * 3600).unwrap();
+        let shifted = parse_time_bound("11-08|12:15:00", 2025, Some(east), "--from").unwrap();
+        assert_eq!(shifted, rfc);
+    }
+
+    #[test]
+    fn test_parse_offset_forms() {
+        assert_eq!(parse_offset("+02:00"), Ok(FixedOffset::east_opt(2 * 3600).unwrap()));
+        assert_eq!(parse_offset("-0530"), Ok(FixedOffset::west_opt(5 * 3600 + 30 * 60).unwrap()));
+        assert_eq!(parse_offset("Z"), Ok(Utc.fix()));
+        assert!(parse_offset("Europe/Berlin").is_err());
+    }
+
+    #[test]
+    fn test_subcommands_resolve_to_parse_flags() {
+        use clap::CommandFactory;
+        Cli::command().debug_assert();
+        let resolve = |argv: &[&str]| {
+            let cli = Cli::try_parse_from(["geth-log-cruncher"].iter().chain(argv)).unwrap();
+            match cli.command {
+                Some(command) => command.into_parse_args(),
+                None => Ok(cli.parse),
+            }
+        };
+
+        let stats = resolve(&["stats", "geth.log", "--top", "3", "--level", "warn"]).unwrap();
+        let legacy = resolve(&["geth.log", "--stats", "--top", "3", "--level", "warn"]).unwrap();
+        let tail = resolve(&["tail", "-n", "5", "geth.log"]).unwrap();
+        let follow = resolve(&["follow", "geth.log", "--retries", "2"]).unwrap();
+
+        assert!(stats.stats && legacy.stats);
+        assert_eq!((stats.top, legacy.top), (3, 3));
+        assert_eq!(stats.common.level, legacy.common.level);
+        assert_eq!(stats.common.log_file_paths, vec!["geth.log"]);
+        assert_eq!(tail.tail, Some(5));
+        assert!(follow.follow && follow.follow_retries == 2);
+        assert!(resolve(&["parse", "geth.log"]).is_ok_and(|parse| !parse.stats && parse.tail.is_none()));
+        assert!(resolve(&["stats", "geth.log", "--group-by", "peer"]).unwrap_err().contains("--group-by"));
+        assert!(resolve(&["follow", "geth.log", "--state", "geth.state"]).unwrap_err().contains("--state"));
+        assert!(Cli::try_parse_from(["geth-log-cruncher", "stats", "geth.log", "--stats"]).is_err());
     }
 }
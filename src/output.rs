@@ -0,0 +1,754 @@
+//! Output sinks that serialize parsed `LogEntry` values in the supported formats.
+
+// --- IMPORTS ---
+// Standard library imports
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+// Third-party libraries
+use chrono::{DateTime, Local, SecondsFormat};
+use clap::ValueEnum;
+use owo_colors::OwoColorize;
+use serde::{Serialize, Serializer};
+use serde_json::Value;
+
+// Local crate
+use crate::stats::{GroupStats, LogStats};
+use crate::{message_text, output_level, output_timestamp, parse_metric, serialize_timestamp, LogEntry};
+
+// --- DATA STRUCTURES ---
+/// Serialization formats for emitted entries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Json,  // One JSON object per entry (NDJSON unless pretty-printed)
+    JsonArray, // A single JSON array of entries, streamed one element at a time
+    Csv,   // `level,timestamp,message` records (or the `set_csv_columns` columns) after a header row
+    Human, // Compact `timestamp level message` lines for reading in a terminal
+    Logfmt, // `t=... lvl=... msg="..." key=value` lines, as geth's logfmt backend writes them
+}
+
+/// How `--split-by` partitions entries into separate files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SplitBy {
+    Day,    // One `YYYY-MM-DD.ndjson` file per calendar day of the entry timestamp (at the output offset, see `set_output_offset`)
+    Level,  // One file per level, named after it in lowercase, e.g. `error.ndjson`
+}
+
+/// Writes entries to an underlying writer in the selected format.
+pub enum EntryWriter<W: Write> {
+    Json { out: W, pretty: bool },  // `pretty` switches to indented JSON
+    JsonArray { out: W, pretty: bool, empty: bool },  // `empty` until the first element, which gets no leading comma
+    Csv(Box<csv::Writer<W>>),       // `details` only appears as the keys chosen with `set_csv_columns`
+    Human { out: W, color: bool },  // `color` enables ANSI colors for levels and details
+    Logfmt(W),                      // One logfmt line per entry
+    Stats { out: W, stats: Box<LogStats>, top: usize },  // Aggregates entries, reporting once at the end
+    Grouped { out: W, groups: Box<GroupStats> },  // Rolls entries up per `details` value, one NDJSON line per group at the end
+    Pivot { out: PivotOut<W>, key: String, points: usize },  // Only `timestamp,value` pairs of one numeric `details` key
+    Discard,                        // Drops every entry without serializing it
+    Split { dir: PathBuf, split_by: SplitBy, files: BTreeMap<String, SplitFile> },  // NDJSON routed to partition files in `dir`
+    Multi(Vec<OutputSink<W>>),      // Every entry written to each sink in turn, in a single pass
+}
+
+/// One destination of a multi-sink writer.
+pub struct OutputSink<W: Write> {
+    pub name: String,            // Label for the run summary, e.g. `csv=report.csv`
+    pub writer: EntryWriter<W>,  // Writer for this sink's format
+    pub written: usize,          // Entries written to this sink so far
+}
+
+/// One partition file of split output.
+pub struct SplitFile {
+    out: BufWriter<File>,  // The open partition file
+    written: usize,        // Entries written to it so far
+}
+
+/// Where `--pivot` writes its points.
+pub enum PivotOut<W: Write> {
+    Json(W),                   // One `{"timestamp":...,"value":...}` object per line
+    Csv(Box<csv::Writer<W>>),  // A `timestamp,value` record per point
+}
+
+/// One point of a `--pivot` series.
+#[derive(Serialize)]
+struct PivotPoint<'a> {
+    timestamp: CsvTimestamp<'a>,  // The entry's timestamp, in the current `TimeFormat`
+    value: f64,                   // The key's value, parsed like a metric
+}
+
+/// A timestamp that serializes in the current `TimeFormat`, for CSV records and pivot points.
+struct CsvTimestamp<'a>(&'a DateTime<Local>);
+
+/// One cell of a CSV record.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum CsvField<'a> {
+    Text(&'a str),                // The level, message or a `details` value; empty when the key is missing
+    Timestamp(CsvTimestamp<'a>),  // The entry's timestamp, in the current `TimeFormat`
+}
+
+// --- GLOBAL VARIABLES ---
+/// CSV columns when none are chosen with [`set_csv_columns`].
+pub const DEFAULT_CSV_COLUMNS: [&str; 3] = ["level", "timestamp", "message"];
+
+/// Prefix of the top-level keys `details` pairs become under [`set_flatten_details`].
+pub const FLAT_DETAILS_PREFIX: &str = "d_";
+
+/// `--split-by` partition, without the extension, for entries lacking the value they're split by.
+pub const UNKNOWN_PARTITION: &str = "unknown";
+
+thread_local! {
+    // Whether JSON entries carry their `details` as prefixed top-level keys instead of a nested object
+    static FLATTEN_DETAILS: Cell<bool> = const { Cell::new(false) };
+
+    // CSV columns in order: `level`, `timestamp`, `message`, or a `details` key; empty for the defaults
+    static CSV_COLUMNS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+// --- IMPLEMENTATIONS ---
+impl Serialize for CsvTimestamp<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_timestamp(self.0, serializer)
+    }
+}
+
+impl<W: Write> EntryWriter<W> {
+    /// Creates a writer for `format`, emitting any header the format needs up front.
+    ///
+    /// `pretty` only applies to JSON output.
+    pub fn new(out: W, format: OutputFormat, pretty: bool) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::with_header(out, format, pretty, true)
+    }
+
+    /// Like [`EntryWriter::new`], for output appended after entries written earlier: the CSV
+    /// header isn't repeated.
+    pub fn continuing(out: W, format: OutputFormat, pretty: bool) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::with_header(out, format, pretty, false)
+    }
+
+    /// Creates a writer for `format`, writing the CSV header only if `header` is set.
+    fn with_header(mut out: W, format: OutputFormat, pretty: bool, header: bool) -> Result<Self, Box<dyn std::error::Error>> {
+        match format {
+            OutputFormat::Json => Ok(EntryWriter::Json { out, pretty }),
+            OutputFormat::JsonArray => {
+                write!(out, "[")?;
+                Ok(EntryWriter::JsonArray { out, pretty, empty: true })
+            }
+            OutputFormat::Csv => {
+                let mut writer = csv::WriterBuilder::new().has_headers(false).from_writer(out);
+                if header {
+                    CSV_COLUMNS.with_borrow(|columns| {
+                        if columns.is_empty() { writer.write_record(DEFAULT_CSV_COLUMNS) } else { writer.write_record(columns) }
+                    })?;
+                }
+                Ok(EntryWriter::Csv(Box::new(writer)))
+            }
+            OutputFormat::Human => Ok(EntryWriter::human(out, false)),
+            OutputFormat::Logfmt => Ok(EntryWriter::Logfmt(out)),
+        }
+    }
+
+    /// Creates a human-readable writer, coloring levels and dimming details when `color` is set.
+    pub fn human(out: W, color: bool) -> Self {
+        EntryWriter::Human { out, color }
+    }
+
+    /// Creates a writer that aggregates entries and writes a single stats report from `finish`.
+    ///
+    /// The report keeps the `top` most frequent normalized messages.
+    pub fn stats(out: W, top: usize) -> Self {
+        EntryWriter::Stats { out, stats: Box::default(), top }
+    }
+
+    /// Creates a writer that rolls entries up by the `details` value of `key`, writing one NDJSON
+    /// summary per group from `finish`.
+    pub fn grouped(out: W, key: &str) -> Self {
+        EntryWriter::Grouped { out, groups: Box::new(GroupStats::new(key)) }
+    }
+
+    /// Creates a writer that emits only the timestamp and numeric value of `key` for each entry
+    /// carrying it, as NDJSON or CSV (with a `timestamp,value` header if `header` is set).
+    pub fn pivot(out: W, key: &str, format: OutputFormat, header: bool) -> Result<Self, Box<dyn std::error::Error>> {
+        let out = match format {
+            OutputFormat::Json => PivotOut::Json(out),
+            OutputFormat::Csv => {
+                let mut writer = csv::WriterBuilder::new().has_headers(false).from_writer(out);
+                if header {
+                    writer.write_record(["timestamp", "value"])?;
+                }
+                PivotOut::Csv(Box::new(writer))
+            }
+            _ => {
+                let name = format.to_possible_value().map(|value| value.get_name().to_string()).unwrap_or_default();
+                return Err(format!("Error: --pivot writes json or csv, not {}", name).into());
+            }
+        };
+        Ok(EntryWriter::Pivot { out, key: key.to_string(), points: 0 })
+    }
+
+    /// Creates a writer that discards entries, for runs that only need the line counts.
+    pub fn discard() -> Self {
+        EntryWriter::Discard
+    }
+
+    /// Creates a writer that routes NDJSON entries into one file per partition under `dir`.
+    ///
+    /// `dir` is created if needed; partition files are created (or truncated) on first use.
+    pub fn split(dir: &Path, split_by: SplitBy) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(EntryWriter::Split { dir: dir.to_path_buf(), split_by, files: BTreeMap::new() })
+    }
+
+    /// Creates a writer that fans every entry out to each of `sinks`, given as `(name, writer)` pairs.
+    pub fn multi(sinks: Vec<(String, EntryWriter<W>)>) -> Self {
+        EntryWriter::Multi(sinks.into_iter().map(|(name, writer)| OutputSink { name, writer, written: 0 }).collect())
+    }
+
+    /// Each sink's name and the entries written to it, for multi-sink output.
+    pub fn sink_counts(&self) -> Option<Vec<(&str, usize)>> {
+        match self {
+            EntryWriter::Multi(sinks) => Some(sinks.iter().map(|sink| (sink.name.as_str(), sink.written)).collect()),
+            _ => None,
+        }
+    }
+
+    /// Number of points written so far, for `--pivot` output.
+    pub fn pivot_points(&self) -> Option<usize> {
+        match self {
+            EntryWriter::Pivot { points, .. } => Some(*points),
+            _ => None,
+        }
+    }
+
+    /// Number of partition files written so far, for split output.
+    pub fn files_written(&self) -> Option<usize> {
+        match self {
+            EntryWriter::Split { files, .. } => Some(files.len()),
+            _ => None,
+        }
+    }
+
+    /// Each partition file's name and the entries written to it, in name order, for split output.
+    pub fn split_counts(&self) -> Option<Vec<(String, usize)>> {
+        match self {
+            EntryWriter::Split { files, .. } => {
+                Some(files.iter().map(|(partition, file)| (format!("{}.ndjson", partition), file.written)).collect())
+            }
+            _ => None,
+        }
+    }
+
+    /// Serializes a single entry to the underlying writer.
+    pub fn write_entry(&mut self, entry: &LogEntry) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            EntryWriter::Json { out, pretty } => writeln!(out, "{}", entry_json(entry, *pretty)?)?,
+            EntryWriter::JsonArray { out, pretty, empty } => {
+                let separator = if *empty { "" } else { "," };
+                write!(out, "{}\n{}", separator, entry_json(entry, *pretty)?)?;
+                *empty = false;
+            }
+            EntryWriter::Csv(writer) => write_csv_record(writer, entry)?,
+            EntryWriter::Human { out, color } => write_human_line(out, entry, *color)?,
+            EntryWriter::Logfmt(out) => write_logfmt_line(out, entry)?,
+            EntryWriter::Stats { stats, .. } => stats.record(entry),
+            EntryWriter::Grouped { groups, .. } => groups.record(entry),
+            EntryWriter::Pivot { out, key, points } => {
+                // Entries without the key, or with a value that isn't a number, are no point.
+                let Some(value) = entry.details.get(key.as_str()).and_then(|value| parse_metric(value)) else {
+                    return Ok(());
+                };
+                let point = PivotPoint { timestamp: CsvTimestamp(&entry.timestamp), value };
+                match out {
+                    PivotOut::Json(out) => writeln!(out, "{}", serde_json::to_string(&point)?)?,
+                    PivotOut::Csv(writer) => writer.serialize(point)?,
+                }
+                *points += 1;
+            }
+            EntryWriter::Discard => {}
+            EntryWriter::Split { dir, split_by, files } => {
+                let partition = match split_by {
+                    SplitBy::Day => output_timestamp(&entry.timestamp).format("%Y-%m-%d").to_string(),
+                    SplitBy::Level => level_file_stem(&entry.level),
+                };
+                let out = match files.entry(partition) {
+                    std::collections::btree_map::Entry::Occupied(file) => file.into_mut(),
+                    std::collections::btree_map::Entry::Vacant(slot) => {
+                        let path = dir.join(format!("{}.ndjson", slot.key()));
+                        let file = File::create(&path)
+                            .map_err(|e| format!("Error: Could not create output file '{}': {}", path.display(), e))?;
+                        slot.insert(SplitFile { out: BufWriter::new(file), written: 0 })
+                    }
+                };
+                writeln!(out.out, "{}", entry_json(entry, false)?)?;
+                out.written += 1;
+            }
+            EntryWriter::Multi(sinks) => {
+                for sink in sinks {
+                    sink.writer.write_entry(entry)?;
+                    sink.written += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes anything the format emits after the last entry, then flushes.
+    pub fn finish(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            EntryWriter::JsonArray { out, empty, .. } => writeln!(out, "{}]", if *empty { "" } else { "\n" })?,
+            EntryWriter::Stats { out, stats, top } => writeln!(out, "{}", serde_json::to_string(&stats.report(*top))?)?,
+            EntryWriter::Grouped { out, groups } => {
+                for summary in groups.summaries() {
+                    writeln!(out, "{}", serde_json::to_string(&summary)?)?;
+                }
+            }
+            EntryWriter::Multi(sinks) => return sinks.iter_mut().try_for_each(|sink| sink.writer.finish()),
+            _ => {}
+        }
+        self.flush()?;
+        Ok(())
+    }
+
+    /// Flushes any buffered output to the underlying writer.
+    pub fn flush(&mut self) -> io::Result<()> {
+        match self {
+            EntryWriter::Json { out, .. }
+            | EntryWriter::JsonArray { out, .. }
+            | EntryWriter::Human { out, .. }
+            | EntryWriter::Stats { out, .. }
+            | EntryWriter::Grouped { out, .. }
+            | EntryWriter::Logfmt(out) => out.flush(),
+            EntryWriter::Csv(writer) => writer.flush(),
+            EntryWriter::Pivot { out: PivotOut::Json(out), .. } => out.flush(),
+            EntryWriter::Pivot { out: PivotOut::Csv(writer), .. } => writer.flush(),
+            EntryWriter::Discard => Ok(()),
+            EntryWriter::Split { files, .. } => files.values_mut().try_for_each(|file| file.out.flush()),
+            EntryWriter::Multi(sinks) => sinks.iter_mut().try_for_each(|sink| sink.writer.flush()),
+        }
+    }
+}
+
+// --- HELPER FUNCTIONS ---
+/// The `--split-by level` file name for `level`, without the extension: lowercased, with anything
+/// but letters and digits (which a custom `--pattern` could capture) replaced so it stays in the
+/// output directory. A level with no letters or digits at all, such as an empty one from
+/// `--input-format json`, goes to [`UNKNOWN_PARTITION`].
+fn level_file_stem(level: &str) -> String {
+    if !level.chars().any(|c| c.is_ascii_alphanumeric()) {
+        return UNKNOWN_PARTITION.to_string();
+    }
+    level.chars().map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' }).collect()
+}
+
+/// Sets whether JSON entries are written flat on the current thread: each `details` pair becomes
+/// a top-level key prefixed with [`FLAT_DETAILS_PREFIX`], and there is no `details` object.
+///
+/// Call this on the thread that writes the output.
+pub fn set_flatten_details(flatten: bool) {
+    FLATTEN_DETAILS.with(|current| current.set(flatten));
+}
+
+/// Sets the CSV columns on the current thread, in order: `level`, `timestamp` and `message` are
+/// the entry's fields, and any other name is a `details` key, written as an empty cell when an
+/// entry lacks it. An empty list restores [`DEFAULT_CSV_COLUMNS`].
+///
+/// Call this on the thread that writes the output.
+pub fn set_csv_columns(columns: Vec<String>) {
+    CSV_COLUMNS.with(|current| *current.borrow_mut() = columns);
+}
+
+/// Writes an entry as one CSV record in the [`set_csv_columns`] order.
+fn write_csv_record<W: Write>(writer: &mut csv::Writer<W>, entry: &LogEntry) -> csv::Result<()> {
+    let level = output_level(&entry.level);
+    let field = |column: &str| match column {
+        "level" => CsvField::Text(&level),
+        "timestamp" => CsvField::Timestamp(CsvTimestamp(&entry.timestamp)),
+        "message" => CsvField::Text(&entry.message),
+        key => CsvField::Text(entry.details.get(key).map_or("", String::as_str)),
+    };
+    CSV_COLUMNS.with_borrow(|columns| {
+        if columns.is_empty() {
+            writer.serialize(DEFAULT_CSV_COLUMNS.map(field))
+        } else {
+            writer.serialize(columns.iter().map(|column| field(column)).collect::<Vec<_>>())
+        }
+    })
+}
+
+/// Returns `entry` as a JSON object with its `details` merged into the top level, e.g.
+/// `details.peer` as `d_peer`. No field starts with the prefix, so a key like `level` can't
+/// clobber one.
+pub fn flatten_entry(entry: &LogEntry) -> serde_json::Result<Value> {
+    let mut value = serde_json::to_value(entry)?;
+    let Value::Object(fields) = &mut value else {
+        return Ok(value);
+    };
+    if let Some(Value::Object(details)) = fields.remove("details") {
+        for (key, detail) in details {
+            fields.insert(format!("{}{}", FLAT_DETAILS_PREFIX, key), detail);
+        }
+    }
+    Ok(value)
+}
+
+/// Serializes one entry as JSON, flattened under [`set_flatten_details`].
+fn entry_json(entry: &LogEntry, pretty: bool) -> serde_json::Result<String> {
+    match (FLATTEN_DETAILS.with(Cell::get), pretty) {
+        (true, true) => serde_json::to_string_pretty(&flatten_entry(entry)?),
+        (true, false) => serde_json::to_string(&flatten_entry(entry)?),
+        (false, true) => serde_json::to_string_pretty(entry),
+        (false, false) => serde_json::to_string(entry),
+    }
+}
+
+/// Writes `timestamp level message key=value...` as a single line, followed by any
+/// continuation lines indented beneath it. The pairs are taken out of the message and written
+/// from `details`, so each appears once.
+fn write_human_line<W: Write>(out: &mut W, entry: &LogEntry, color: bool) -> io::Result<()> {
+    let timestamp = output_timestamp(&entry.timestamp).format("%Y-%m-%d %H:%M:%S%.3f");
+    let level = format!("{:<5}", output_level(&entry.level));
+    let message = message_text(&entry.message);
+    let details: String = entry
+        .details
+        .iter()
+        .map(|(key, value)| format!(" {}={}", key, value))
+        .collect();
+
+    if color {
+        let level = match entry.level.as_str() {
+            "ERROR" => level.red().bold().to_string(),
+            "WARN" => level.yellow().to_string(),
+            "INFO" => level.green().to_string(),
+            "DEBUG" => level.cyan().to_string(),
+            _ => level.dimmed().to_string(),
+        };
+        writeln!(out, "{} {} {}{}", timestamp, level, message, details.dimmed())?;
+    } else {
+        writeln!(out, "{} {} {}{}", timestamp, level, message, details)?;
+    }
+
+    for line in &entry.continuation {
+        writeln!(out, "    {}", line)?;
+    }
+    Ok(())
+}
+
+/// Writes an entry as a logfmt line: `t`, `lvl` and `msg` (the message without its pairs), then
+/// each `details` pair in key order. A key collected as an array is repeated once per value.
+/// `lvl` is always lowercase, as geth writes it, whatever `--level-case` and `--level-style` say.
+fn write_logfmt_line<W: Write>(out: &mut W, entry: &LogEntry) -> io::Result<()> {
+    let timestamp = output_timestamp(&entry.timestamp).to_rfc3339_opts(SecondsFormat::Millis, true);
+    write!(out, "t={} lvl={} msg={}", timestamp, logfmt_value(&entry.level.to_lowercase()), logfmt_value(&message_text(&entry.message)))?;
+    for (key, value) in entry.details.iter() {
+        match entry.details.repeated(key) {
+            Some(values) => values.iter().try_for_each(|value| write!(out, " {}={}", key, logfmt_value(value)))?,
+            None => write!(out, " {}={}", key, logfmt_value(value))?,
+        }
+    }
+    writeln!(out)
+}
+
+/// Quotes a logfmt value that is empty or contains spaces, `=` or `"`, escaping as the logfmt
+/// parser expects; other values are written as is.
+fn logfmt_value(value: &str) -> String {
+    let needs_quotes = value.is_empty() || value.chars().any(|c| c.is_whitespace() || c == '=' || c == '"' || c == '\\');
+    if !needs_quotes {
+        return value.to_string();
+    }
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+// --- UNIT TESTS ---
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::details::DuplicateKeyPolicy;
+    use crate::{parse_line, set_time_format, LineParser, TimeFormat};
+
+    #[test]
+    fn test_csv_output_uses_time_format() {
+        let entry = parse_line("INFO [11-08|10:49:09.250] Started", 2025).unwrap();
+        let mut out = Vec::new();
+
+        set_time_format(TimeFormat::EpochMs);
+        let mut writer = EntryWriter::new(&mut out, OutputFormat::Csv, false).unwrap();
+        writer.write_entry(&entry).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+        set_time_format(TimeFormat::Rfc3339);
+
+        let output = String::from_utf8(out).unwrap();
+        assert_eq!(output.lines().nth(1), Some(format!("INFO,{},Started", entry.timestamp.timestamp_millis()).as_str()));
+    }
+
+    #[test]
+    fn test_csv_columns_pick_fields_and_details_keys() {
+        let entry = parse_line("WARN [11-08|10:49:09] Stale peer peer=abc reason=\"read timeout, closing\"", 2025).unwrap();
+        let mut out = Vec::new();
+
+        set_csv_columns(["peer", "level", "number", "message", "reason"].map(String::from).to_vec());
+        let mut writer = EntryWriter::new(&mut out, OutputFormat::Csv, false).unwrap();
+        writer.write_entry(&entry).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+        set_csv_columns(Vec::new());
+
+        let output = String::from_utf8(out).unwrap();
+        assert_eq!(
+            output,
+            "peer,level,number,message,reason\n\
+             abc,WARN,,\"Stale peer peer=abc reason=\"\"read timeout, closing\"\"\",\"read timeout, closing\"\n"
+        );
+    }
+
+    #[test]
+    fn test_flatten_details_moves_pairs_to_top_level() {
+        let parser = LineParser { on_duplicate_key: DuplicateKeyPolicy::Array, ..LineParser::new(2025) };
+        let entry = parser.parse("WARN [11-08|10:49:09] Dropping peer level=3 peer=a peer=b").unwrap();
+        let mut out = Vec::new();
+
+        set_flatten_details(true);
+        let mut writer = EntryWriter::new(&mut out, OutputFormat::Json, false).unwrap();
+        writer.write_entry(&entry).unwrap();
+        drop(writer);
+        set_flatten_details(false);
+
+        let flat: Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(flat["level"], "WARN");
+        assert_eq!(flat["d_level"], "3");
+        assert_eq!(flat["d_peer"], serde_json::json!(["a", "b"]));
+        assert!(flat.get("details").is_none());
+        assert_eq!(flat["message"], entry.message);
+    }
+
+    #[test]
+    fn test_json_array_output_parses_as_array() {
+        let entries = [
+            parse_line("INFO [11-08|10:49:09] Started", 2025).unwrap(),
+            parse_line("WARN [11-08|10:49:10] Stale peer id=abc", 2025).unwrap(),
+        ];
+        for pretty in [false, true] {
+            let mut out = Vec::new();
+            let mut empty_out = Vec::new();
+
+            let mut writer = EntryWriter::new(&mut out, OutputFormat::JsonArray, pretty).unwrap();
+            entries.iter().for_each(|entry| writer.write_entry(entry).unwrap());
+            writer.finish().unwrap();
+            drop(writer);
+            EntryWriter::new(&mut empty_out, OutputFormat::JsonArray, pretty).unwrap().finish().unwrap();
+
+            let parsed: Vec<LogEntry> = serde_json::from_slice(&out).unwrap();
+            assert_eq!(parsed.len(), 2);
+            assert_eq!(parsed[1].details["id"], "abc");
+            assert_eq!(String::from_utf8(empty_out).unwrap(), "[]\n");
+        }
+    }
+
+    #[test]
+    fn test_csv_output_quotes_message() {
+        let entry = parse_line(r#"WARN [11-08|10:49:09] Dropping peer, "bad" handshake reason=timeout"#, 2025).unwrap();
+        let mut out = Vec::new();
+
+        let mut writer = EntryWriter::new(&mut out, OutputFormat::Csv, false).unwrap();
+        writer.write_entry(&entry).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+
+        let output = String::from_utf8(out).unwrap();
+        let mut lines = output.lines();
+        assert_eq!(lines.next(), Some("level,timestamp,message"));
+        let record = lines.next().unwrap();
+        assert!(record.starts_with("WARN,2025-11-08T10:49:09"));
+        assert!(record.ends_with(r#","Dropping peer, ""bad"" handshake reason=timeout""#));
+    }
+
+    #[test]
+    fn test_csv_output_header_without_entries() {
+        let mut out = Vec::new();
+
+        let mut writer = EntryWriter::new(&mut out, OutputFormat::Csv, false).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+
+        assert_eq!(String::from_utf8(out).unwrap(), "level,timestamp,message\n");
+    }
+
+    #[test]
+    fn test_multi_output_writes_every_sink() {
+        let entry = parse_line("INFO [11-08|10:49:09] Started", 2025).unwrap();
+        let mut json = Vec::new();
+        let mut csv = Vec::new();
+
+        let mut writer = EntryWriter::multi(vec![
+            ("json=a.ndjson".to_string(), EntryWriter::new(&mut json, OutputFormat::Json, false).unwrap()),
+            ("csv=b.csv".to_string(), EntryWriter::new(&mut csv, OutputFormat::Csv, false).unwrap()),
+        ]);
+        writer.write_entry(&entry).unwrap();
+        writer.write_entry(&entry).unwrap();
+        writer.finish().unwrap();
+        assert_eq!(writer.sink_counts(), Some(vec![("json=a.ndjson", 2), ("csv=b.csv", 2)]));
+        drop(writer);
+
+        assert_eq!(String::from_utf8(json).unwrap().lines().count(), 2);
+        assert_eq!(String::from_utf8(csv).unwrap().lines().count(), 3);
+    }
+
+    #[test]
+    fn test_human_output_plain_and_colored() {
+        let entry = parse_line("ERROR[11-08|10:49:09.123] Failed to import block number=7", 2025).unwrap();
+        let mut plain = Vec::new();
+        let mut colored = Vec::new();
+
+        EntryWriter::new(&mut plain, OutputFormat::Human, false).unwrap().write_entry(&entry).unwrap();
+        EntryWriter::human(&mut colored, true).write_entry(&entry).unwrap();
+
+        assert_eq!(
+            String::from_utf8(plain).unwrap(),
+            "2025-11-08 10:49:09.123 ERROR Failed to import block number=7\n"
+        );
+        let colored = String::from_utf8(colored).unwrap();
+        assert!(colored.contains("\x1b[31m"));
+        assert!(colored.contains("Failed to import block"));
+        assert_eq!(colored.matches("number=7").count(), 1);
+    }
+
+    #[test]
+    fn test_logfmt_output_round_trips() {
+        let line = r#"WARN [11-08|10:49:09.123] Dropping unsynced peer          peer=10.0.0.7 reason="read timeout" id=a\b"#;
+        let entry = parse_line(line, 2025).unwrap();
+        let mut out = Vec::new();
+
+        EntryWriter::new(&mut out, OutputFormat::Logfmt, false).unwrap().write_entry(&entry).unwrap();
+        let logfmt = String::from_utf8(out).unwrap();
+        let reparsed = parse_line(logfmt.trim_end(), 2025).unwrap();
+
+        let t = output_timestamp(&entry.timestamp).to_rfc3339_opts(SecondsFormat::Millis, true);
+        assert_eq!(
+            logfmt,
+            format!("t={} lvl=warn msg=\"Dropping unsynced peer\" id=\"a\\\\b\" peer=10.0.0.7 reason=\"read timeout\"\n", t)
+        );
+        assert_eq!(reparsed.level, entry.level);
+        assert_eq!(reparsed.timestamp, entry.timestamp);
+        assert_eq!(reparsed.details, entry.details);
+        assert_eq!(reparsed.message, "Dropping unsynced peer");
+    }
+
+    #[test]
+    fn test_human_output_indents_continuation_lines() {
+        let mut entry = parse_line("ERROR[11-08|10:49:09.123] Panic", 2025).unwrap();
+        entry.continuation = vec!["goroutine 1 [running]:".to_string(), "main.main()".to_string()];
+        let mut out = Vec::new();
+
+        EntryWriter::human(&mut out, false).write_entry(&entry).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "2025-11-08 10:49:09.123 ERROR Panic\n    goroutine 1 [running]:\n    main.main()\n"
+        );
+    }
+
+    #[test]
+    fn test_pivot_writes_only_numeric_points_of_the_key() {
+        let lines = [
+            "INFO [11-08|10:00:00] Transaction pool status report executable=10 pending=1,200 queued=3",
+            "INFO [11-08|10:00:05] Imported new chain segment number=42",
+            "INFO [11-08|10:00:08] Transaction pool status report pending=unknown",
+            "INFO [11-08|10:00:10] Transaction pool status report pending=1350 queued=0",
+        ];
+        let pivot = |format| {
+            let mut out = Vec::new();
+            let mut writer = EntryWriter::pivot(&mut out, "pending", format, true).unwrap();
+            for line in lines {
+                writer.write_entry(&parse_line(line, 2025).unwrap()).unwrap();
+            }
+            writer.finish().unwrap();
+            let points = writer.pivot_points();
+            drop(writer);
+            (points, String::from_utf8(out).unwrap())
+        };
+
+        set_time_format(TimeFormat::EpochS);
+        let (json_points, json) = pivot(OutputFormat::Json);
+        let (csv_points, csv) = pivot(OutputFormat::Csv);
+        set_time_format(TimeFormat::Rfc3339);
+
+        let first = parse_line(lines[0], 2025).unwrap().timestamp.timestamp();
+        assert_eq!((json_points, csv_points), (Some(2), Some(2)));
+        assert_eq!(json, format!("{{\"timestamp\":{},\"value\":1200.0}}\n{{\"timestamp\":{},\"value\":1350.0}}\n", first, first + 10));
+        assert_eq!(csv, format!("timestamp,value\n{},1200.0\n{},1350.0\n", first, first + 10));
+        assert!(EntryWriter::pivot(Vec::new(), "pending", OutputFormat::Human, true).is_err());
+    }
+
+    #[test]
+    fn test_split_by_day_writes_one_file_per_day() {
+        let dir = std::env::temp_dir().join(format!("glc-split-{}", std::process::id()));
+        let lines = [
+            "INFO [11-08|23:59:59] Late block",
+            "INFO [11-09|00:00:01] Early block",
+            "WARN [11-08|23:59:59] Out of order",
+        ];
+
+        let mut writer: EntryWriter<Vec<u8>> = EntryWriter::split(&dir, SplitBy::Day).unwrap();
+        for line in lines {
+            writer.write_entry(&parse_line(line, 2025).unwrap()).unwrap();
+        }
+        writer.finish().unwrap();
+        let files_written = writer.files_written();
+        drop(writer);
+
+        let first_day = fs::read_to_string(dir.join("2025-11-08.ndjson")).unwrap();
+        let second_day = fs::read_to_string(dir.join("2025-11-09.ndjson")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(files_written, Some(2));
+        assert_eq!(first_day.lines().count(), 2);
+        assert!(second_day.contains("Early block"));
+    }
+
+    #[test]
+    fn test_split_by_level_creates_files_only_for_seen_levels() {
+        let dir = std::env::temp_dir().join(format!("glc-split-level-{}", std::process::id()));
+        let lines = [
+            "ERROR [11-08|10:00:01] Database corrupted",
+            "WARN [11-08|10:00:02] Stale peer",
+            "ERROR [11-08|10:00:03] Sync failed",
+        ];
+        let mut levelless = parse_line("INFO [11-08|10:00:04] Re-read entry", 2025).unwrap();
+        levelless.level.clear();
+
+        let mut writer: EntryWriter<Vec<u8>> = EntryWriter::split(&dir, SplitBy::Level).unwrap();
+        for line in lines {
+            writer.write_entry(&parse_line(line, 2025).unwrap()).unwrap();
+        }
+        writer.write_entry(&levelless).unwrap();
+        writer.finish().unwrap();
+        let counts = writer.split_counts().unwrap();
+        drop(writer);
+
+        let mut created: Vec<String> =
+            fs::read_dir(&dir).unwrap().map(|file| file.unwrap().file_name().into_string().unwrap()).collect();
+        created.sort();
+        let errors = fs::read_to_string(dir.join("error.ndjson")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(created, ["error.ndjson", "unknown.ndjson", "warn.ndjson"]);
+        assert_eq!(
+            counts,
+            [("error.ndjson".to_string(), 2), ("unknown.ndjson".to_string(), 1), ("warn.ndjson".to_string(), 1)]
+        );
+        assert!(errors.contains("Database corrupted") && errors.contains("Sync failed"));
+    }
+}
@@ -0,0 +1,1663 @@
+//! Line-by-line processing: parsing chunks of input, filtering, and the output stages
+//! (`--tail`, `--dedup`, `--sort-by`, ...) that sit between the parser and the entry writer.
+
+// --- IMPORTS ---
+// Standard library imports
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// Third-party libraries
+use clap::ValueEnum;
+use chrono::{DateTime, Local};
+use indicatif::ProgressBar;
+use rayon::prelude::*;
+use regex::{Captures, Regex};
+
+// Local crate
+use geth_log_cruncher::{normalize_message, LineParser, LogEntry, LogLevel, TimestampVariant, YearRollover};
+use geth_log_cruncher::anonymize::Anonymizer;
+use geth_log_cruncher::output::EntryWriter;
+use geth_log_cruncher::script::ScriptExtractor;
+use geth_log_cruncher::stats::{ErrorRateMonitor, Histogram, LatencyStats};
+
+// --- DATA STRUCTURES ---
+/// Line counters collected while processing a log file.
+#[derive(Debug, Default, Clone)]
+pub struct ProcessStats {
+    pub total_lines: usize,         // Every line read from the input
+    pub valid_lines: usize,         // Lines that parsed into a `LogEntry`
+    pub filtered_by_level: usize,   // Valid lines dropped by the `--level` threshold
+    pub filtered_by_time: usize,    // Valid lines dropped by the `--from`/`--until` window
+    pub filtered_by_details: usize, // Valid lines dropped by a `--where` condition
+    pub filtered_by_grep: usize,    // Valid lines dropped by `--grep` (or `--grep-invert`)
+    pub suppressed_by_tail: usize,  // Emittable entries dropped because they fell outside `--tail`
+    pub lossy_lines: usize,         // Lines with invalid UTF-8 that were lossily converted
+    pub collapsed_duplicates: usize, // Entries folded into a previous one by `--dedup`
+    pub year_rollovers: usize,      // New Year boundaries detected by `--auto-year`
+    pub bytes_read: u64,            // Input bytes consumed, after decompression
+    pub joined_lines: usize,        // Non-log lines attached to the preceding entry by `--join-continuations`
+    pub oversized_lines: usize,     // Lines longer than `--max-line-bytes`, skipped without parsing
+    pub malformed_hashes: usize,    // Valid entries whose `hash=` value is not a well-formed hash
+    pub script_errors: usize,       // Valid entries the `--extract-script` failed on, kept without derived fields
+    pub truncated_details: usize,   // Valid entries with more `key=value` pairs than `--limit-details`
+    pub anonymized_values: usize,   // Distinct IPs and enode keys first replaced by `--anonymize` in this input
+    pub redactions: usize,          // Matches of `--redact` patterns replaced with `[REDACTED]`
+    pub sampled_out: usize,         // Entries that passed the filters but were dropped by `--sample` or `--sample-random`
+    pub duplicates_dropped: usize,  // Entries dropped by `--unique-by` because another entry kept their value (a stage count)
+    pub timestamp_variants: BTreeMap<TimestampVariant, usize>, // Valid lines per timestamp format, for `--single-format`
+}
+
+/// Post-parse filters deciding which valid entries get emitted.
+#[derive(Debug, Default)]
+pub struct EntryFilters {
+    pub min_level: Option<LogLevel>,      // Minimum level to keep
+    pub from: Option<DateTime<Local>>,    // Inclusive lower bound on `timestamp`
+    pub until: Option<DateTime<Local>>,   // Inclusive upper bound on `timestamp`
+    pub details: Vec<DetailFilter>,       // `--where` conditions, all of which must hold
+    pub grep: Option<Regex>,              // Pattern the message must match
+    pub grep_invert: bool,                // Keep messages that do *not* match `grep` instead
+}
+
+/// A `--where` condition on a single `details` pair.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DetailFilter {
+    pub key: String,            // Key that must be present in `details`
+    pub value: Option<String>,  // Exact value required; `None` (from `key=*`) accepts any value
+}
+
+/// Settings controlling how `LogProcessor` parses and filters input.
+#[derive(Debug, Default)]
+pub struct ProcessOptions {
+    pub parser: LineParser,      // Line parser, including the year used to complete timestamps
+    pub filters: EntryFilters,   // Post-parse filters applied to valid entries
+    pub parallel: bool,          // Parse each chunk across CPU cores with rayon
+    pub tail: Option<usize>,     // Emit only the last N entries that pass the filters
+    pub dedup: bool,             // Collapse consecutive entries with the same level and normalized message
+    pub auto_year: bool,         // Move entries into the next year when the month goes backwards
+    pub join_continuations: bool, // Attach non-log lines to the preceding entry's `continuation`
+    pub max_lines: Option<usize>, // Stop after reading this many lines across all inputs
+    pub follow: bool,            // Input is a live file: read line by line and flush after each
+    pub strict: bool,            // Fail on the first line that can't be parsed
+    pub sort_by: Option<SortBy>, // Buffer every emitted entry and sort by this key before writing
+    pub fields: Option<Vec<String>>, // `details` keys kept in emitted entries; `None` keeps them all
+    pub keep_raw: bool,          // Record each entry's trimmed source line in `raw`
+    pub enrich_time: bool,       // Add `weekday` and `iso_week` derived from each entry's timestamp
+    pub with_line_number: bool,  // Record each entry's source line number in `line`, and its input in `file`
+    pub debug_invalid: bool,     // Report why each dropped line failed to parse, up to `DEBUG_INVALID_LIMIT`
+    pub extract_script: Option<ScriptExtractor>, // Script deriving extra `details` fields from each valid entry
+    pub interrupted: Arc<AtomicBool>, // Set by Ctrl-C; reading stops before the next chunk
+    pub max_line_bytes: Option<usize>, // Longer lines are skipped, and never held in memory in full
+    pub anonymize: bool,         // Replace IPs and enode keys with salted placeholders
+    pub redact: Vec<Regex>,      // Patterns whose matches are replaced with `[REDACTED]`
+    pub chunk_lines: Option<usize>, // Lines per parsed batch, overriding `CHUNK_LINES`
+    pub sample: Option<Sampler>, // Downsampling of the entries that pass the filters (`--sample`, `--sample-random`)
+    pub unique_by: Option<(String, KeepEntry)>, // Keep one entry per distinct value of this key (`--unique-by`, `--keep`)
+}
+
+/// Keeps roughly one in `n` entries, either by count or at random.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sampler {
+    Every { n: u64, seen: u64 },   // Keep the first entry of every `n` (`--sample`)
+    Random { n: u64, state: u64 }, // Keep each entry with probability 1/n, from a seeded SplitMix64 (`--sample-random`)
+}
+
+/// Which entry `--unique-by` keeps for each distinct value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum KeepEntry {
+    #[default]
+    First,  // The first entry with each value; later ones are dropped as they arrive
+    Last,   // The last entry with each value; every entry is held until the end of the run
+}
+
+/// State of `--unique-by`: the values seen so far, and under `KeepEntry::Last` the held entries.
+#[derive(Debug)]
+pub struct UniqueBy {
+    key: String,                    // `details` key (or `event`) whose values must be unique
+    keep: KeepEntry,                // Which entry of each value survives
+    seen: HashMap<String, usize>,   // Every value seen, with the index in `held` of its entry under `KeepEntry::Last`
+    held: Vec<Option<LogEntry>>,    // Entries in input order under `KeepEntry::Last`; `None` once superseded
+    dropped: usize,                 // Entries dropped because another entry keeps their value
+}
+
+/// Keys `--sort-by` can order the output by. Sorting is stable, so ties keep input order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SortBy {
+    Timestamp,  // Earliest first
+    Level,      // Most severe first; unrecognized levels last
+}
+
+/// What a single `read_chunk` call consumed from the input.
+#[derive(Debug, Default)]
+pub struct ChunkRead {
+    lines: usize,        // Lines read into the chunk; zero means the input is exhausted
+    bytes: usize,        // Raw bytes consumed, including line endings
+    lossy_lines: usize,  // Lines that contained invalid UTF-8
+    oversized: Vec<usize>, // Chunk offsets of lines over the byte limit, left empty in the chunk
+}
+
+/// Processing state carried across every input of a run, so features like `--tail` span all files.
+pub struct LogProcessor<'a, W: Write> {
+    options: &'a ProcessOptions,         // Parsing and filtering settings
+    writer: &'a mut EntryWriter<W>,      // Sink for emitted entries
+    errors: Option<&'a mut dyn Write>,   // Optional sink for unparseable lines
+    pb: &'a ProgressBar,                 // Progress bar advanced by bytes read
+    tail_buffer: VecDeque<LogEntry>,     // Ring buffer holding the last `--tail` entries
+    dedup_pending: Option<(LogEntry, String)>, // Entry being deduplicated, with its normalized message
+    year_rollover: YearRollover,         // Month tracking for `--auto-year`, carried across inputs
+    lines_remaining: Option<usize>,      // Lines left to read under `--max-lines`
+    sort_buffer: Vec<LogEntry>,          // Every emitted entry, held for `--sort-by`
+    pub file_year: Option<i32>,              // Year for the current input, overriding the parser's (`--year-from-filename`)
+    pub histogram: Option<Histogram>,        // Entries per time bucket, for `--histogram`
+    pub error_rate: Option<ErrorRateMonitor>, // Sliding count of ERROR entries passing the filters, for `--error-rate-*`
+    pub latency: Option<LatencyStats>,       // `elapsed_ms` of entries passing the filters, for `--latency-stats`
+    pub invalid_reported: usize,             // Dropped lines explained so far under `--debug-invalid`
+    script_errors_seen: usize,           // `--extract-script` failures so far, across inputs
+    anonymizer: Option<Anonymizer>,      // Placeholder salt and values seen, for `--anonymize`
+    stage_stats: ProcessStats,           // Counters from the output stages, which span all inputs
+    progress_pending: u64,               // Bytes read but not yet added to the progress bar
+    progress_updated: Instant,           // When the progress bar was last advanced
+    sampler: Option<Sampler>,            // `options.sample`, advanced across inputs
+    unique: Option<UniqueBy>,            // Values seen for `--unique-by`, across inputs
+    pub read_error: Option<io::Error>,       // Why the last `process_log_file` stopped before the end of its input
+}
+
+// --- GLOBAL VARIABLES ---
+/// Replacement for each `--redact` match.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Number of lines read and parsed together as one batch.
+pub const CHUNK_LINES: usize = 4096;
+
+/// Shortest time between progress bar updates.
+pub const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Most dropped lines `--debug-invalid` explains; later ones are only counted.
+pub const DEBUG_INVALID_LIMIT: usize = 20;
+
+// --- IMPLEMENTATIONS ---
+impl UniqueBy {
+    /// Tracks unique values of `key`, keeping the first or last entry of each.
+    pub fn new(key: String, keep: KeepEntry) -> Self {
+        UniqueBy { key, keep, seen: HashMap::new(), held: Vec::new(), dropped: 0 }
+    }
+
+    /// Returns `entry` if it should be passed on now: it's the first with its value, or lacks the
+    /// key. Under `KeepEntry::Last` every entry is held instead, and a repeated value supersedes
+    /// the entry held for it.
+    pub fn admit(&mut self, entry: LogEntry) -> Option<LogEntry> {
+        let value = detail_or_event(&entry, &self.key).cloned();
+        match self.keep {
+            KeepEntry::First => {
+                if let Some(value) = value
+                    && self.seen.insert(value, 0).is_some()
+                {
+                    self.dropped += 1;
+                    return None;
+                }
+                Some(entry)
+            }
+            KeepEntry::Last => {
+                let index = self.held.len();
+                self.held.push(Some(entry));
+                if let Some(previous) = value.and_then(|value| self.seen.insert(value, index)) {
+                    self.held[previous] = None;
+                    self.dropped += 1;
+                }
+                None
+            }
+        }
+    }
+
+    /// Returns the entries held under `KeepEntry::Last`, in input order.
+    pub fn drain(&mut self) -> impl Iterator<Item = LogEntry> + use<> {
+        std::mem::take(&mut self.held).into_iter().flatten()
+    }
+}
+
+impl Sampler {
+    /// Keeps entries 1, `n` + 1, 2`n` + 1, ...
+    pub fn every(n: usize) -> Self {
+        Sampler::Every { n: n as u64, seen: 0 }
+    }
+
+    /// Keeps each entry with probability 1/`n`; the same `seed` keeps the same entries.
+    pub fn random(n: usize, seed: u64) -> Self {
+        Sampler::Random { n: n as u64, state: seed }
+    }
+
+    /// Decides whether the next entry is kept.
+    pub fn keep(&mut self) -> bool {
+        match self {
+            Sampler::Every { n, seen } => {
+                *seen += 1;
+                (*seen - 1) % *n == 0
+            }
+            Sampler::Random { n, state } => {
+                // SplitMix64: https://prng.di.unimi.it/splitmix64.c
+                *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+                let mut z = *state;
+                z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+                z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+                (z ^ (z >> 31)) % *n == 0
+            }
+        }
+    }
+}
+
+impl ProcessOptions {
+    /// True once Ctrl-C has stopped a run early. Under `--follow`, Ctrl-C is the normal way to end.
+    pub fn was_interrupted(&self) -> bool {
+        !self.follow && self.interrupted.load(Ordering::Relaxed)
+    }
+}
+
+impl ProcessStats {
+    /// Adds another set of counters (e.g. from one input file) into this one.
+    pub fn merge(&mut self, other: &ProcessStats) {
+        self.total_lines += other.total_lines;
+        self.valid_lines += other.valid_lines;
+        self.filtered_by_level += other.filtered_by_level;
+        self.filtered_by_time += other.filtered_by_time;
+        self.filtered_by_details += other.filtered_by_details;
+        self.filtered_by_grep += other.filtered_by_grep;
+        self.suppressed_by_tail += other.suppressed_by_tail;
+        self.lossy_lines += other.lossy_lines;
+        self.collapsed_duplicates += other.collapsed_duplicates;
+        self.year_rollovers += other.year_rollovers;
+        self.bytes_read += other.bytes_read;
+        self.joined_lines += other.joined_lines;
+        self.oversized_lines += other.oversized_lines;
+        self.malformed_hashes += other.malformed_hashes;
+        self.script_errors += other.script_errors;
+        self.truncated_details += other.truncated_details;
+        self.anonymized_values += other.anonymized_values;
+        self.redactions += other.redactions;
+        self.sampled_out += other.sampled_out;
+        self.duplicates_dropped += other.duplicates_dropped;
+        for (&variant, &count) in &other.timestamp_variants {
+            *self.timestamp_variants.entry(variant).or_default() += count;
+        }
+    }
+
+    /// Whether the summary should show the timestamp format distribution: always with
+    /// `--single-format`, otherwise only when it's mixed.
+    pub fn report_timestamp_variants(&self, single_format: bool) -> bool {
+        single_format || self.timestamp_variants.len() > 1
+    }
+
+    /// The timestamp format distribution as `bracket-millis: 120, rfc3339: 3`.
+    pub fn describe_timestamp_variants(&self) -> String {
+        self.timestamp_variants
+            .iter()
+            .map(|(variant, count)| format!("{}: {}", variant.as_str(), count))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Lines that could not be parsed into a `LogEntry` or joined onto one, excluding oversized lines.
+    pub fn invalid_lines(&self) -> usize {
+        self.total_lines - self.valid_lines - self.joined_lines - self.oversized_lines
+    }
+
+    /// Invalid lines as a percentage of all lines, or 0 when nothing was read.
+    pub fn invalid_percentage(&self) -> f64 {
+        if self.total_lines > 0 {
+            (self.invalid_lines() as f64 / self.total_lines as f64) * 100.0
+        } else {
+            0.0
+        }
+    }
+}
+
+impl<'a, W: Write> LogProcessor<'a, W> {
+    /// Creates a processor emitting to `writer`, and unparseable lines to `errors` if given.
+    pub fn new(
+        options: &'a ProcessOptions,
+        writer: &'a mut EntryWriter<W>,
+        errors: Option<&'a mut dyn Write>,
+        pb: &'a ProgressBar,
+    ) -> Self {
+        let tail_buffer = VecDeque::with_capacity(options.tail.map_or(0, |tail| tail + 1));
+        LogProcessor {
+            options,
+            writer,
+            errors,
+            pb,
+            tail_buffer,
+            dedup_pending: None,
+            year_rollover: YearRollover::default(),
+            lines_remaining: options.max_lines,
+            sort_buffer: Vec::new(),
+            file_year: None,
+            histogram: None,
+            error_rate: None,
+            latency: None,
+            invalid_reported: 0,
+            script_errors_seen: 0,
+            anonymizer: options.anonymize.then(Anonymizer::new),
+            stage_stats: ProcessStats::default(),
+            progress_pending: 0,
+            progress_updated: Instant::now(),
+            sampler: options.sample,
+            unique: options.unique_by.clone().map(|(key, keep)| UniqueBy::new(key, keep)),
+            read_error: None,
+        }
+    }
+
+    /// The core file processing engine. Reads input in chunks of lines, parses, and emits entries.
+    ///
+    /// Each chunk is parsed in parallel when `options.parallel` is set; results are always
+    /// emitted in input order. Entries rejected by the filters are counted as valid but not emitted.
+    /// Unparseable lines are written to the errors sink as `<line number>: <original text>`,
+    /// prefixed with `source` when given to tell files apart.
+    pub fn process_log_file<R: BufRead>(
+        &mut self,
+        mut reader: R,
+        source: Option<&str>,
+    ) -> Result<ProcessStats, Box<dyn std::error::Error>> {
+        let options = self.options;
+        let mut stats = ProcessStats::default();
+        let anonymized_before = self.anonymizer.as_ref().map_or(0, Anonymizer::distinct_values);
+        let file_parser = self
+            .file_year
+            .filter(|&year| year != options.parser.year)
+            .map(|year| LineParser { year, ..options.parser.clone() });
+        let parser = file_parser.as_ref().unwrap_or(&options.parser);
+
+        // Line buffers are reused across chunks to avoid reallocating per line.
+        let batch_lines = options.chunk_lines.unwrap_or(CHUNK_LINES);
+        let mut chunk: Vec<String> = Vec::with_capacity(batch_lines);
+        let mut raw_line: Vec<u8> = Vec::new();
+        let mut joining: Option<LogEntry> = None;
+        loop {
+            if options.was_interrupted() {
+                break;
+            }
+            // A followed file is read a line at a time so entries are emitted as they arrive.
+            let chunk_lines = if options.follow { 1 } else { batch_lines };
+            let max_lines = self.lines_remaining.map_or(chunk_lines, |remaining| remaining.min(chunk_lines));
+            let chunk_read = match read_chunk(&mut reader, &mut chunk, &mut raw_line, max_lines, options.max_line_bytes) {
+                Ok(chunk_read) => chunk_read,
+                Err(e) => {
+                    self.read_error = Some(e);
+                    break;
+                }
+            };
+            if chunk_read.lines == 0 {
+                break; 
+            }
+            if let Some(remaining) = self.lines_remaining.as_mut() {
+                *remaining -= chunk_read.lines;
+            }
+            let lines = &chunk[..chunk_read.lines];
+            let first_line_number = stats.total_lines + 1;
+            stats.total_lines += chunk_read.lines;
+            stats.lossy_lines += chunk_read.lossy_lines;
+            stats.bytes_read += chunk_read.bytes as u64;
+
+            // Parse the chunk; collecting keeps results in input order.
+            let parsed: Vec<Option<LogEntry>> = if options.parallel {
+                lines.par_iter().map(|line| parser.parse(line)).collect()
+            } else {
+                lines.iter().map(|line| parser.parse(line)).collect()
+            };
+
+            // Filter and output valid entries. With `--join-continuations`, each entry is held back
+            // until the next log line or a blank line closes its group.
+            for (offset, parsed_entry) in parsed.into_iter().enumerate() {
+                let Some(mut log_entry) = parsed_entry else {
+                    let line = lines[offset].trim_end_matches(['\r', '\n']);
+                    if let Some(entry) = joining.as_mut()
+                        && !line.trim().is_empty()
+                    {
+                        let mut line = match self.anonymizer.as_mut() {
+                            Some(anonymizer) => anonymizer.scrub(line),
+                            None => line.to_string(),
+                        };
+                        stats.redactions += redact_text(&mut line, &options.redact);
+                        entry.continuation.push(line);
+                        stats.joined_lines += 1;
+                        continue;
+                    }
+                    if let Some(entry) = joining.take() {
+                        self.filter_and_emit(entry, &mut stats)?;
+                    }
+                    if chunk_read.oversized.contains(&offset) {
+                        stats.oversized_lines += 1;
+                        continue;
+                    }
+
+                    let location = || line_location(source, first_line_number + offset);
+                    if options.debug_invalid && self.invalid_reported < DEBUG_INVALID_LIMIT {
+                        self.invalid_reported += 1;
+                        let reason = parser.diagnose(line).map_or_else(|| "unknown".to_string(), |failure| failure.to_string());
+                        self.pb.suspend(|| eprintln!("Invalid line at {}: {}: {:?}", location(), reason, line));
+                    }
+                    if options.strict {
+                        return Err(format!(
+                            "Error: Unparseable log line at {} (--strict): {:?}. \
+                             Check the input format, try --pattern or --input-format, or drop --strict to skip such lines",
+                            location(),
+                            line
+                        )
+                        .into());
+                    }
+
+                    if let Some(errors) = self.errors.as_mut() {
+                        match source {
+                            Some(source) => writeln!(errors, "{}:{}: {}", source, first_line_number + offset, line)?,
+                            None => writeln!(errors, "{}: {}", first_line_number + offset, line)?,
+                        }
+                    }
+                    continue;
+                };
+                stats.valid_lines += 1;
+                if let Some(variant) = log_entry.timestamp_variant {
+                    *stats.timestamp_variants.entry(variant).or_default() += 1;
+                }
+                if log_entry.details_truncated {
+                    stats.truncated_details += 1;
+                }
+                if options.keep_raw {
+                    log_entry.raw = lines[offset].trim().to_string();
+                }
+                if options.with_line_number {
+                    log_entry.line = Some(first_line_number + offset);
+                    log_entry.file = source.map(str::to_string);
+                }
+                if let Some(anonymizer) = self.anonymizer.as_mut() {
+                    anonymizer.anonymize_entry(&mut log_entry);
+                }
+                // A failing script only costs this entry its derived fields; the first failure is shown.
+                if let Some(extractor) = &options.extract_script
+                    && let Err(e) = extractor.extract(&mut log_entry)
+                {
+                    if self.script_errors_seen == 0 {
+                        let location = line_location(source, first_line_number + offset);
+                        self.pb.suspend(|| eprintln!("Warning: --extract-script failed at {}: {}", location, e));
+                    }
+                    self.script_errors_seen += 1;
+                    stats.script_errors += 1;
+                }
+                // Redacting after the script also covers any secrets it copied into derived fields.
+                stats.redactions += redact_entry(&mut log_entry, &options.redact);
+                if log_entry.has_malformed_hash() {
+                    stats.malformed_hashes += 1;
+                }
+
+                if options.auto_year && self.year_rollover.adjust(&mut log_entry) {
+                    stats.year_rollovers += 1;
+                }
+                if options.enrich_time {
+                    log_entry.enrich_time();
+                }
+                if let Some(histogram) = self.histogram.as_mut() {
+                    histogram.record(log_entry.timestamp);
+                }
+
+                if options.join_continuations {
+                    if let Some(entry) = joining.replace(log_entry) {
+                        self.filter_and_emit(entry, &mut stats)?;
+                    }
+                } else {
+                    self.filter_and_emit(log_entry, &mut stats)?;
+                }
+            }
+
+            self.advance_progress(chunk_read.bytes as u64, stats.total_lines, false);
+            if options.follow {
+                self.writer.flush()?;
+                if let Some(errors) = self.errors.as_mut() {
+                    errors.flush()?;
+                }
+            }
+        }
+
+        self.advance_progress(0, stats.total_lines, true);
+
+        // Continuation groups never span inputs.
+        if let Some(entry) = joining.take() {
+            self.filter_and_emit(entry, &mut stats)?;
+        }
+        if let Some(anonymizer) = &self.anonymizer {
+            stats.anonymized_values = anonymizer.distinct_values() - anonymized_before;
+        }
+
+        Ok(stats)
+    }
+
+    /// Advances the progress bar by `bytes`, redrawing at most every `PROGRESS_INTERVAL` so small
+    /// batches don't spend their time formatting messages. `force` applies any pending bytes, so
+    /// the final position is exact.
+    fn advance_progress(&mut self, bytes: u64, line: usize, force: bool) {
+        self.progress_pending += bytes;
+        if !force && self.progress_updated.elapsed() < PROGRESS_INTERVAL {
+            return;
+        }
+        self.pb.inc(self.progress_pending);
+        self.pb.set_message(format!("Processing line {}", line));
+        self.progress_pending = 0;
+        self.progress_updated = Instant::now();
+    }
+
+    /// Returns true once `--max-lines` lines have been read; later inputs are skipped.
+    pub fn reached_max_lines(&self) -> bool {
+        self.lines_remaining == Some(0)
+    }
+
+    /// Applies the post-parse filters to a valid entry, emitting it if it passes all of them.
+    ///
+    /// `--fields` trims `details` only afterwards, so `--where` still sees every key.
+    fn filter_and_emit(&mut self, mut log_entry: LogEntry, stats: &mut ProcessStats) -> Result<(), Box<dyn std::error::Error>> {
+        let filters = &self.options.filters;
+        if !meets_min_level(&log_entry, filters.min_level) {
+            stats.filtered_by_level += 1;
+            return Ok(());
+        }
+
+        if !within_time_range(&log_entry, filters.from, filters.until) {
+            stats.filtered_by_time += 1;
+            return Ok(());
+        }
+
+        if !matches_details(&log_entry, &filters.details) {
+            stats.filtered_by_details += 1;
+            return Ok(());
+        }
+
+        if !matches_grep(&log_entry, filters.grep.as_ref(), filters.grep_invert) {
+            stats.filtered_by_grep += 1;
+            return Ok(());
+        }
+
+        if let Some(error_rate) = self.error_rate.as_mut() {
+            error_rate.record(&log_entry);
+        }
+        if let Some(latency) = self.latency.as_mut() {
+            latency.record(&log_entry);
+        }
+        if let Some(unique) = self.unique.as_mut() {
+            match unique.admit(log_entry) {
+                Some(unique_entry) => log_entry = unique_entry,
+                None => return Ok(()),
+            }
+        }
+        self.sample_and_emit(log_entry, stats)
+    }
+
+    /// Passes an entry that is unique under `--unique-by` through `--sample` and `--fields`, then
+    /// on to the output stages.
+    fn sample_and_emit(&mut self, mut log_entry: LogEntry, stats: &mut ProcessStats) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(sampler) = self.sampler.as_mut()
+            && !sampler.keep()
+        {
+            stats.sampled_out += 1;
+            return Ok(());
+        }
+        if let Some(fields) = &self.options.fields {
+            log_entry.details.retain(|key, _| fields.contains(key));
+        }
+
+        self.emit(log_entry)
+    }
+
+    /// Sends an entry that passed the filters through the output stages: `--sort-by`, `--dedup`,
+    /// then `--tail`. Sorting holds every entry back until `finish`.
+    fn emit(&mut self, log_entry: LogEntry) -> Result<(), Box<dyn std::error::Error>> {
+        if self.options.sort_by.is_some() {
+            self.sort_buffer.push(log_entry);
+            return Ok(());
+        }
+        self.emit_sorted(log_entry)
+    }
+
+    /// Collapses an entry that made it past `--sort-by` into the pending one under `--dedup`.
+    fn emit_sorted(&mut self, mut log_entry: LogEntry) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.options.dedup {
+            return self.emit_deduplicated(log_entry);
+        }
+
+        let normalized = normalize_message(&log_entry.message);
+        if let Some((pending, pending_normalized)) = &mut self.dedup_pending
+            && pending.level == log_entry.level
+            && *pending_normalized == normalized
+        {
+            pending.repeat_count = Some(pending.repeat_count.unwrap_or(1) + 1);
+            self.stage_stats.collapsed_duplicates += 1;
+            return Ok(());
+        }
+
+        log_entry.repeat_count = Some(1);
+        match self.dedup_pending.replace((log_entry, normalized)) {
+            Some((previous, _)) => self.emit_deduplicated(previous),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes an entry that made it past `--dedup`, or holds it in the `--tail` buffer.
+    fn emit_deduplicated(&mut self, log_entry: LogEntry) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(tail) = self.options.tail {
+            self.tail_buffer.push_back(log_entry);
+            if self.tail_buffer.len() > tail {
+                self.tail_buffer.pop_front();
+                self.stage_stats.suppressed_by_tail += 1;
+            }
+            return Ok(());
+        }
+
+        self.writer.write_entry(&log_entry)
+    }
+
+    /// Emits anything held back until every input has been seen (the `--sort-by` buffer,
+    /// the pending `--dedup` entry and the `--tail` buffer).
+    ///
+    /// Returns the output-stage counters, to be merged into the run totals.
+    pub fn finish(mut self) -> Result<ProcessStats, Box<dyn std::error::Error>> {
+        if let Some(mut unique) = self.unique.take() {
+            let mut stats = ProcessStats::default();
+            for log_entry in unique.drain() {
+                self.sample_and_emit(log_entry, &mut stats)?;
+            }
+            stats.duplicates_dropped = unique.dropped;
+            self.stage_stats.merge(&stats);
+        }
+        let mut sort_buffer = std::mem::take(&mut self.sort_buffer);
+        match self.options.sort_by {
+            Some(SortBy::Timestamp) => sort_buffer.sort_by_key(|entry| entry.timestamp),
+            Some(SortBy::Level) => sort_buffer.sort_by_key(|entry| std::cmp::Reverse(LogLevel::from_level_str(&entry.level))),
+            None => {}
+        }
+        for log_entry in sort_buffer {
+            self.emit_sorted(log_entry)?;
+        }
+        if let Some((pending, _)) = self.dedup_pending.take() {
+            self.emit_deduplicated(pending)?;
+        }
+        for log_entry in self.tail_buffer.drain(..) {
+            self.writer.write_entry(&log_entry)?;
+        }
+        Ok(self.stage_stats)
+    }
+}
+
+// --- HELPER FUNCTIONS ---
+/// Describes where a line is for messages: `<source>:<n>` when inputs are told apart, else `line <n>`.
+pub fn line_location(source: Option<&str>, line_number: usize) -> String {
+    match source {
+        Some(source) => format!("{}:{}", source, line_number),
+        None => format!("line {}", line_number),
+    }
+}
+
+/// Reads up to `max_lines` lines into `chunk`, reusing its string buffers.
+///
+/// Lines are read as raw bytes so invalid UTF-8 doesn't abort the run; such lines are
+/// converted lossily (bad bytes become U+FFFD) and counted. Each is stored without its line
+/// ending, whether `\n`, `\r\n` or a stray `\r` before the end of input, so CRLF and mixed files
+/// parse like Unix ones. `raw_line` is scratch space.
+/// Lines over `max_line_bytes` are left empty and listed in `ChunkRead.oversized`.
+pub fn read_chunk<R: BufRead>(
+    reader: &mut R,
+    chunk: &mut Vec<String>,
+    raw_line: &mut Vec<u8>,
+    max_lines: usize,
+    max_line_bytes: Option<usize>,
+) -> io::Result<ChunkRead> {
+    let mut chunk_read = ChunkRead::default();
+    while chunk_read.lines < max_lines {
+        raw_line.clear();
+        let (bytes_read_this_line, oversized) = match max_line_bytes {
+            Some(limit) => read_line_limited(reader, raw_line, limit)?,
+            None => (reader.read_until(b'\n', raw_line)?, false),
+        };
+        if bytes_read_this_line == 0 {
+            break;
+        }
+
+        if chunk_read.lines == chunk.len() {
+            chunk.push(String::new());
+        }
+        let line_buffer = &mut chunk[chunk_read.lines];
+        line_buffer.clear();
+        if oversized {
+            chunk_read.oversized.push(chunk_read.lines);
+        } else {
+            while raw_line.last().is_some_and(|&byte| byte == b'\n' || byte == b'\r') {
+                raw_line.pop();
+            }
+            match std::str::from_utf8(raw_line) {
+                Ok(line) => line_buffer.push_str(line),
+                Err(_) => {
+                    line_buffer.push_str(&String::from_utf8_lossy(raw_line));
+                    chunk_read.lossy_lines += 1;
+                }
+            }
+        }
+
+        chunk_read.bytes += bytes_read_this_line;
+        chunk_read.lines += 1;
+    }
+    Ok(chunk_read)
+}
+
+/// Reads one line like `read_until(b'\n')`, but stops copying into `buf` once the line is
+/// longer than `limit` bytes (excluding its ending), consuming the rest without keeping it.
+///
+/// Returns the bytes consumed and whether the line was over the limit.
+pub fn read_line_limited<R: BufRead>(reader: &mut R, buf: &mut Vec<u8>, limit: usize) -> io::Result<(usize, bool)> {
+    let mut consumed = 0;
+    let mut line_len = 0;
+    loop {
+        let available = match reader.fill_buf() {
+            Ok(available) => available,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if available.is_empty() {
+            break;
+        }
+        let newline = available.iter().position(|&byte| byte == b'\n');
+        let used = newline.map_or(available.len(), |index| index + 1);
+        line_len += newline.unwrap_or(available.len());
+        if line_len <= limit {
+            buf.extend_from_slice(&available[..used]);
+        }
+        reader.consume(used);
+        consumed += used;
+        if newline.is_some() {
+            break;
+        }
+    }
+    Ok((consumed, line_len > limit))
+}
+
+/// Scans an input for the latest timestamp among its valid entries, without emitting anything.
+///
+/// Timestamps are moved across New Year through `year_rollover` when given (`--auto-year`).
+pub fn latest_timestamp<R: BufRead>(
+    mut reader: R,
+    parser: &LineParser,
+    mut year_rollover: Option<&mut YearRollover>,
+    max_line_bytes: Option<usize>,
+    chunk_lines: usize,
+) -> io::Result<Option<DateTime<Local>>> {
+    let mut latest = None;
+    let mut chunk: Vec<String> = Vec::with_capacity(chunk_lines);
+    let mut raw_line: Vec<u8> = Vec::new();
+    loop {
+        let chunk_read = read_chunk(&mut reader, &mut chunk, &mut raw_line, chunk_lines, max_line_bytes)?;
+        if chunk_read.lines == 0 {
+            return Ok(latest);
+        }
+        let entries: Vec<LogEntry> = chunk[..chunk_read.lines].par_iter().filter_map(|line| parser.parse(line)).collect();
+        for mut entry in entries {
+            if let Some(year_rollover) = year_rollover.as_deref_mut() {
+                year_rollover.adjust(&mut entry);
+            }
+            latest = latest.max(Some(entry.timestamp));
+        }
+    }
+}
+
+/// Returns true if the entry's level is at or above `min_level` (or no threshold is set).
+pub fn meets_min_level(entry: &LogEntry, min_level: Option<LogLevel>) -> bool {
+    match min_level {
+        Some(min) => LogLevel::from_level_str(&entry.level).is_some_and(|level| level >= min),
+        None => true,
+    }
+}
+
+/// Returns true if the entry's timestamp falls within `[from, until]`; either bound may be open.
+pub fn within_time_range(entry: &LogEntry, from: Option<DateTime<Local>>, until: Option<DateTime<Local>>) -> bool {
+    from.is_none_or(|from| entry.timestamp >= from) && until.is_none_or(|until| entry.timestamp <= until)
+}
+
+/// Returns true if the entry's `details` satisfy every `--where` condition. `event` also matches
+/// the entry's `event` classification, unless `details` has a pair of that name.
+pub fn matches_details(entry: &LogEntry, conditions: &[DetailFilter]) -> bool {
+    conditions.iter().all(|condition| match (detail_or_event(entry, &condition.key), &condition.value) {
+        (Some(actual), Some(expected)) => actual == expected,
+        (Some(_), None) => true,
+        (None, _) => false,
+    })
+}
+
+/// Looks `key` up in `details`, falling back to the entry's `event` classification for `event`.
+pub fn detail_or_event<'a>(entry: &'a LogEntry, key: &str) -> Option<&'a String> {
+    match entry.details.get(key) {
+        None if key == "event" => entry.event.as_ref(),
+        value => value,
+    }
+}
+
+/// Returns true if the entry's message matches `grep`, or doesn't when `invert` is set.
+///
+/// With no pattern, every entry passes.
+pub fn matches_grep(entry: &LogEntry, grep: Option<&Regex>, invert: bool) -> bool {
+    grep.is_none_or(|grep| grep.is_match(&entry.message) != invert)
+}
+
+/// Redacts `message`, `details` values, `peer_id`, `enode`, `raw` and `continuation`, returning
+/// how many matches were replaced.
+pub fn redact_entry(entry: &mut LogEntry, patterns: &[Regex]) -> usize {
+    if patterns.is_empty() {
+        return 0;
+    }
+    let mut redactions = redact_text(&mut entry.message, patterns);
+    for value in entry.details.values_mut() {
+        redactions += redact_text(value, patterns);
+    }
+    for value in entry.peer_id.iter_mut().chain(entry.enode.iter_mut()) {
+        redactions += redact_text(value, patterns);
+    }
+    redactions += redact_text(&mut entry.raw, patterns);
+    for line in entry.continuation.iter_mut() {
+        redactions += redact_text(line, patterns);
+    }
+    redactions
+}
+
+/// Replaces every match of each pattern in `text` with [`REDACTED`], returning how many were replaced.
+pub fn redact_text(text: &mut String, patterns: &[Regex]) -> usize {
+    let mut redactions = 0;
+    for pattern in patterns {
+        let redacted = pattern.replace_all(text, |_: &Captures| {
+            redactions += 1;
+            REDACTED
+        });
+        if let Cow::Owned(redacted) = redacted {
+            *text = redacted;
+        }
+    }
+    redactions
+}
+
+// --- UNIT TESTS ---
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::path::Path;
+    use std::process;
+    use chrono::Datelike;
+    use geth_log_cruncher::output::OutputFormat;
+    use geth_log_cruncher::parse_line;
+    use crate::input::open_log_file;
+    use crate::{compile_grep, compile_redact, parse_detail_filter, parse_time_bound};
+
+    /// Builds options for tests with the given filters and the 2025 year.
+    fn test_options(filters: EntryFilters) -> ProcessOptions {
+        ProcessOptions { parser: LineParser::new(2025), filters, parallel: true, ..ProcessOptions::default() }
+    }
+
+    /// Runs `process_log_file` over an in-memory input, returning the stats and emitted output.
+    fn process_str(input: &str, options: &ProcessOptions, format: OutputFormat, pretty: bool) -> (ProcessStats, String) {
+        let pb = ProgressBar::hidden();
+        let mut out = Vec::new();
+        let mut writer = EntryWriter::new(&mut out, format, pretty).unwrap();
+
+        let mut processor = LogProcessor::new(options, &mut writer, None, &pb);
+        let mut stats = processor.process_log_file(input.as_bytes(), None).unwrap();
+        stats.merge(&processor.finish().unwrap());
+        writer.finish().unwrap();
+        drop(writer);
+
+        (stats, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_process_log_file_from_reader() {
+        let input = "INFO [11-08|10:49:09] Started\nnot a log line\nWARN [11-08|10:49:10] Stale peer\n";
+        let (stats, _) = process_str(input, &test_options(EntryFilters::default()), OutputFormat::Json, false);
+
+        assert_eq!(stats.total_lines, 3);
+        assert_eq!(stats.valid_lines, 2);
+    }
+
+    #[test]
+    fn test_throttled_progress_ends_at_exact_position() {
+        let input = "INFO [11-08|10:49:09] Started\n".repeat(50);
+        let options = ProcessOptions { chunk_lines: Some(1), ..test_options(EntryFilters::default()) };
+        let pb = ProgressBar::hidden();
+        let mut out = Vec::new();
+        let mut writer = EntryWriter::new(&mut out, OutputFormat::Json, false).unwrap();
+
+        let mut processor = LogProcessor::new(&options, &mut writer, None, &pb);
+        processor.process_log_file(input.as_bytes(), None).unwrap();
+
+        assert_eq!(pb.position(), input.len() as u64);
+        assert_eq!(pb.message(), "Processing line 50");
+    }
+
+    #[test]
+    fn test_meets_min_level_ordering() {
+        let warn = parse_line("WARN [11-08|10:49:09] Stale peer", 2025).unwrap();
+        let debug = parse_line("DEBUG[11-08|10:49:09] Recalculated QoS", 2025).unwrap();
+
+        assert!(meets_min_level(&warn, Some(LogLevel::Warn)));
+        assert!(meets_min_level(&warn, Some(LogLevel::Info)));
+        assert!(!meets_min_level(&warn, Some(LogLevel::Error)));
+        assert!(!meets_min_level(&debug, Some(LogLevel::Info)));
+        assert!(meets_min_level(&debug, None));
+    }
+
+    #[test]
+    fn test_time_range_filter() {
+        let input = "INFO [11-08|10:00:00] Before\n\
+                     INFO [11-08|10:30:00] Inside\n\
+                     INFO [11-08|11:00:00] Edge\n\
+                     INFO [11-08|11:30:00] After\n";
+        let filters = EntryFilters {
+            from: Some(parse_time_bound("11-08|10:15:00", 2025, None, "--from").unwrap()),
+            until: Some(parse_time_bound("11-08|11:00:00", 2025, None, "--until").unwrap()),
+            ..EntryFilters::default()
+        };
+        let (stats, output) = process_str(input, &test_options(filters), OutputFormat::Json, false);
+
+        assert_eq!(stats.valid_lines, 4);
+        assert_eq!(stats.filtered_by_time, 2);
+        assert!(output.contains("Inside") && output.contains("Edge"));
+    }
+
+    #[test]
+    fn test_where_filters_and_together() {
+        let input = "INFO [11-08|10:00:01] Imported block hash=0xabc number=1\n\
+                     INFO [11-08|10:00:02] Imported block hash=0xdef number=2\n\
+                     INFO [11-08|10:00:03] Imported block hash=0xabc\n\
+                     INFO [11-08|10:00:04] Synced\n";
+        let filters = EntryFilters {
+            details: vec![parse_detail_filter("hash=0xabc").unwrap(), parse_detail_filter("number=*").unwrap()],
+            ..EntryFilters::default()
+        };
+
+        let (stats, output) = process_str(input, &test_options(filters), OutputFormat::Json, false);
+
+        assert_eq!(output.lines().count(), 1);
+        assert!(output.contains("\"number\":\"1\""));
+        assert_eq!(stats.filtered_by_details, 3);
+        assert_eq!(stats.invalid_lines(), 0);
+    }
+
+    #[test]
+    fn test_where_event_matches_classified_entries() {
+        let input = "INFO [11-08|10:00:01] Imported new chain segment number=1\n\
+                     WARN [11-08|10:00:02] Chain reorg detected number=1 drop=1 add=2\n";
+        let filters = EntryFilters { details: vec![parse_detail_filter("event=reorg").unwrap()], ..EntryFilters::default() };
+
+        let (_, output) = process_str(input, &test_options(filters), OutputFormat::Json, false);
+
+        let entries: Vec<LogEntry> = output.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].reorg_depth, Some(1));
+    }
+
+    #[test]
+    fn test_limit_details_counts_truncated_entries() {
+        let input = "INFO [11-08|10:00:01] Peer a=1 b=2 c=3\n\
+                     INFO [11-08|10:00:02] Peer a=1 b=2\n";
+        let options = ProcessOptions {
+            parser: LineParser { max_details: Some(2), ..LineParser::new(2025) },
+            ..test_options(EntryFilters::default())
+        };
+
+        let (stats, output) = process_str(input, &options, OutputFormat::Json, false);
+
+        assert_eq!(stats.truncated_details, 1);
+        assert_eq!(output.matches("\"details_truncated\":true").count(), 1);
+    }
+
+    #[test]
+    fn test_crlf_and_mixed_line_endings_leave_no_carriage_returns() {
+        let input = "INFO [11-08|10:00:01] Imported block hash=0xabc\r\n\
+                     INFO [11-08|10:00:02] Imported block hash=0xdef\n\
+                     \r\n\
+                     WARN [11-08|10:00:03] Stale peer hash=0xabc\r";
+        let filters = EntryFilters { details: vec![parse_detail_filter("hash=0xabc").unwrap()], ..EntryFilters::default() };
+
+        let (stats, output) = process_str(input, &test_options(filters), OutputFormat::Json, false);
+
+        let messages: Vec<String> =
+            output.lines().map(|line| serde_json::from_str::<LogEntry>(line).unwrap().message).collect();
+        assert_eq!(messages, ["Imported block hash=0xabc", "Stale peer hash=0xabc"]);
+        assert!(!output.contains("\\r"));
+        assert_eq!(stats.total_lines, 4);
+        assert_eq!(stats.invalid_lines(), 1);
+    }
+
+    #[test]
+    fn test_grep_keeps_matching_messages() {
+        let input = "INFO [11-08|10:00:01] Imported new chain segment\n\
+                     WARN [11-08|10:00:02] Dropping peer\n\
+                     not a log line\n\
+                     INFO [11-08|10:00:03] Imported new block headers\n";
+        let filters = EntryFilters { grep: Some(compile_grep("^Imported").unwrap()), ..EntryFilters::default() };
+
+        let (stats, output) = process_str(input, &test_options(filters), OutputFormat::Json, false);
+
+        assert_eq!(output.lines().count(), 2);
+        assert!(!output.contains("Dropping peer"));
+        assert_eq!(stats.filtered_by_grep, 1);
+        assert_eq!(stats.invalid_lines(), 1);
+    }
+
+    #[test]
+    fn test_grep_invert_keeps_non_matching_messages() {
+        let input = "INFO [11-08|10:00:01] Imported new chain segment\n\
+                     WARN [11-08|10:00:02] Dropping peer\n";
+        let filters = EntryFilters {
+            grep: Some(compile_grep("^Imported").unwrap()),
+            grep_invert: true,
+            ..EntryFilters::default()
+        };
+
+        let (stats, output) = process_str(input, &test_options(filters), OutputFormat::Json, false);
+
+        assert_eq!(output.lines().count(), 1);
+        assert!(output.contains("Dropping peer"));
+        assert_eq!(stats.filtered_by_grep, 1);
+    }
+
+    #[test]
+    fn test_fields_keeps_only_requested_details() {
+        let input = "INFO [11-08|10:00:01] Imported new chain segment number=1 hash=0xabc txs=3 elapsed=1.2s\n";
+        let options = ProcessOptions {
+            fields: Some(vec!["hash".to_string(), "txs".to_string()]),
+            ..test_options(EntryFilters {
+                details: vec![parse_detail_filter("number=1").unwrap()],
+                ..EntryFilters::default()
+            })
+        };
+
+        let (_, output) = process_str(input, &options, OutputFormat::Json, false);
+
+        let entry: LogEntry = serde_json::from_str(output.trim()).unwrap();
+        assert_eq!(entry.details.keys().collect::<Vec<_>>(), ["hash", "txs"]);
+        assert_eq!(entry.block_number, Some(1));
+        assert_eq!(entry.elapsed_ms, Some(1200));
+    }
+
+    #[test]
+    fn test_pretty_output_round_trips() {
+        let input = "INFO [11-08|10:49:09.123] Imported new block headers count=1 elapsed=2.5s\n\
+                     WARN [11-08|10:49:10] Stale peer\n";
+        let (_, output) = process_str(input, &test_options(EntryFilters::default()), OutputFormat::Json, true);
+
+        let values: Vec<serde_json::Value> = serde_json::Deserializer::from_str(&output)
+            .into_iter()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert!(output.contains("\n  \"level\": \"INFO\""));
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[0]["details"]["count"], "1");
+        assert_eq!(values[1]["level"], "WARN");
+    }
+
+    #[test]
+    fn test_parallel_output_matches_serial() {
+        // Span several chunks and mix in invalid lines so ordering is exercised.
+        let input: String = (0..(CHUNK_LINES * 2 + 17))
+            .map(|i| match i % 5 {
+                0 => "not a log line\n".to_string(),
+                1 => format!("WARN [11-08|10:{:02}:{:02}] Stale peer id=p{} reason=timeout\n", (i / 60) % 60, i % 60, i),
+                _ => format!("INFO [11-08|10:{:02}:{:02}.{:03}] Imported block number={} txs={}\n", (i / 60) % 60, i % 60, i % 1000, i, i % 7),
+            })
+            .collect();
+        let parallel = test_options(EntryFilters::default());
+        let serial = ProcessOptions { parallel: false, ..test_options(EntryFilters::default()) };
+
+        let (parallel_stats, parallel_output) = process_str(&input, &parallel, OutputFormat::Json, false);
+        let (serial_stats, serial_output) = process_str(&input, &serial, OutputFormat::Json, false);
+
+        assert_eq!(parallel_stats.total_lines, serial_stats.total_lines);
+        assert_eq!(parallel_stats.valid_lines, serial_stats.valid_lines);
+        assert_eq!(parallel_output, serial_output);
+
+        // One worker thread and small batches still match the serial path exactly.
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+        let (tuned_stats, tuned_output) = pool.install(|| {
+            let tuned = ProcessOptions { chunk_lines: Some(7), ..test_options(EntryFilters::default()) };
+            process_str(&input, &tuned, OutputFormat::Json, false)
+        });
+        assert_eq!(tuned_stats.total_lines, serial_stats.total_lines);
+        assert_eq!(tuned_output, serial_output);
+    }
+
+    #[test]
+    fn test_tail_keeps_last_entries() {
+        let input = "INFO [11-08|10:00:01] First\n\
+                     INFO [11-08|10:00:02] Second\n\
+                     garbage\n\
+                     INFO [11-08|10:00:03] Third\n\
+                     INFO [11-08|10:00:04] Fourth\n";
+        let options = ProcessOptions { tail: Some(2), ..test_options(EntryFilters::default()) };
+
+        let (stats, output) = process_str(input, &options, OutputFormat::Json, false);
+
+        let messages: Vec<String> = output
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["message"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(messages, ["Third", "Fourth"]);
+        assert_eq!(stats.valid_lines, 4);
+        assert_eq!(stats.suppressed_by_tail, 2);
+    }
+
+    #[test]
+    fn test_errors_report_lists_invalid_lines() {
+        let input = "INFO [11-08|10:00:01] First\n\
+                     garbage one\n\
+                     INFO [11-08|10:00:02] Second\n\
+                     INFO [bad-timestamp] Third\r\n";
+        let options = test_options(EntryFilters::default());
+        let pb = ProgressBar::hidden();
+        let mut errors = Vec::new();
+        let mut writer = EntryWriter::new(io::sink(), OutputFormat::Json, false).unwrap();
+
+        let stats = LogProcessor::new(&options, &mut writer, Some(&mut errors), &pb)
+            .process_log_file(input.as_bytes(), None)
+            .unwrap();
+
+        let report = String::from_utf8(errors).unwrap();
+        assert_eq!(report, "2: garbage one\n4: INFO [bad-timestamp] Third\n");
+        assert_eq!(report.lines().count(), stats.total_lines - stats.valid_lines);
+    }
+
+    #[test]
+    fn test_multiple_inputs_share_tail_and_label_errors() {
+        let first = "INFO [11-08|10:00:01] First\nbad line\nINFO [11-08|10:00:02] Second\n";
+        let second = "INFO [11-08|10:00:03] Third\nanother bad line\n";
+        let options = ProcessOptions { tail: Some(2), ..test_options(EntryFilters::default()) };
+        let pb = ProgressBar::hidden();
+        let mut out = Vec::new();
+        let mut errors = Vec::new();
+        let mut writer = EntryWriter::new(&mut out, OutputFormat::Json, false).unwrap();
+
+        let mut processor = LogProcessor::new(&options, &mut writer, Some(&mut errors), &pb);
+        let first_stats = processor.process_log_file(first.as_bytes(), Some("geth.log.1")).unwrap();
+        let second_stats = processor.process_log_file(second.as_bytes(), Some("geth.log")).unwrap();
+        processor.finish().unwrap();
+        drop(writer);
+
+        let output = String::from_utf8(out).unwrap();
+        assert_eq!((first_stats.total_lines, first_stats.valid_lines), (3, 2));
+        assert_eq!((second_stats.total_lines, second_stats.valid_lines), (2, 1));
+        assert_eq!(output.lines().count(), 2);
+        assert!(output.contains("Second") && output.contains("Third"));
+        assert_eq!(String::from_utf8(errors).unwrap(), "geth.log.1:2: bad line\ngeth.log:2: another bad line\n");
+    }
+
+    #[test]
+    fn test_read_line_limited_discards_long_lines() {
+        // A tiny buffer makes long lines span several `fill_buf` calls.
+        let mut reader = io::BufReader::with_capacity(4, "short\nthis line is too long\nexact\nlast".as_bytes());
+        let mut buf = Vec::new();
+
+        let mut read = |reader: &mut io::BufReader<&[u8]>| {
+            buf.clear();
+            let result = read_line_limited(reader, &mut buf, 5).unwrap();
+            (result, String::from_utf8(buf.clone()).unwrap())
+        };
+
+        assert_eq!(read(&mut reader), ((6, false), "short\n".to_string()));
+        assert_eq!(read(&mut reader).0, (22, true));
+        assert_eq!(read(&mut reader), ((6, false), "exact\n".to_string()));
+        assert_eq!(read(&mut reader), ((4, false), "last".to_string()));
+        assert_eq!(read(&mut reader), ((0, false), String::new()));
+    }
+
+    #[test]
+    fn test_max_line_bytes_skips_oversized_lines() {
+        let input = format!(
+            "INFO [11-08|10:00:01] Before\nINFO [11-08|10:00:02] {}\nnot a log line\nINFO [11-08|10:00:03] After\n",
+            "x".repeat(200)
+        );
+        let options = ProcessOptions { max_line_bytes: Some(100), ..test_options(EntryFilters::default()) };
+
+        let (stats, output) = process_str(&input, &options, OutputFormat::Json, false);
+
+        assert_eq!(stats.total_lines, 4);
+        assert_eq!(stats.valid_lines, 2);
+        assert_eq!(stats.oversized_lines, 1);
+        assert_eq!(stats.invalid_lines(), 1);
+        assert_eq!(output.lines().count(), 2);
+    }
+
+    #[test]
+    fn test_invalid_utf8_is_converted_lossily() {
+        let input: &[u8] = b"INFO [11-08|10:00:01] Peer name=geth\xff\xfe dropped\n\
+                             \xff\xff garbage\n\
+                             INFO [11-08|10:00:02] Clean line\n";
+        let options = test_options(EntryFilters::default());
+        let pb = ProgressBar::hidden();
+        let mut out = Vec::new();
+        let mut writer = EntryWriter::new(&mut out, OutputFormat::Json, false).unwrap();
+
+        let stats = LogProcessor::new(&options, &mut writer, None, &pb).process_log_file(input, None).unwrap();
+        drop(writer);
+
+        let output = String::from_utf8(out).unwrap();
+        assert_eq!(stats.total_lines, 3);
+        assert_eq!(stats.valid_lines, 2);
+        assert_eq!(stats.lossy_lines, 2);
+        assert!(output.contains("geth\u{fffd}\u{fffd}"));
+    }
+
+    #[test]
+    fn test_latest_timestamp_ignores_invalid_lines() {
+        let input = "INFO [11-08|10:49:12] Late\nnot a log line\nINFO [11-08|10:49:05] Early\n";
+
+        let latest = latest_timestamp(input.as_bytes(), &LineParser::new(2025), None, None, CHUNK_LINES).unwrap();
+
+        assert_eq!(latest.unwrap().format("%H:%M:%S").to_string(), "10:49:12");
+        assert_eq!(latest_timestamp("garbage\n".as_bytes(), &LineParser::new(2025), None, None, 1).unwrap(), None);
+    }
+
+    #[test]
+    fn test_auto_year_rolls_over_at_new_year() {
+        let input = "INFO [12-31|23:59:59] Last block of the year\nINFO [01-01|00:00:01] First block of the year\n";
+        let auto_year = ProcessOptions { auto_year: true, ..test_options(EntryFilters::default()) };
+
+        let (stats, output) = process_str(input, &auto_year, OutputFormat::Json, false);
+        let (_, single_year_output) = process_str(input, &test_options(EntryFilters::default()), OutputFormat::Json, false);
+
+        let timestamps: Vec<String> = output
+            .lines()
+            .map(|line| serde_json::from_str::<LogEntry>(line).unwrap().timestamp.format("%Y-%m-%d").to_string())
+            .collect();
+        assert_eq!(timestamps, ["2025-12-31", "2026-01-01"]);
+        assert_eq!(stats.year_rollovers, 1);
+        assert!(single_year_output.contains("2025-01-01"));
+    }
+
+    #[test]
+    fn test_join_continuations_attaches_stack_trace() {
+        let input = "ERROR[11-08|10:00:01] Panic in handler\n\
+                     goroutine 1 [running]:\n\
+                     main.main()\n\
+                     \n\
+                     stray line\n\
+                     INFO [11-08|10:00:02] Recovered\n";
+        let options = ProcessOptions { join_continuations: true, ..test_options(EntryFilters::default()) };
+
+        let (stats, output) = process_str(input, &options, OutputFormat::Json, false);
+
+        let entries: Vec<LogEntry> = output.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].continuation, ["goroutine 1 [running]:", "main.main()"]);
+        assert!(entries[1].continuation.is_empty());
+        assert_eq!(stats.joined_lines, 2);
+        assert_eq!(stats.invalid_lines(), 2);
+    }
+
+    #[test]
+    fn test_continuation_lines_are_invalid_by_default() {
+        let input = "ERROR[11-08|10:00:01] Panic in handler\ngoroutine 1 [running]:\n";
+
+        let (stats, output) = process_str(input, &test_options(EntryFilters::default()), OutputFormat::Json, false);
+
+        assert!(!output.contains("continuation"));
+        assert_eq!(stats.invalid_lines(), 1);
+    }
+
+    #[test]
+    fn test_max_lines_stops_across_chunks_and_inputs() {
+        let line = "INFO [11-08|10:49:09] Imported new block headers count=1\n";
+        let input = line.repeat(CHUNK_LINES + 10);
+        let options = ProcessOptions { max_lines: Some(CHUNK_LINES + 3), ..test_options(EntryFilters::default()) };
+        let mut writer = EntryWriter::new(Vec::new(), OutputFormat::Json, false).unwrap();
+        let pb = ProgressBar::hidden();
+
+        let mut processor = LogProcessor::new(&options, &mut writer, None, &pb);
+        let first = processor.process_log_file(input.as_bytes(), None).unwrap();
+        assert!(processor.reached_max_lines());
+        let second = processor.process_log_file(input.as_bytes(), None).unwrap();
+        processor.finish().unwrap();
+
+        assert_eq!(first.total_lines, CHUNK_LINES + 3);
+        assert_eq!(first.bytes_read, (line.len() * (CHUNK_LINES + 3)) as u64);
+        assert_eq!(second.total_lines, 0);
+        let EntryWriter::Json { out, .. } = writer else { unreachable!() };
+        assert_eq!(String::from_utf8(out).unwrap().lines().count(), CHUNK_LINES + 3);
+    }
+
+    #[test]
+    fn test_strict_fails_on_first_unparseable_line() {
+        let input = "INFO [11-08|10:00:01] Imported block\nnot a log line\nalso garbage\n";
+        let options = ProcessOptions { strict: true, ..test_options(EntryFilters::default()) };
+        let mut writer = EntryWriter::new(Vec::new(), OutputFormat::Json, false).unwrap();
+        let pb = ProgressBar::hidden();
+
+        let mut processor = LogProcessor::new(&options, &mut writer, None, &pb);
+        let error = processor.process_log_file(input.as_bytes(), None).unwrap_err().to_string();
+
+        assert!(error.contains("line 2"));
+        assert!(error.contains("\"not a log line\""));
+    }
+
+    #[test]
+    fn test_sort_by_timestamp_orders_shuffled_input() {
+        let input = "INFO [11-08|10:00:03] third\n\
+                     WARN [11-08|10:00:01] first\n\
+                     INFO [11-08|10:00:04] fourth\n\
+                     ERROR[11-08|10:00:02] second\n\
+                     INFO [11-08|10:00:02] second tie\n";
+        let options = ProcessOptions { sort_by: Some(SortBy::Timestamp), ..test_options(EntryFilters::default()) };
+
+        let (_, output) = process_str(input, &options, OutputFormat::Json, false);
+
+        let messages: Vec<String> =
+            output.lines().map(|line| serde_json::from_str::<LogEntry>(line).unwrap().message).collect();
+        assert_eq!(messages, ["first", "second", "second tie", "third", "fourth"]);
+    }
+
+    #[test]
+    fn test_sort_by_level_puts_most_severe_first() {
+        let input = "INFO [11-08|10:00:01] a\nERROR[11-08|10:00:02] b\nWARN [11-08|10:00:03] c\nINFO [11-08|10:00:04] d\n";
+        let options = ProcessOptions { sort_by: Some(SortBy::Level), ..test_options(EntryFilters::default()) };
+
+        let (_, output) = process_str(input, &options, OutputFormat::Json, false);
+
+        let messages: Vec<String> =
+            output.lines().map(|line| serde_json::from_str::<LogEntry>(line).unwrap().message).collect();
+        assert_eq!(messages, ["b", "c", "a", "d"]);
+    }
+
+    #[test]
+    fn test_file_year_overrides_parser_year() {
+        let input = "INFO [11-08|10:00:01] Imported block\n";
+        let options = test_options(EntryFilters::default());
+        let mut writer = EntryWriter::new(Vec::new(), OutputFormat::Json, false).unwrap();
+        let pb = ProgressBar::hidden();
+
+        let mut processor = LogProcessor::new(&options, &mut writer, None, &pb);
+        processor.file_year = Some(2021);
+        processor.process_log_file(input.as_bytes(), None).unwrap();
+        processor.file_year = None;
+        processor.process_log_file(input.as_bytes(), None).unwrap();
+        processor.finish().unwrap();
+
+        let EntryWriter::Json { out, .. } = writer else { unreachable!() };
+        let output = String::from_utf8(out).unwrap();
+        let years: Vec<i32> =
+            output.lines().map(|line| serde_json::from_str::<LogEntry>(line).unwrap().timestamp.year()).collect();
+        assert_eq!(years, [2021, 2025]);
+    }
+
+    #[test]
+    fn test_dedup_collapses_consecutive_duplicates() {
+        let input = "WARN [11-08|10:00:01] Stale peer id=a\n\
+                     WARN [11-08|10:00:02] Stale peer id=b\n\
+                     WARN [11-08|10:00:03] Stale peer id=c\n\
+                     INFO [11-08|10:00:04] Imported block\n\
+                     WARN [11-08|10:00:05] Stale peer id=d\n";
+        let options = ProcessOptions { dedup: true, ..test_options(EntryFilters::default()) };
+
+        let (stats, output) = process_str(input, &options, OutputFormat::Json, false);
+
+        let values: Vec<serde_json::Value> = output.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(values.len(), 3);
+        assert_eq!(values[0]["details"]["id"], "a");
+        assert_eq!(values[0]["repeat_count"], 3);
+        assert_eq!(values[1]["repeat_count"], 1);
+        assert_eq!(values[2]["details"]["id"], "d");
+        assert_eq!(stats.collapsed_duplicates, 2);
+    }
+
+    #[test]
+    fn test_sample_keeps_every_nth_entry_after_filters() {
+        let input: String = (1..=10).map(|i| format!("INFO [11-08|10:00:{:02}] Entry {}\n", i, i)).collect();
+        let input = format!("{}DEBUG [11-08|10:00:11] Skipped\n{}", input, "INFO [11-08|10:00:12] Entry 11\n");
+        let filters = EntryFilters { min_level: Some(LogLevel::Info), ..EntryFilters::default() };
+        let options = ProcessOptions { sample: Some(Sampler::every(3)), ..test_options(filters) };
+
+        let (stats, output) = process_str(&input, &options, OutputFormat::Json, false);
+
+        let messages: Vec<String> =
+            output.lines().map(|line| serde_json::from_str::<LogEntry>(line).unwrap().message).collect();
+        assert_eq!(messages, ["Entry 1", "Entry 4", "Entry 7", "Entry 10"]);
+        assert_eq!(stats.sampled_out, 7);
+        assert_eq!(stats.filtered_by_level, 1);
+    }
+
+    #[test]
+    fn test_unique_by_keeps_first_or_last_entry_per_hash() {
+        let input = "INFO [11-08|10:00:01] Imported block hash=0xaa n=1\n\
+                     INFO [11-08|10:00:02] Imported block hash=0xbb n=2\n\
+                     INFO [11-08|10:00:03] Imported block hash=0xaa n=3\n\
+                     INFO [11-08|10:00:04] Chain head updated\n\
+                     INFO [11-08|10:00:05] Imported block hash=0xaa n=4\n";
+        let run = |keep| {
+            let options =
+                ProcessOptions { unique_by: Some(("hash".to_string(), keep)), ..test_options(EntryFilters::default()) };
+            let (stats, output) = process_str(input, &options, OutputFormat::Json, false);
+            let kept: Vec<String> = output
+                .lines()
+                .map(|line| serde_json::from_str::<LogEntry>(line).unwrap())
+                .map(|entry| entry.details.get("n").cloned().unwrap_or(entry.message))
+                .collect();
+            (stats.duplicates_dropped, kept)
+        };
+
+        assert_eq!(run(KeepEntry::First), (2, vec!["1".to_string(), "2".into(), "Chain head updated".into()]));
+        assert_eq!(run(KeepEntry::Last), (2, vec!["2".to_string(), "Chain head updated".into(), "4".into()]));
+    }
+
+    #[test]
+    fn test_sample_random_is_reproducible_by_seed() {
+        let keeps = |seed| {
+            let mut sampler = Sampler::random(4, seed);
+            (0..1000).map(|_| sampler.keep()).collect::<Vec<bool>>()
+        };
+
+        let kept = keeps(7).iter().filter(|&&keep| keep).count();
+        assert_eq!(keeps(7), keeps(7));
+        assert_ne!(keeps(7), keeps(8));
+        assert!((200..300).contains(&kept), "kept {} of 1000", kept);
+    }
+
+    #[test]
+    fn test_with_line_number_records_source_lines() {
+        let input = "INFO [11-08|10:00:01] First\n\
+                     not a log line\n\
+                     INFO [11-08|10:00:02] Second\n\
+                     DEBUG [11-08|10:00:03] Filtered\n\
+                     INFO [11-08|10:00:04] Third\n\
+                     INFO [11-08|10:00:05] Fourth\n";
+        let filters = EntryFilters { min_level: Some(LogLevel::Info), ..EntryFilters::default() };
+        let options = ProcessOptions { with_line_number: true, chunk_lines: Some(2), ..test_options(filters) };
+        let pb = ProgressBar::hidden();
+        let mut out = Vec::new();
+        let mut writer = EntryWriter::new(&mut out, OutputFormat::Json, false).unwrap();
+
+        let mut processor = LogProcessor::new(&options, &mut writer, None, &pb);
+        processor.process_log_file(input.as_bytes(), None).unwrap();
+        processor.process_log_file(input.as_bytes(), Some("b.log")).unwrap();
+        processor.finish().unwrap();
+        drop(writer);
+
+        let entries: Vec<LogEntry> =
+            String::from_utf8(out).unwrap().lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        let lines: Vec<usize> = entries.iter().map(|entry| entry.line.unwrap()).collect();
+        let files: Vec<Option<&str>> = entries.iter().map(|entry| entry.file.as_deref()).collect();
+        assert_eq!(lines, [1, 3, 5, 6, 1, 3, 5, 6]);
+        assert_eq!(files, [None, None, None, None, Some("b.log"), Some("b.log"), Some("b.log"), Some("b.log")]);
+        for entry in &entries {
+            assert!(input.lines().nth(entry.line.unwrap() - 1).unwrap().ends_with(&entry.message));
+        }
+    }
+
+    #[test]
+    fn test_keep_raw_records_trimmed_source_line() {
+        let input = "INFO [11-08|10:49:09] Imported new block headers count=1  \r\nWARN [11-08|10:49:10] Stale peer\n";
+        let options = ProcessOptions { keep_raw: true, ..test_options(EntryFilters::default()) };
+
+        let (_, kept) = process_str(input, &options, OutputFormat::Json, false);
+        let (_, plain) = process_str(input, &test_options(EntryFilters::default()), OutputFormat::Json, false);
+
+        let raws: Vec<String> =
+            kept.lines().map(|line| serde_json::from_str::<LogEntry>(line).unwrap().raw).collect();
+        assert_eq!(raws, ["INFO [11-08|10:49:09] Imported new block headers count=1", "WARN [11-08|10:49:10] Stale peer"]);
+        assert!(!plain.contains("\"raw\""));
+    }
+
+    #[test]
+    fn test_extract_script_adds_fields_and_counts_failures() {
+        let input = "INFO [11-08|10:49:09] Imported new chain segment txs=4\nINFO [11-08|10:49:10] Imported new chain segment\n";
+        let extractor = ScriptExtractor::compile("#{ txs_x2: parse_int(details.txs) * 2 }").unwrap();
+        let options = ProcessOptions { extract_script: Some(extractor), ..test_options(EntryFilters::default()) };
+
+        let (stats, output) = process_str(input, &options, OutputFormat::Json, false);
+
+        let entries: Vec<LogEntry> = output.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].details["txs_x2"], "8");
+        assert!(!entries[1].details.contains_key("txs_x2"));
+        assert_eq!(stats.script_errors, 1);
+    }
+
+    #[test]
+    fn test_anonymize_scrubs_output_and_counts_distinct_values() {
+        let input = "INFO [11-08|10:49:09] Looking for peers peer=10.0.0.7:30303\n\
+                     WARN [11-08|10:49:10] Dropping peer peer=10.0.0.7:30303 reason=timeout\n\
+                     WARN [11-08|10:49:11] Dropping peer peer=10.0.0.8:30303 reason=timeout\n";
+        let options = ProcessOptions { anonymize: true, ..test_options(EntryFilters::default()) };
+
+        let (stats, output) = process_str(input, &options, OutputFormat::Json, false);
+
+        let peers: Vec<String> = output
+            .lines()
+            .map(|line| serde_json::from_str::<LogEntry>(line).unwrap().details["peer"].clone())
+            .collect();
+        assert_eq!(stats.anonymized_values, 2);
+        assert!(!output.contains("10.0.0."));
+        assert_eq!(peers[0], peers[1]);
+        assert_ne!(peers[1], peers[2]);
+        assert!(peers[0].starts_with("ip-") && peers[0].ends_with(":30303"));
+    }
+
+    #[test]
+    fn test_redact_replaces_tokens_and_counts_matches() {
+        let input = "INFO [11-08|10:49:09] Dialing https://rpc.example/v1/sk_live_4f9a2c url=https://rpc.example/?key=sk_live_77e1d0\n\
+                     INFO [11-08|10:49:10] Connected\n";
+        let options = ProcessOptions {
+            redact: vec![compile_redact(r"sk_live_[0-9a-f]+").unwrap()],
+            keep_raw: true,
+            ..test_options(EntryFilters::default())
+        };
+
+        let (stats, output) = process_str(input, &options, OutputFormat::Json, false);
+
+        let entry: LogEntry = serde_json::from_str(output.lines().next().unwrap()).unwrap();
+        assert_eq!(stats.redactions, 5);
+        assert!(!output.contains("sk_live_"));
+        assert!(entry.message.starts_with("Dialing https://rpc.example/v1/[REDACTED] url="));
+        assert_eq!(entry.details["url"], "https://rpc.example/?key=[REDACTED]");
+        assert!(compile_redact("x*").unwrap_err().to_string().contains("must not match the empty string"));
+        assert!(compile_redact("(").is_err());
+    }
+
+    #[test]
+    fn test_interrupted_stops_before_reading() {
+        let options = test_options(EntryFilters::default());
+        options.interrupted.store(true, Ordering::Relaxed);
+
+        let (stats, output) = process_str("INFO [11-08|10:49:09] Started\n", &options, OutputFormat::Json, false);
+
+        assert!(options.was_interrupted());
+        assert_eq!(stats.total_lines, 0);
+        assert!(output.is_empty());
+
+        let follow = ProcessOptions { follow: true, ..test_options(EntryFilters::default()) };
+        follow.interrupted.store(true, Ordering::Relaxed);
+        assert!(!follow.was_interrupted());
+    }
+
+    #[test]
+    fn test_repeat_count_omitted_without_dedup() {
+        let input = "WARN [11-08|10:00:01] Stale peer\nWARN [11-08|10:00:02] Stale peer\n";
+
+        let (_, output) = process_str(input, &test_options(EntryFilters::default()), OutputFormat::Json, false);
+
+        assert_eq!(output.lines().count(), 2);
+        assert!(!output.contains("repeat_count"));
+    }
+
+    #[test]
+    fn test_gzip_input_matches_plain_input() {
+        let input = "INFO [11-08|10:49:09.123] Imported new block headers count=1 elapsed=2.5s\n\
+                     WARN [11-08|10:49:10] Stale peer id=abc\n\
+                     garbage line\n";
+        let dir = std::env::temp_dir();
+        let plain_path = dir.join(format!("glc-test-{}.log", process::id()));
+        let gz_path = dir.join(format!("glc-test-{}.log.gz", process::id()));
+
+        std::fs::write(&plain_path, input).unwrap();
+        let mut encoder = flate2::write::GzEncoder::new(File::create(&gz_path).unwrap(), flate2::Compression::default());
+        encoder.write_all(input.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+
+        let pb = ProgressBar::hidden();
+        let mut plain_out = Vec::new();
+        let mut gz_out = Vec::new();
+        let mut plain_writer = EntryWriter::new(&mut plain_out, OutputFormat::Json, false).unwrap();
+        let mut gz_writer = EntryWriter::new(&mut gz_out, OutputFormat::Json, false).unwrap();
+        let plain_input = open_log_file(&plain_path, false).unwrap();
+        let gz_input = open_log_file(&gz_path, false).unwrap();
+        assert_eq!(plain_input.total_bytes, Some(input.len() as u64));
+        assert_eq!(gz_input.total_bytes, None);
+
+        let options = test_options(EntryFilters::default());
+        LogProcessor::new(&options, &mut plain_writer, None, &pb).process_log_file(plain_input.reader, None).unwrap();
+        LogProcessor::new(&options, &mut gz_writer, None, &pb).process_log_file(gz_input.reader, None).unwrap();
+        drop((plain_writer, gz_writer));
+
+        std::fs::remove_file(&plain_path).unwrap();
+        std::fs::remove_file(&gz_path).unwrap();
+
+        assert_eq!(String::from_utf8(plain_out.clone()).unwrap().lines().count(), 2);
+        assert_eq!(plain_out, gz_out);
+    }
+
+    #[test]
+    fn test_mmap_input_matches_buffered_input() {
+        let input = "INFO [11-08|10:49:09.123] Imported new block headers count=1\r\n\
+                     garbage line\n\
+                     WARN [11-08|10:49:10] Stale peer id=abc";
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("glc-test-{}-mmap.log", process::id()));
+        let gz_path = dir.join(format!("glc-test-{}-mmap.log.gz", process::id()));
+        let empty_path = dir.join(format!("glc-test-{}-mmap-empty.log", process::id()));
+        std::fs::write(&path, input).unwrap();
+        let mut encoder = flate2::write::GzEncoder::new(File::create(&gz_path).unwrap(), flate2::Compression::default());
+        encoder.write_all(input.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+        std::fs::write(&empty_path, "").unwrap();
+
+        let options = test_options(EntryFilters::default());
+        let crunch = |path: &Path, mmap: bool| {
+            let pb = ProgressBar::hidden();
+            let mut out = Vec::new();
+            let mut writer = EntryWriter::new(&mut out, OutputFormat::Json, false).unwrap();
+            let input = open_log_file(path, mmap).unwrap();
+            let stats = LogProcessor::new(&options, &mut writer, None, &pb).process_log_file(input.reader, None).unwrap();
+            drop(writer);
+            (input.total_bytes, stats.total_lines, out)
+        };
+        let buffered = crunch(&path, false);
+        let mapped = crunch(&path, true);
+        let gz_mapped = crunch(&gz_path, true);
+        let empty_mapped = crunch(&empty_path, true);
+        for path in [&path, &gz_path, &empty_path] {
+            std::fs::remove_file(path).unwrap();
+        }
+
+        assert_eq!(mapped, buffered);
+        assert_eq!(String::from_utf8(mapped.2.clone()).unwrap().lines().count(), 2);
+        assert_eq!(gz_mapped.2, buffered.2);
+        assert_eq!(empty_mapped, (Some(0), 0, Vec::new()));
+    }
+}
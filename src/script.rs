@@ -0,0 +1,137 @@
+//! User-supplied Rhai scripts that derive extra `details` fields from each parsed entry.
+//!
+//! The script sees three variables and returns a map of new fields (or `()` for none):
+//!
+//! ```rhai
+//! // level: "INFO", message: the full message, details: map of the parsed key=value pairs
+//! if details.contains("txs") && details.contains("gas") {
+//!     #{ gas_per_tx: parse_int(details.gas) / parse_int(details.txs) }
+//! }
+//! ```
+
+// --- IMPORTS ---
+// Standard library imports
+use std::fs;
+
+// Third-party libraries
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::{Dynamic, Engine, Map, Scope, AST};
+
+// Local crate
+use crate::LogEntry;
+
+// --- DATA STRUCTURES ---
+/// A compiled field-extraction script and the sandboxed engine that runs it.
+#[derive(Debug)]
+pub struct ScriptExtractor {
+    engine: Engine,  // Engine with no file access and bounded resources
+    ast: AST,        // The compiled script
+}
+
+// --- GLOBAL VARIABLES ---
+/// Most operations one script run may take, so an endless loop fails that line instead of hanging.
+const MAX_OPERATIONS: u64 = 100_000;
+
+/// Largest string, array or map a script may build.
+const MAX_VALUE_SIZE: usize = 64 * 1024;
+
+// --- IMPLEMENTATIONS ---
+impl ScriptExtractor {
+    /// Reads and compiles the script at `path`.
+    pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let source =
+            fs::read_to_string(path).map_err(|e| format!("Error: Could not read extract script '{}': {}", path, e))?;
+        Self::compile(&source).map_err(|e| format!("Error: Invalid extract script '{}': {}", path, e).into())
+    }
+
+    /// Compiles `source` in a sandboxed engine: no `import` of files, no `eval`, no printing,
+    /// and capped operations, call depth and value sizes.
+    pub fn compile(source: &str) -> Result<Self, String> {
+        let mut engine = Engine::new();
+        engine.set_module_resolver(DummyModuleResolver::new());
+        engine.disable_symbol("eval");
+        engine.on_print(|_| {});
+        engine.on_debug(|_, _, _| {});
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.set_max_call_levels(32);
+        engine.set_max_expr_depths(64, 32);
+        engine.set_max_string_size(MAX_VALUE_SIZE);
+        engine.set_max_array_size(MAX_VALUE_SIZE);
+        engine.set_max_map_size(MAX_VALUE_SIZE);
+
+        let ast = engine.compile(source).map_err(|e| e.to_string())?;
+        Ok(ScriptExtractor { engine, ast })
+    }
+
+    /// Runs the script for `entry`, merging the returned fields into its `details` as strings.
+    ///
+    /// Returned keys overwrite parsed ones. On error `entry` is left unchanged.
+    pub fn extract(&self, entry: &mut LogEntry) -> Result<(), String> {
+        let details: Map = entry
+            .details
+            .iter()
+            .map(|(key, value)| (key.into(), Dynamic::from(value.clone())))
+            .collect();
+        let mut scope = Scope::new();
+        scope.push("level", entry.level.clone());
+        scope.push("message", entry.message.clone());
+        scope.push("details", details);
+
+        let result: Dynamic = self.engine.eval_ast_with_scope(&mut scope, &self.ast).map_err(|e| e.to_string())?;
+        if result.is_unit() {
+            return Ok(());
+        }
+        let type_name = result.type_name();
+        let fields = result
+            .try_cast::<Map>()
+            .ok_or_else(|| format!("script must return a map or (), got {}", type_name))?;
+        for (key, value) in fields {
+            entry.details.insert(key.to_string(), value.to_string());
+        }
+        Ok(())
+    }
+}
+
+// --- UNIT TESTS ---
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_line;
+
+    #[test]
+    fn test_extract_merges_returned_fields() {
+        let extractor = ScriptExtractor::compile(
+            r#"
+            let fields = #{ lower_level: level.to_lower() };
+            if details.contains("txs") {
+                fields.gas_per_tx = parse_int(details.gas) / parse_int(details.txs);
+            }
+            fields
+            "#,
+        )
+        .unwrap();
+        let mut entry = parse_line("INFO [11-08|10:49:09] Imported new chain segment txs=4 gas=84000", 2025).unwrap();
+
+        extractor.extract(&mut entry).unwrap();
+
+        assert_eq!(entry.details["gas_per_tx"], "21000");
+        assert_eq!(entry.details["lower_level"], "info");
+        assert_eq!(entry.details["txs"], "4");
+    }
+
+    #[test]
+    fn test_extract_errors_leave_entry_unchanged() {
+        let mut entry = parse_line("INFO [11-08|10:49:09] Started", 2025).unwrap();
+
+        let failing = ScriptExtractor::compile(r#"#{ n: parse_int(details.missing) }"#).unwrap();
+        let endless = ScriptExtractor::compile("loop {}").unwrap();
+        let wrong_type = ScriptExtractor::compile("42").unwrap();
+
+        assert!(failing.extract(&mut entry).is_err());
+        assert!(endless.extract(&mut entry).is_err());
+        assert!(wrong_type.extract(&mut entry).unwrap_err().contains("must return a map"));
+        assert!(ScriptExtractor::compile(r#"import "other" as other;"#).unwrap().extract(&mut entry).is_err());
+        assert!(entry.details.is_empty());
+        assert!(ScriptExtractor::compile("let = ;").is_err());
+    }
+}
@@ -0,0 +1,193 @@
+//! `--state` files: how far each input was read, so the next run only processes appended lines.
+
+// --- IMPORTS ---
+// Standard library imports
+use std::collections::BTreeMap;
+use std::fs::{self, File, Metadata};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
+
+// Third-party libraries
+use serde::{Deserialize, Serialize};
+
+// Local crate
+use crate::input::{LogInput, GZIP_MAGIC, ZSTD_MAGIC};
+use crate::processor::ProcessStats;
+
+// --- DATA STRUCTURES ---
+/// Resume offsets of every file crunched with the same `--state` file, keyed by path.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ResumeState {
+    files: BTreeMap<String, FileState>,  // Progress per input path, as given on the command line
+}
+
+/// How far one file was read, and what it looked like at the time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileState {
+    pub offset: u64,  // Bytes processed from the start of the file; always the end of a complete line
+    pub size: u64,    // File size when it was read
+    pub inode: u64,   // Inode number, to notice a rotated file at the same path (0 where unavailable)
+}
+
+// --- GLOBAL VARIABLES ---
+/// Bytes read at a time while looking backwards for the last newline.
+const SCAN_BLOCK_BYTES: u64 = 8 * 1024;
+
+// --- IMPLEMENTATIONS ---
+impl ResumeState {
+    /// Reads a state file. A missing file is a fresh start; an unreadable or malformed one is an error.
+    pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(format!("Error: Could not read state file '{}': {}", path, e).into()),
+        };
+        serde_json::from_str(&contents).map_err(|e| format!("Error: Invalid state file '{}': {}", path, e).into())
+    }
+
+    /// Writes the state file, replacing it only once the new contents are complete.
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let partial = format!("{}.tmp", path);
+        fs::write(&partial, serde_json::to_string_pretty(self)? + "\n")
+            .and_then(|()| fs::rename(&partial, path))
+            .map_err(|e| format!("Error: Could not write state file '{}': {}", path, e).into())
+    }
+
+    /// Where to resume reading `path`, now that it looks like `current`: the stored offset, or 0
+    /// for a file not seen before. `Err` explains why the stored offset no longer applies (the
+    /// file was rotated or truncated), in which case it is read from the start.
+    pub fn resume_offset(&self, path: &str, current: &FileState) -> Result<u64, String> {
+        let Some(stored) = self.files.get(path) else {
+            return Ok(0);
+        };
+        if stored.inode != current.inode {
+            return Err(format!("'{}' was replaced since the last run (rotated?)", path));
+        }
+        if current.size < stored.size || current.size < stored.offset {
+            return Err(format!("'{}' shrank from {} to {} bytes since the last run", path, stored.size, current.size));
+        }
+        Ok(stored.offset)
+    }
+
+    /// Records how far `path` has been read.
+    pub fn record(&mut self, path: &str, state: FileState) {
+        self.files.insert(path.to_string(), state);
+    }
+}
+
+impl FileState {
+    /// Describes a file from its metadata, read up to `offset`.
+    pub fn new(metadata: &Metadata, offset: u64) -> Self {
+        #[cfg(unix)]
+        let inode = metadata.ino();
+        #[cfg(not(unix))]
+        let inode = 0;
+        FileState { offset, size: metadata.len(), inode }
+    }
+}
+
+// --- HELPER FUNCTIONS ---
+/// Returns the offset just past the last newline in `file` between `from` and `size`, or `from`
+/// if there is none, so a line still being written is left for the next run.
+pub fn complete_lines_end(file: &mut File, from: u64, size: u64) -> io::Result<u64> {
+    let mut block = Vec::new();
+    let mut end = size;
+    while end > from {
+        let start = end.saturating_sub(SCAN_BLOCK_BYTES).max(from);
+        block.resize((end - start) as usize, 0);
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(&mut block)?;
+        if let Some(newline) = block.iter().rposition(|&byte| byte == b'\n') {
+            return Ok(start + newline as u64 + 1);
+        }
+        end = start;
+    }
+    Ok(from)
+}
+
+/// Opens a plain log file for `--state`, from where the previous run stopped to the end of its
+/// last complete line. A rotated or truncated file is read from the start, with a warning.
+///
+/// Returns the input, its starting offset, and the file's state once the input is fully read.
+pub fn open_resumed(
+    path: &str,
+    state: &ResumeState,
+    quiet: bool,
+) -> Result<(LogInput<'static>, u64, FileState), Box<dyn std::error::Error>> {
+    let mut file = File::open(path)?;
+    let current = FileState::new(&file.metadata()?, 0);
+    let mut head = [0; 4];
+    let head_len = file.read(&mut head)?;
+    let head = &head[..head_len];
+    let extension = Path::new(path).extension().and_then(|ext| ext.to_str());
+    if matches!(extension, Some("gz" | "zst" | "zstd")) || head.starts_with(&GZIP_MAGIC) || head == ZSTD_MAGIC {
+        return Err(format!("Error: Cannot resume compressed file '{}' with --state", path).into());
+    }
+
+    let start = state.resume_offset(path, &current).unwrap_or_else(|reason| {
+        if !quiet {
+            eprintln!("Warning: {}; reading it from the start", reason);
+        }
+        0
+    });
+    let end = complete_lines_end(&mut file, start, current.size)?;
+    file.seek(SeekFrom::Start(start))?;
+    let reader = Box::new(io::BufReader::new(file.take(end - start)));
+    Ok((LogInput { reader, total_bytes: Some(end - start) }, start, FileState { offset: end, ..current }))
+}
+
+/// Records how far each `--state` input was read, and saves the state file. Inputs skipped after
+/// `--max-lines` or Ctrl-C keep their starting offset.
+pub fn save_resume_state(
+    path: &str,
+    state: &mut ResumeState,
+    resumed: &[(&str, u64, FileState)],
+    file_stats: &[(&str, i32, ProcessStats)],
+) -> Result<(), Box<dyn std::error::Error>> {
+    for (index, &(log_file_path, start, read_to_end)) in resumed.iter().enumerate() {
+        let bytes_read = file_stats.get(index).map_or(0, |(_, _, stats)| stats.bytes_read);
+        state.record(log_file_path, FileState { offset: start + bytes_read, ..read_to_end });
+    }
+    state.save(path)
+}
+
+// --- UNIT TESTS ---
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resume_offset_detects_rotation_and_truncation() {
+        let mut state = ResumeState::default();
+        state.record("geth.log", FileState { offset: 100, size: 120, inode: 7 });
+
+        let grown = FileState { offset: 0, size: 500, inode: 7 };
+        let rotated = FileState { offset: 0, size: 500, inode: 8 };
+        let truncated = FileState { offset: 0, size: 50, inode: 7 };
+
+        assert_eq!(state.resume_offset("geth.log", &grown), Ok(100));
+        assert_eq!(state.resume_offset("other.log", &grown), Ok(0));
+        assert!(state.resume_offset("geth.log", &rotated).unwrap_err().contains("replaced"));
+        assert!(state.resume_offset("geth.log", &truncated).unwrap_err().contains("shrank from 120 to 50"));
+    }
+
+    #[test]
+    fn test_complete_lines_end_skips_partial_last_line() {
+        let path = std::env::temp_dir().join(format!("glc-state-{}.log", std::process::id()));
+        let long_line = "x".repeat(SCAN_BLOCK_BYTES as usize * 2);
+        fs::write(&path, format!("first\n{}\npartial", long_line)).unwrap();
+        let mut file = File::open(&path).unwrap();
+        let size = file.metadata().unwrap().len();
+
+        let end = complete_lines_end(&mut file, 0, size).unwrap();
+        let from_partial = complete_lines_end(&mut file, end, size).unwrap();
+        let within_first = complete_lines_end(&mut file, 0, 3).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(end, size - "partial".len() as u64);
+        assert_eq!(from_partial, end);
+        assert_eq!(within_first, 0);
+    }
+}
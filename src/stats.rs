@@ -0,0 +1,430 @@
+//! Aggregate statistics collected over parsed log entries.
+
+// --- IMPORTS ---
+// Standard library imports
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+// Third-party libraries
+use chrono::{DateTime, Duration, Local, TimeZone};
+use serde::{Serialize, Serializer};
+
+// Local crate
+use crate::{normalize_message, serialize_timestamp, LogEntry, LogLevel};
+
+// --- DATA STRUCTURES ---
+/// Running totals accumulated one entry at a time during a single pass.
+#[derive(Debug, Default)]
+pub struct LogStats {
+    level_counts: BTreeMap<String, usize>,    // Entries seen per level
+    earliest: Option<DateTime<Local>>,        // Earliest timestamp seen
+    latest: Option<DateTime<Local>>,          // Latest timestamp seen
+    message_counts: HashMap<String, usize>,   // Entries seen per normalized message
+}
+
+/// The final stats report, serialized as a single JSON object.
+#[derive(Debug, Serialize)]
+pub struct StatsReport {
+    pub levels: BTreeMap<String, usize>,      // Entry count per level
+    #[serde(serialize_with = "serialize_optional_timestamp")]
+    pub earliest: Option<DateTime<Local>>,    // Earliest timestamp seen
+    #[serde(serialize_with = "serialize_optional_timestamp")]
+    pub latest: Option<DateTime<Local>>,      // Latest timestamp seen
+    pub top_messages: Vec<MessageCount>,      // Most frequent normalized messages, most frequent first
+}
+
+/// Per-group rollups of entries, grouped by the value of one `details` key.
+#[derive(Debug)]
+pub struct GroupStats {
+    key: String,                                // `details` key whose value names the group
+    groups: HashMap<String, GroupSummary>,      // Running summary per group value
+}
+
+/// The rollup for one group, serialized as one NDJSON line.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GroupSummary {
+    pub group: String,                          // The key's value, or `NO_GROUP` for entries without it
+    pub count: usize,                           // Entries in the group
+    #[serde(serialize_with = "serialize_timestamp")]
+    pub first: DateTime<Local>,                 // Earliest timestamp in the group
+    #[serde(serialize_with = "serialize_timestamp")]
+    pub last: DateTime<Local>,                  // Latest timestamp in the group
+    pub levels: BTreeMap<String, usize>,        // Entry count per level
+}
+
+/// A normalized message and how many entries carried it.
+#[derive(Debug, Serialize)]
+pub struct MessageCount {
+    pub message: String,
+    pub count: usize,
+}
+
+/// Entry counts per fixed-width time bucket, for spotting bursts.
+#[derive(Debug)]
+pub struct Histogram {
+    interval_secs: i64,                 // Bucket width in seconds
+    counts: BTreeMap<i64, usize>,       // Entries per bucket, keyed by bucket start (Unix seconds)
+}
+
+/// Group name for entries that don't carry the `--group-by` key.
+pub const NO_GROUP: &str = "(none)";
+
+/// One histogram bucket: entries with `start <= timestamp < start + interval`.
+#[derive(Debug, Serialize)]
+pub struct HistogramBucket {
+    #[serde(serialize_with = "serialize_timestamp")]
+    pub start: DateTime<Local>,
+    pub count: usize,
+}
+
+/// Every `elapsed_ms` value seen, buffered so exact percentiles can be taken at the end.
+#[derive(Debug, Default)]
+pub struct LatencyStats {
+    samples: Vec<u64>,                  // `elapsed_ms` of each entry that carried one, in arrival order
+}
+
+/// Distribution of `elapsed_ms` values, in milliseconds.
+///
+/// Percentiles use the nearest-rank method: `pN` is the smallest value with at least N% of
+/// samples at or below it, so it is always one of the observed values.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LatencyReport {
+    pub count: usize,                   // Entries that carried an `elapsed` duration
+    pub min_ms: u64,
+    pub p50_ms: u64,
+    pub p90_ms: u64,
+    pub p99_ms: u64,
+    pub max_ms: u64,
+    pub mean_ms: f64,
+}
+
+/// Sliding-window count of ERROR entries, flagging any window that holds more than a maximum.
+///
+/// Assumes entries arrive in time order, as they do within a log file.
+#[derive(Debug)]
+pub struct ErrorRateMonitor {
+    window: Duration,                   // Width of the sliding window
+    max: usize,                         // Most ERROR entries allowed in any one window
+    recent: VecDeque<DateTime<Local>>,  // ERROR timestamps less than `window` before the latest one
+    peak: Option<RateBreach>,           // Busiest window found above `max`, if any
+}
+
+/// A window holding more ERROR entries than allowed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct RateBreach {
+    #[serde(serialize_with = "serialize_timestamp")]
+    pub start: DateTime<Local>,         // First ERROR entry in the window
+    #[serde(serialize_with = "serialize_timestamp")]
+    pub end: DateTime<Local>,           // Last ERROR entry in the window
+    pub count: usize,                   // ERROR entries from `start` to `end`, inclusive
+}
+
+// --- IMPLEMENTATIONS ---
+impl LogStats {
+    /// Folds a single entry into the running totals.
+    pub fn record(&mut self, entry: &LogEntry) {
+        *self.level_counts.entry(entry.level.clone()).or_insert(0) += 1;
+
+        if self.earliest.is_none_or(|earliest| entry.timestamp < earliest) {
+            self.earliest = Some(entry.timestamp);
+        }
+        if self.latest.is_none_or(|latest| entry.timestamp > latest) {
+            self.latest = Some(entry.timestamp);
+        }
+
+        *self.message_counts.entry(normalize_message(&entry.message)).or_insert(0) += 1;
+    }
+
+    /// Builds the report, keeping the `top` most frequent messages.
+    ///
+    /// Ties are broken alphabetically so the report is deterministic.
+    pub fn report(&self, top: usize) -> StatsReport {
+        let mut top_messages: Vec<MessageCount> = self
+            .message_counts
+            .iter()
+            .map(|(message, &count)| MessageCount { message: message.clone(), count })
+            .collect();
+        top_messages.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.message.cmp(&b.message)));
+        top_messages.truncate(top);
+
+        StatsReport {
+            levels: self.level_counts.clone(),
+            earliest: self.earliest,
+            latest: self.latest,
+            top_messages,
+        }
+    }
+}
+
+impl GroupStats {
+    /// Creates empty rollups grouping by the `details` value of `key`.
+    pub fn new(key: &str) -> Self {
+        GroupStats { key: key.to_string(), groups: HashMap::new() }
+    }
+
+    /// Folds a single entry into its group's summary.
+    pub fn record(&mut self, entry: &LogEntry) {
+        let group = entry.details.get(&self.key).map_or(NO_GROUP, String::as_str);
+        let summary = self.groups.entry(group.to_string()).or_insert_with(|| GroupSummary {
+            group: group.to_string(),
+            count: 0,
+            first: entry.timestamp,
+            last: entry.timestamp,
+            levels: BTreeMap::new(),
+        });
+        summary.count += 1;
+        summary.first = summary.first.min(entry.timestamp);
+        summary.last = summary.last.max(entry.timestamp);
+        *summary.levels.entry(entry.level.clone()).or_insert(0) += 1;
+    }
+
+    /// Every group's summary, largest first, ties broken by group name.
+    pub fn summaries(&self) -> Vec<GroupSummary> {
+        let mut summaries: Vec<GroupSummary> = self.groups.values().cloned().collect();
+        summaries.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.group.cmp(&b.group)));
+        summaries
+    }
+}
+
+impl Histogram {
+    /// Creates an empty histogram with buckets `interval` wide, aligned to the Unix epoch.
+    ///
+    /// Returns `None` for intervals shorter than one second.
+    pub fn new(interval: Duration) -> Option<Self> {
+        let interval_secs = interval.num_seconds();
+        (interval_secs > 0).then(|| Histogram { interval_secs, counts: BTreeMap::new() })
+    }
+
+    /// Counts one entry in the bucket containing `timestamp`.
+    pub fn record(&mut self, timestamp: DateTime<Local>) {
+        let start = timestamp.timestamp().div_euclid(self.interval_secs) * self.interval_secs;
+        *self.counts.entry(start).or_insert(0) += 1;
+    }
+
+    /// Number of buckets from the earliest to the latest non-empty one, inclusive.
+    pub fn span(&self) -> usize {
+        match (self.counts.first_key_value(), self.counts.last_key_value()) {
+            (Some((first, _)), Some((last, _))) => ((last - first) / self.interval_secs) as usize + 1,
+            _ => 0,
+        }
+    }
+
+    /// Every bucket from the earliest to the latest non-empty one, including empty buckets in between.
+    pub fn buckets(&self) -> Vec<HistogramBucket> {
+        let Some((&first, _)) = self.counts.first_key_value() else {
+            return Vec::new();
+        };
+        (0..self.span() as i64)
+            .map(|index| {
+                let start = first + index * self.interval_secs;
+                HistogramBucket {
+                    start: Local.timestamp_opt(start, 0).unwrap(),
+                    count: self.counts.get(&start).copied().unwrap_or(0),
+                }
+            })
+            .collect()
+    }
+}
+
+impl LatencyStats {
+    /// Records the entry's `elapsed_ms`, if it has one.
+    pub fn record(&mut self, entry: &LogEntry) {
+        if let Some(elapsed_ms) = entry.elapsed_ms {
+            self.samples.push(elapsed_ms);
+        }
+    }
+
+    /// Summarizes the recorded values, or `None` if no entry carried `elapsed`.
+    pub fn report(&self) -> Option<LatencyReport> {
+        let mut sorted = self.samples.clone();
+        sorted.sort_unstable();
+        let count = sorted.len();
+        let nearest_rank = |percentile: usize| sorted[(count * percentile).div_ceil(100).max(1) - 1];
+        Some(LatencyReport {
+            count,
+            min_ms: *sorted.first()?,
+            p50_ms: nearest_rank(50),
+            p90_ms: nearest_rank(90),
+            p99_ms: nearest_rank(99),
+            max_ms: *sorted.last()?,
+            mean_ms: sorted.iter().sum::<u64>() as f64 / count as f64,
+        })
+    }
+}
+
+impl ErrorRateMonitor {
+    /// Creates a monitor allowing at most `max` ERROR entries in any `window`.
+    ///
+    /// Returns `None` for a zero or negative window.
+    pub fn new(window: Duration, max: usize) -> Option<Self> {
+        (window > Duration::zero()).then(|| ErrorRateMonitor { window, max, recent: VecDeque::new(), peak: None })
+    }
+
+    /// Counts `entry` if it is an ERROR, sliding the window up to its timestamp.
+    pub fn record(&mut self, entry: &LogEntry) {
+        if LogLevel::from_level_str(&entry.level) != Some(LogLevel::Error) {
+            return;
+        }
+        let timestamp = entry.timestamp;
+        while self.recent.front().is_some_and(|&first| timestamp - first >= self.window) {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(timestamp);
+
+        let count = self.recent.len();
+        if count > self.max && self.peak.is_none_or(|peak| count > peak.count) {
+            self.peak = Some(RateBreach { start: self.recent[0], end: timestamp, count });
+        }
+    }
+
+    /// The busiest window that held more than the allowed ERROR entries, if any did.
+    pub fn breach(&self) -> Option<RateBreach> {
+        self.peak
+    }
+}
+
+// --- HELPER FUNCTIONS ---
+/// Serializes an optional timestamp the same way as `LogEntry.timestamp`, or as null.
+fn serialize_optional_timestamp<S: Serializer>(
+    timestamp: &Option<DateTime<Local>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match timestamp {
+        Some(timestamp) => serialize_timestamp(timestamp, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+// --- UNIT TESTS ---
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_line;
+
+    #[test]
+    fn test_stats_report_counts_and_top_messages() {
+        let lines = [
+            "INFO [11-08|10:49:09] Imported new block headers count=1 elapsed=2.5s",
+            "INFO [11-08|10:49:12] Imported new block headers count=4 elapsed=1.1s",
+            "WARN [11-08|10:49:10] Stale peer id=abc",
+            "INFO [11-08|10:49:05] Block synchronisation started",
+        ];
+        let mut stats = LogStats::default();
+        for line in lines {
+            stats.record(&parse_line(line, 2025).unwrap());
+        }
+
+        let report = stats.report(2);
+
+        assert_eq!(report.levels["INFO"], 3);
+        assert_eq!(report.levels["WARN"], 1);
+        assert_eq!(report.earliest.unwrap().format("%H:%M:%S").to_string(), "10:49:05");
+        assert_eq!(report.latest.unwrap().format("%H:%M:%S").to_string(), "10:49:12");
+        assert_eq!(report.top_messages.len(), 2);
+        assert_eq!(report.top_messages[0].message, "Imported new block headers count= elapsed=");
+        assert_eq!(report.top_messages[0].count, 2);
+        assert_eq!(report.top_messages[1].message, "Block synchronisation started");
+    }
+
+    #[test]
+    fn test_histogram_buckets_are_contiguous() {
+        let mut histogram = Histogram::new(Duration::minutes(1)).unwrap();
+        for line in [
+            "INFO [11-08|10:00:05] a",
+            "INFO [11-08|10:00:59] b",
+            "INFO [11-08|10:03:00] c",
+            "INFO [11-08|10:01:30] d",
+        ] {
+            histogram.record(parse_line(line, 2025).unwrap().timestamp);
+        }
+
+        let buckets = histogram.buckets();
+
+        let counts: Vec<usize> = buckets.iter().map(|bucket| bucket.count).collect();
+        assert_eq!(counts, [2, 1, 0, 1]);
+        assert_eq!(buckets[2].start.format("%H:%M:%S").to_string(), "10:02:00");
+        assert_eq!(histogram.span(), 4);
+        assert!(Histogram::new(Duration::milliseconds(500)).is_none());
+    }
+
+    #[test]
+    fn test_error_rate_monitor_finds_busiest_window() {
+        let mut monitor = ErrorRateMonitor::new(Duration::minutes(1), 2).unwrap();
+        for line in [
+            "ERROR [11-08|10:00:00] a",
+            "ERROR [11-08|10:00:30] b",
+            "WARN [11-08|10:00:40] not counted",
+            "ERROR [11-08|10:01:00] c (10:00:00 has left the window)",
+            "ERROR [11-08|10:01:10] d",
+            "ERROR [11-08|10:01:20] e",
+        ] {
+            monitor.record(&parse_line(line, 2025).unwrap());
+        }
+
+        let breach = monitor.breach().unwrap();
+        assert_eq!(breach.count, 4);
+        assert_eq!(breach.start.format("%H:%M:%S").to_string(), "10:00:30");
+        assert_eq!(breach.end.format("%H:%M:%S").to_string(), "10:01:20");
+        assert!(ErrorRateMonitor::new(Duration::zero(), 2).is_none());
+    }
+
+    #[test]
+    fn test_error_rate_monitor_allows_exactly_max() {
+        let mut monitor = ErrorRateMonitor::new(Duration::minutes(1), 2).unwrap();
+        for line in ["ERROR [11-08|10:00:00] a", "ERROR [11-08|10:00:59] b", "ERROR [11-08|10:01:00] c"] {
+            monitor.record(&parse_line(line, 2025).unwrap());
+        }
+
+        assert_eq!(monitor.breach(), None);
+    }
+
+    #[test]
+    fn test_group_stats_rollups_per_value() {
+        let mut groups = GroupStats::new("peer");
+        for line in [
+            "INFO [11-08|10:00:05] Imported block peer=a",
+            "WARN [11-08|10:00:01] Stale peer peer=b",
+            "ERROR [11-08|10:02:00] Dropped peer peer=a",
+            "INFO [11-08|10:00:03] Started",
+            "INFO [11-08|10:01:00] Imported block peer=a",
+        ] {
+            groups.record(&parse_line(line, 2025).unwrap());
+        }
+
+        let summaries = groups.summaries();
+
+        let names: Vec<&str> = summaries.iter().map(|summary| summary.group.as_str()).collect();
+        assert_eq!(names, ["a", NO_GROUP, "b"]);
+        assert_eq!(summaries[0].count, 3);
+        assert_eq!(summaries[0].first.format("%H:%M:%S").to_string(), "10:00:05");
+        assert_eq!(summaries[0].last.format("%H:%M:%S").to_string(), "10:02:00");
+        assert_eq!(summaries[0].levels, BTreeMap::from([("ERROR".to_string(), 1), ("INFO".to_string(), 2)]));
+    }
+
+    #[test]
+    fn test_latency_report_nearest_rank_percentiles() {
+        let mut latency = LatencyStats::default();
+        for ms in (1..=100).rev() {
+            let line = format!("INFO [11-08|10:00:00] Imported new chain segment elapsed={}ms", ms);
+            latency.record(&parse_line(&line, 2025).unwrap());
+        }
+        latency.record(&parse_line("INFO [11-08|10:00:01] Started", 2025).unwrap());
+
+        let report = latency.report().unwrap();
+
+        assert_eq!(report.count, 100);
+        assert_eq!((report.min_ms, report.max_ms), (1, 100));
+        assert_eq!((report.p50_ms, report.p90_ms, report.p99_ms), (50, 90, 99));
+        assert_eq!(report.mean_ms, 50.5);
+        assert_eq!(LatencyStats::default().report(), None);
+    }
+
+    #[test]
+    fn test_latency_report_single_sample() {
+        let mut latency = LatencyStats::default();
+        latency.record(&parse_line("INFO [11-08|10:00:00] Imported elapsed=4.521s", 2025).unwrap());
+
+        let report = latency.report().unwrap();
+
+        assert_eq!((report.min_ms, report.p50_ms, report.p99_ms, report.max_ms), (4521, 4521, 4521, 4521));
+    }
+}
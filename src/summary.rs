@@ -0,0 +1,322 @@
+//! The end-of-run report: the human-readable summary on stderr and the `--summary-json` totals.
+
+// --- IMPORTS ---
+// Standard library imports
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Write;
+use std::time::Duration;
+
+// Third-party libraries
+use chrono::{DateTime, Local};
+use serde::Serialize;
+
+// Local crate
+use geth_log_cruncher::{output_timestamp, TimestampVariant};
+use geth_log_cruncher::output::{EntryWriter, SplitBy};
+use geth_log_cruncher::stats::{HistogramBucket, LatencyReport, RateBreach};
+use crate::processor::{ProcessOptions, ProcessStats};
+use crate::CommonArgs;
+
+// --- DATA STRUCTURES ---
+/// An input that couldn't be opened or read to the end, skipped so the other inputs still run.
+#[derive(Debug, Serialize)]
+pub struct SkippedInput {
+    pub path: String,    // The input, as given or expanded from a directory
+    pub reason: String,  // The I/O error, e.g. `Permission denied (os error 13)`
+}
+
+/// Run totals written by `--summary-json`, for scripts that shouldn't scrape stderr.
+#[derive(Debug, Serialize)]
+pub struct RunSummary {
+    pub total_lines: usize,       // Every line read from the inputs
+    pub valid: usize,             // Lines that parsed into a `LogEntry`
+    pub invalid: usize,           // Lines that could not be parsed
+    pub invalid_percentage: f64,  // `invalid` as a percentage of `total_lines`
+    pub year_used: i32,           // Year used to complete timestamps (the starting year with `--auto-year`)
+    pub elapsed_secs: f64,        // Wall time spent reading and processing the inputs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub histogram: Option<Vec<HistogramBucket>>, // Entries per `--histogram` bucket
+    pub interrupted: bool,        // Ctrl-C stopped the run early, so the totals are partial
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anonymized: Option<usize>, // Distinct IPs and enode keys replaced under `--anonymize`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redactions: Option<usize>, // Matches replaced under `--redact`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sampled_out: Option<usize>, // Entries dropped by `--sample` or `--sample-random`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duplicates_dropped: Option<usize>, // Entries dropped by `--unique-by`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub skipped_inputs: Vec<SkippedInput>, // Inputs that failed to open or read, and why
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_rate_breach: Option<RateBreach>, // Busiest window above `--error-rate-max`, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency: Option<LatencyReport>, // `--latency-stats` over entries carrying `elapsed`, if any did
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp_formats: Option<BTreeMap<TimestampVariant, usize>>, // Valid lines per timestamp format, when mixed or under `--single-format`
+}
+
+// --- GLOBAL VARIABLES ---
+/// Width of the longest `--histogram` bar on stderr, in characters.
+pub const HISTOGRAM_BAR_WIDTH: usize = 40;
+
+// --- IMPLEMENTATIONS ---
+impl RunSummary {
+    /// Collects the totals reported for a run from its merged line counters.
+    pub fn new(stats: &ProcessStats, year_used: i32, elapsed: Duration) -> Self {
+        RunSummary {
+            total_lines: stats.total_lines,
+            valid: stats.valid_lines,
+            invalid: stats.invalid_lines(),
+            invalid_percentage: stats.invalid_percentage(),
+            year_used,
+            elapsed_secs: elapsed.as_secs_f64(),
+            histogram: None,
+            interrupted: false,
+            anonymized: None,
+            redactions: None,
+            sampled_out: None,
+            duplicates_dropped: None,
+            skipped_inputs: Vec::new(),
+            error_rate_breach: None,
+            latency: None,
+            timestamp_formats: None,
+        }
+    }
+}
+
+// --- HELPER FUNCTIONS ---
+/// Records an input that failed to open or read, warning about it unless `quiet` is set.
+pub fn skip_input(skipped: &mut Vec<SkippedInput>, path: &str, reason: String, quiet: bool) {
+    if !quiet {
+        eprintln!("Warning: Could not read '{}': {}; skipping it", path, reason);
+    }
+    skipped.push(SkippedInput { path: path.to_string(), reason });
+}
+
+/// Lists the inputs skipped after an I/O error, after the run summary.
+pub fn print_skipped(skipped: &[SkippedInput]) {
+    if skipped.is_empty() {
+        return;
+    }
+    eprintln!("Skipped Inputs (I/O errors): {}", skipped.len());
+    for input in skipped {
+        eprintln!("  {}: {}", input.path, input.reason);
+    }
+}
+
+/// Prints `--histogram` buckets to stderr as `start  count  bar`, scaling bars to the largest bucket.
+pub fn print_histogram(buckets: &[HistogramBucket]) {
+    let max_count = buckets.iter().map(|bucket| bucket.count).max().unwrap_or(0);
+    eprintln!("\nHistogram");
+    eprintln!("---------------------");
+    for bucket in buckets {
+        eprintln!(
+            "{}  {:>8}  {}",
+            output_timestamp(&bucket.start).format("%Y-%m-%d %H:%M:%S"),
+            bucket.count,
+            "#".repeat(histogram_bar_len(bucket.count, max_count))
+        );
+    }
+    eprintln!("---------------------");
+}
+
+/// Prints the `--latency-stats` report to stderr.
+pub fn print_latency(latency: &LatencyReport) {
+    eprintln!("\nLatency ({} entries with elapsed)", latency.count);
+    eprintln!("---------------------");
+    eprintln!("min   {:>10} ms", latency.min_ms);
+    eprintln!("p50   {:>10} ms", latency.p50_ms);
+    eprintln!("p90   {:>10} ms", latency.p90_ms);
+    eprintln!("p99   {:>10} ms", latency.p99_ms);
+    eprintln!("max   {:>10} ms", latency.max_ms);
+    eprintln!("mean  {:>10.1} ms", latency.mean_ms);
+    eprintln!("---------------------");
+}
+
+/// Length of a histogram bar for `count`, where `max_count` gets the full `HISTOGRAM_BAR_WIDTH`.
+///
+/// Any non-empty bucket gets at least one character so it stays visible.
+pub fn histogram_bar_len(count: usize, max_count: usize) -> usize {
+    if count == 0 {
+        return 0;
+    }
+    (count * HISTOGRAM_BAR_WIDTH / max_count).max(1)
+}
+
+/// Writes `summary` to `path` as a single JSON object, replacing any existing file.
+pub fn write_summary_json(path: &str, summary: &RunSummary) -> Result<(), Box<dyn std::error::Error>> {
+    let mut file = File::create(path).map_err(|e| format!("Error: Could not create summary file '{}': {}", path, e))?;
+    writeln!(file, "{}", serde_json::to_string(summary)?)?;
+    Ok(())
+}
+
+/// Prints the human-readable run summary to stderr.
+pub fn print_summary(
+    args: &CommonArgs,
+    options: &ProcessOptions,
+    file_stats: &[(&str, i32, ProcessStats)],
+    stats: &ProcessStats,
+    since_cutoff: Option<DateTime<Local>>,
+    elapsed: Duration,
+    writer: &EntryWriter<Box<dyn Write>>,
+) {
+    eprintln!("\nRun Summary");
+    eprintln!("---------------------");
+    if file_stats.len() > 1 {
+        for (log_file_path, file_year, per_file) in file_stats {
+            eprintln!(
+                "{}: {} lines, {} valid, {} invalid, year {}",
+                log_file_path,
+                per_file.total_lines,
+                per_file.valid_lines,
+                per_file.invalid_lines(),
+                file_year
+            );
+        }
+        eprintln!("---------------------");
+    }
+    if args.dry_run {
+        eprintln!("Dry Run: no output was written");
+    }
+    eprintln!("Total Lines Processed: {}", stats.total_lines);
+    if let Some(max_lines) = options.max_lines
+        && stats.total_lines >= max_lines
+    {
+        eprintln!("Line Limit Reached (--max-lines {}): remaining input was not read", max_lines);
+    }
+    if options.was_interrupted() {
+        eprintln!("Interrupted (Ctrl-C): remaining input was not read; totals are partial");
+    }
+    eprintln!("Valid Log Entries Found: {}", stats.valid_lines);
+    eprintln!(
+        "Invalid Log Entries: {} ({:.2}% of total lines)",
+        stats.invalid_lines(),
+        stats.invalid_percentage()
+    );
+    if let Some(errors_path) = &args.errors {
+        eprintln!("Invalid Lines Written To: {}", errors_path);
+    }
+    if let (Some(split_files), Some(out_dir)) = (writer.files_written(), &args.out_dir) {
+        eprintln!("Split Output: {} files written to {}", split_files, out_dir);
+        if args.split_by == Some(SplitBy::Level) {
+            for (name, written) in writer.split_counts().unwrap_or_default() {
+                eprintln!("  {}: {} entries", name, written);
+            }
+        }
+    }
+    if let (Some(points), Some(key)) = (writer.pivot_points(), &args.pivot) {
+        eprintln!("Pivot Points Written (entries with a numeric {}): {}", key, points);
+    }
+    for (name, written) in writer.sink_counts().unwrap_or_default() {
+        eprintln!("Output {}: {} entries written", name, written);
+    }
+    if options.join_continuations {
+        eprintln!("Continuation Lines Joined: {}", stats.joined_lines);
+    }
+    if stats.lossy_lines > 0 {
+        eprintln!("Lines With Invalid UTF-8 (lossily converted): {}", stats.lossy_lines);
+    }
+    if let Some(max_line_bytes) = options.max_line_bytes
+        && stats.oversized_lines > 0
+    {
+        eprintln!("Oversized Lines Skipped (over --max-line-bytes {}): {}", max_line_bytes, stats.oversized_lines);
+    }
+    if stats.malformed_hashes > 0 {
+        eprintln!("Warning: Entries With Malformed Hashes: {}", stats.malformed_hashes);
+    }
+    if options.anonymize {
+        eprintln!("Anonymized Values (distinct IPs and enode keys): {}", stats.anonymized_values);
+    }
+    if !options.redact.is_empty() {
+        eprintln!("Redactions (matches of --redact): {}", stats.redactions);
+    }
+    if stats.report_timestamp_variants(args.single_format) {
+        eprintln!("Timestamp Formats: {}", stats.describe_timestamp_variants());
+    }
+    if stats.script_errors > 0 {
+        eprintln!("Warning: Extract Script Errors (entries kept without derived fields): {}", stats.script_errors);
+    }
+    if let Some(limit) = args.limit_details
+        && stats.truncated_details > 0
+    {
+        eprintln!("Entries With Truncated Details (over --limit-details {}): {}", limit, stats.truncated_details);
+    }
+    if let Some(min_level) = args.level {
+        eprintln!(
+            "Filtered Out by Level (below {}): {}",
+            min_level.as_str(), stats.filtered_by_level
+        );
+    }
+    if args.since_duration.is_some() {
+        match since_cutoff {
+            Some(cutoff) => eprintln!("Since-Duration Cutoff: {}", cutoff.to_rfc3339()),
+            None => eprintln!("Since-Duration Cutoff: none (no valid entries)"),
+        }
+    }
+    if options.filters.from.is_some() || options.filters.until.is_some() {
+        eprintln!("Filtered Out by Time Range: {}", stats.filtered_by_time);
+    }
+    if !options.filters.details.is_empty() {
+        eprintln!("Filtered Out by --where: {}", stats.filtered_by_details);
+    }
+    if options.filters.grep.is_some() {
+        eprintln!("Filtered Out by --grep: {}", stats.filtered_by_grep);
+    }
+    if let Some(tail) = options.tail {
+        eprintln!(
+            "Tail Applied (last {} entries): {} earlier entries suppressed",
+            tail, stats.suppressed_by_tail
+        );
+    }
+    if options.dedup {
+        eprintln!("Consecutive Duplicates Collapsed: {}", stats.collapsed_duplicates);
+    }
+    if let Some(key) = &args.unique_by {
+        eprintln!("Duplicates Dropped by --unique-by {}: {}", key, stats.duplicates_dropped);
+    }
+    if let Some(n) = args.sample.or(args.sample_random) {
+        eprintln!("Sampled Out (keeping 1 in {}): {}", n, stats.sampled_out);
+    }
+    match file_stats {
+        [(_, file_year, _)] => eprintln!("Year Used for Timestamps: {}", file_year),
+        _ if args.year_from_filename || args.year_probe.is_some() => eprintln!("Year Used for Timestamps: per file (see above)"),
+        _ => eprintln!("Year Used for Timestamps: {}", options.parser.year),
+    }
+    if options.auto_year {
+        eprintln!("Year Rollovers Detected: {}", stats.year_rollovers);
+    }
+    eprintln!("Elapsed Time: {:.3}s", elapsed.as_secs_f64());
+    eprintln!(
+        "Throughput: {:.2} MB/s, {:.0} lines/s",
+        per_second(stats.bytes_read as f64 / 1_000_000.0, elapsed),
+        per_second(stats.total_lines as f64, elapsed)
+    );
+    eprintln!("---------------------");
+}
+
+/// Returns `amount` per second of `elapsed`, or 0 for an instantaneous run.
+pub fn per_second(amount: f64, elapsed: Duration) -> f64 {
+    let secs = elapsed.as_secs_f64();
+    if secs > 0.0 { amount / secs } else { 0.0 }
+}
+
+// --- UNIT TESTS ---
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_per_second_handles_zero_elapsed() {
+        assert_eq!(per_second(10.0, Duration::from_millis(500)), 20.0);
+        assert_eq!(per_second(10.0, Duration::ZERO), 0.0);
+    }
+
+    #[test]
+    fn test_histogram_bar_len_scales_to_max() {
+        assert_eq!(histogram_bar_len(0, 10), 0);
+        assert_eq!(histogram_bar_len(10, 10), HISTOGRAM_BAR_WIDTH);
+        assert_eq!(histogram_bar_len(5, 10), HISTOGRAM_BAR_WIDTH / 2);
+        assert_eq!(histogram_bar_len(1, 1000), 1);
+    }
+}
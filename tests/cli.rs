@@ -0,0 +1,670 @@
+//! End-to-end tests running the compiled binary, for behavior visible only on stdout/stderr.
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/// Writes `contents` to a uniquely named file in the temp directory.
+fn write_temp_log(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("glc-cli-{}-{}", std::process::id(), name));
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+/// Runs the binary with `args`, returning its captured output.
+fn run_cruncher(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_geth-log-cruncher"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+const SAMPLE_LOG: &str = "INFO [11-08|10:49:09.123] Imported new block headers count=1 elapsed=2.5s\n\
+                          WARN [11-08|10:49:10] Stale peer id=abc\n\
+                          not a log line\n";
+
+#[test]
+fn test_count_only_writes_nothing_to_stdout() {
+    let path = write_temp_log("count-only.log", SAMPLE_LOG);
+
+    let output = run_cruncher(&[path.to_str().unwrap(), "--count-only"]);
+    fs::remove_file(&path).unwrap();
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(stderr.contains("Total Lines Processed: 3"));
+    assert!(stderr.contains("Valid Log Entries Found: 2"));
+}
+
+#[test]
+fn test_since_duration_keeps_entries_near_latest_timestamp() {
+    let log = "INFO [11-08|10:00:00] Early\n\
+               INFO [11-08|10:50:00] Within window\n\
+               INFO [11-08|11:00:00] Latest\n";
+    let path = write_temp_log("since-duration.log", log);
+
+    let output = run_cruncher(&[path.to_str().unwrap(), "--year", "2025", "--since-duration", "15m"]);
+    fs::remove_file(&path).unwrap();
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success());
+    assert_eq!(stdout.lines().count(), 2);
+    assert!(!stdout.contains("Early"));
+    assert!(stderr.contains("Since-Duration Cutoff: 2025-11-08T10:45:00"));
+    assert!(stderr.contains("Filtered Out by Time Range: 1"));
+}
+
+#[test]
+fn test_quiet_leaves_stderr_empty() {
+    let path = write_temp_log("quiet.log", SAMPLE_LOG);
+
+    let output = run_cruncher(&[path.to_str().unwrap(), "--quiet"]);
+    fs::remove_file(&path).unwrap();
+
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+    assert_eq!(String::from_utf8(output.stdout).unwrap().lines().count(), 2);
+}
+
+#[test]
+fn test_summary_json_reports_totals() {
+    let path = write_temp_log("summary-json.log", SAMPLE_LOG);
+    let summary_path = std::env::temp_dir().join(format!("glc-cli-{}-summary.json", std::process::id()));
+
+    let output = run_cruncher(&[
+        path.to_str().unwrap(),
+        "--year",
+        "2024",
+        "--quiet",
+        "--summary-json",
+        summary_path.to_str().unwrap(),
+    ]);
+    let summary: serde_json::Value = serde_json::from_str(&fs::read_to_string(&summary_path).unwrap()).unwrap();
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&summary_path).unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(summary["total_lines"], 3);
+    assert_eq!(summary["valid"], 2);
+    assert_eq!(summary["invalid"], 1);
+    assert_eq!(summary["year_used"], 2024);
+    assert!(summary["elapsed_secs"].is_f64());
+}
+
+#[test]
+fn test_histogram_in_summary_json() {
+    let path = write_temp_log("histogram.log", SAMPLE_LOG);
+    let summary_path = std::env::temp_dir().join(format!("glc-cli-{}-histogram.json", std::process::id()));
+
+    let output = run_cruncher(&[
+        path.to_str().unwrap(),
+        "--count-only",
+        "--histogram",
+        "1s",
+        "--summary-json",
+        summary_path.to_str().unwrap(),
+    ]);
+    let summary: serde_json::Value = serde_json::from_str(&fs::read_to_string(&summary_path).unwrap()).unwrap();
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&summary_path).unwrap();
+
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Histogram"));
+    let counts: Vec<u64> = summary["histogram"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|bucket| bucket["count"].as_u64().unwrap())
+        .collect();
+    assert_eq!(counts, [1, 1]);
+}
+
+#[test]
+fn test_strict_exits_non_zero_on_unparseable_line() {
+    let path = write_temp_log("strict.log", SAMPLE_LOG);
+
+    let strict = run_cruncher(&[path.to_str().unwrap(), "--strict"]);
+    let tolerant = run_cruncher(&[path.to_str().unwrap()]);
+    fs::remove_file(&path).unwrap();
+
+    assert!(!strict.status.success());
+    assert!(String::from_utf8(strict.stderr).unwrap().contains("line 3 (--strict)"));
+    assert!(tolerant.status.success());
+}
+
+#[test]
+fn test_dry_run_writes_nothing_but_still_validates() {
+    let path = write_temp_log("dry-run.log", SAMPLE_LOG);
+
+    let valid = run_cruncher(&[path.to_str().unwrap(), "--dry-run"]);
+    let failing = run_cruncher(&[path.to_str().unwrap(), "--dry-run", "--threshold", "10"]);
+    fs::remove_file(&path).unwrap();
+
+    assert!(valid.status.success());
+    assert!(valid.stdout.is_empty());
+    assert!(String::from_utf8(valid.stderr).unwrap().contains("Dry Run"));
+    assert!(!failing.status.success());
+}
+
+#[test]
+fn test_files_from_combines_with_positional_paths() {
+    let first = write_temp_log("files-from-1.log", SAMPLE_LOG);
+    let second = write_temp_log("files-from-2.log", SAMPLE_LOG);
+    let list = write_temp_log(
+        "files-from.txt",
+        &format!("# inventory\n\n{}\n/nonexistent/geth.log\n", second.display()),
+    );
+
+    let aborted = run_cruncher(&[first.to_str().unwrap(), "--files-from", list.to_str().unwrap()]);
+    let skipped = run_cruncher(&[first.to_str().unwrap(), "--files-from", list.to_str().unwrap(), "--skip-missing"]);
+    for path in [&first, &second, &list] {
+        fs::remove_file(path).unwrap();
+    }
+
+    assert!(!aborted.status.success());
+    assert!(aborted.stdout.is_empty());
+    assert!(skipped.status.success());
+    assert_eq!(String::from_utf8(skipped.stdout).unwrap().lines().count(), 4);
+    assert!(String::from_utf8(skipped.stderr).unwrap().contains("/nonexistent/geth.log"));
+}
+
+#[test]
+fn test_unreadable_input_is_skipped_unless_strict_io() {
+    let readable = write_temp_log("io-readable.log", SAMPLE_LOG);
+    let broken = write_temp_log("io-broken.log.gz", "not actually gzip data\n");
+
+    let skipped = run_cruncher(&[readable.to_str().unwrap(), broken.to_str().unwrap()]);
+    let strict = run_cruncher(&[readable.to_str().unwrap(), broken.to_str().unwrap(), "--strict-io"]);
+    let all_failed = run_cruncher(&[broken.to_str().unwrap()]);
+    fs::remove_file(&readable).unwrap();
+    fs::remove_file(&broken).unwrap();
+
+    let stderr = String::from_utf8(skipped.stderr).unwrap();
+    assert!(skipped.status.success());
+    assert_eq!(String::from_utf8(skipped.stdout).unwrap().lines().count(), 2);
+    assert!(stderr.contains(&format!("Warning: Could not read '{}'", broken.display())));
+    assert!(stderr.contains("Skipped Inputs (I/O errors): 1"));
+    assert!(!strict.status.success());
+    assert!(String::from_utf8(strict.stderr).unwrap().contains(&format!("Error: Could not read '{}'", broken.display())));
+    assert!(!all_failed.status.success());
+    assert!(String::from_utf8(all_failed.stderr).unwrap().contains("All 1 inputs failed to read"));
+}
+
+#[test]
+fn test_directory_input_reads_matching_files_by_mtime() {
+    let dir = std::env::temp_dir().join(format!("glc-cli-{}-dir", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("b.log"), "INFO [11-08|10:49:09] First written\n").unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    fs::write(dir.join("a.log"), "INFO [11-08|10:49:10] Second written\n").unwrap();
+    fs::write(dir.join("skipped.txt"), "INFO [11-08|10:49:11] Not a log file\n").unwrap();
+
+    let output = run_cruncher(&[dir.to_str().unwrap(), "--glob", "*.log", "--file-order", "mtime"]);
+    fs::remove_dir_all(&dir).unwrap();
+
+    let messages: Vec<String> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["message"].as_str().unwrap().to_string())
+        .collect();
+    assert!(output.status.success());
+    assert_eq!(messages, ["First written", "Second written"]);
+}
+
+#[test]
+fn test_state_resumes_after_last_complete_line() {
+    let path = write_temp_log("state.log", "INFO [11-08|10:49:09] First\nINFO [11-08|10:49:10] Sec");
+    let state_path = std::env::temp_dir().join(format!("glc-cli-{}-state.json", std::process::id()));
+    let _ = fs::remove_file(&state_path);
+    let run = || {
+        let output = run_cruncher(&[path.to_str().unwrap(), "--state", state_path.to_str().unwrap()]);
+        assert!(output.status.success());
+        let messages: Vec<String> = String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["message"].as_str().unwrap().to_string())
+            .collect();
+        (messages, String::from_utf8(output.stderr).unwrap())
+    };
+
+    let (first_run, _) = run();
+    fs::write(&path, "INFO [11-08|10:49:09] First\nINFO [11-08|10:49:10] Second\nINFO [11-08|10:49:11] Third\n").unwrap();
+    let (second_run, _) = run();
+    let (third_run, third_stderr) = run();
+    fs::write(&path, "INFO [11-09|00:00:00] Rotated\n").unwrap();
+    let (rotated_run, rotated_stderr) = run();
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&state_path).unwrap();
+
+    assert_eq!(first_run, ["First"]);
+    assert_eq!(second_run, ["Second", "Third"]);
+    assert!(third_run.is_empty());
+    assert!(third_stderr.contains("No new lines since the last run"));
+    assert_eq!(rotated_run, ["Rotated"]);
+    assert!(rotated_stderr.contains("reading it from the start"));
+}
+
+#[test]
+fn test_debug_invalid_explains_dropped_lines() {
+    let bad_lines: String = (0..25).map(|i| format!("INFO [13-{:02}|10:00:00] Bad month\n", i + 1)).collect();
+    let path = write_temp_log("debug-invalid.log", &format!("{}{}", SAMPLE_LOG, bad_lines));
+
+    let output = run_cruncher(&[path.to_str().unwrap(), "--count-only", "--debug-invalid"]);
+    fs::remove_file(&path).unwrap();
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success());
+    assert!(stderr.contains("Invalid line at line 3: no log line format matched: \"not a log line\""));
+    assert!(stderr.contains("Invalid line at line 4: timestamp '13-01|10:00:00' did not parse"));
+    assert_eq!(stderr.matches("Invalid line at").count(), 20);
+    assert!(stderr.contains("6 more invalid lines not shown"));
+}
+
+#[test]
+fn test_utc_output_is_independent_of_timezone() {
+    let path = write_temp_log("utc.log", SAMPLE_LOG);
+
+    let outputs: Vec<Output> = ["UTC", "America/New_York", "Asia/Kolkata"]
+        .iter()
+        .map(|tz| {
+            Command::new(env!("CARGO_BIN_EXE_geth-log-cruncher"))
+                .args([path.to_str().unwrap(), "--year", "2024", "--utc", "--quiet"])
+                .env("TZ", tz)
+                .output()
+                .unwrap()
+        })
+        .collect();
+    fs::remove_file(&path).unwrap();
+
+    assert!(outputs.iter().all(|output| output.status.success()));
+    assert!(String::from_utf8_lossy(&outputs[0].stdout).contains("\"timestamp\":\"2024-11-08T10:49:09.123Z\""));
+    assert_eq!(outputs[0].stdout, outputs[1].stdout);
+    assert_eq!(outputs[0].stdout, outputs[2].stdout);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_ctrl_c_prints_partial_summary_and_exits_130() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_geth-log-cruncher"))
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(SAMPLE_LOG.as_bytes()).unwrap();
+    // Give the binary time to install its handler while it waits for more input.
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    let killed = Command::new("kill").args(["-INT", &child.id().to_string()]).status().unwrap();
+    drop(stdin);
+    let output = child.wait_with_output().unwrap();
+
+    assert!(killed.success());
+    assert_eq!(output.status.code(), Some(130));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("Interrupted (Ctrl-C)"));
+}
+
+#[test]
+fn test_repeated_output_writes_each_format() {
+    let path = write_temp_log("multi-output.log", SAMPLE_LOG);
+    let json_path = std::env::temp_dir().join(format!("glc-cli-{}-multi.ndjson", std::process::id()));
+    let csv_path = std::env::temp_dir().join(format!("glc-cli-{}-multi.csv", std::process::id()));
+    let json_arg = format!("json={}", json_path.display());
+    let csv_arg = format!("csv={}", csv_path.display());
+
+    let output = run_cruncher(&[path.to_str().unwrap(), "--output", &json_arg, "--output", &csv_arg]);
+    let json = fs::read_to_string(&json_path).unwrap();
+    let csv = fs::read_to_string(&csv_path).unwrap();
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&json_path).unwrap();
+    fs::remove_file(&csv_path).unwrap();
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert_eq!(json.lines().count(), 2);
+    assert_eq!(csv.lines().count(), 3);
+    assert!(stderr.contains(&format!("Output {}: 2 entries written", json_arg)));
+    assert!(stderr.contains(&format!("Output {}: 2 entries written", csv_arg)));
+}
+
+#[test]
+fn test_append_accumulates_runs_in_one_file() {
+    let path = write_temp_log("append.log", SAMPLE_LOG);
+    let json_path = std::env::temp_dir().join(format!("glc-cli-{}-append.ndjson", std::process::id()));
+    let csv_path = std::env::temp_dir().join(format!("glc-cli-{}-append.csv", std::process::id()));
+    let json_arg = format!("json={}", json_path.display());
+    let csv_arg = format!("csv={}", csv_path.display());
+    let _ = fs::remove_file(&json_path);
+    let _ = fs::remove_file(&csv_path);
+
+    let runs: Vec<Output> = (0..2)
+        .map(|_| run_cruncher(&[path.to_str().unwrap(), "--append", "--output", &json_arg, "--output", &csv_arg]))
+        .collect();
+    let array = run_cruncher(&[path.to_str().unwrap(), "--append", "--output", json_path.to_str().unwrap(), "--format", "json-array"]);
+    let json = fs::read_to_string(&json_path).unwrap();
+    let csv = fs::read_to_string(&csv_path).unwrap();
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&json_path).unwrap();
+    fs::remove_file(&csv_path).unwrap();
+
+    assert!(runs.iter().all(|run| run.status.success()));
+    assert_eq!(json.lines().count(), 4);
+    assert_eq!(csv.lines().count(), 5);
+    assert_eq!(csv.lines().filter(|line| line.starts_with("level,")).count(), 1);
+    assert!(!array.status.success());
+    assert!(String::from_utf8(array.stderr).unwrap().contains("--append can't be used with json-array output"));
+}
+
+#[test]
+fn test_json_array_format_is_one_array_even_when_empty() {
+    let path = write_temp_log("array.log", SAMPLE_LOG);
+    let empty_path = write_temp_log("array-empty.log", "");
+
+    let output = run_cruncher(&[path.to_str().unwrap(), "--format", "json-array"]);
+    let empty = run_cruncher(&[empty_path.to_str().unwrap(), "--format", "json-array"]);
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&empty_path).unwrap();
+
+    let entries: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(output.status.success());
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[1]["level"], "WARN");
+    assert!(empty.status.success());
+    assert_eq!(String::from_utf8(empty.stdout).unwrap(), "[]\n");
+}
+
+#[test]
+fn test_invert_writes_only_invalid_lines_to_stdout() {
+    let path = write_temp_log("invert.log", SAMPLE_LOG);
+
+    let output = run_cruncher(&[path.to_str().unwrap(), "--invert"]);
+    fs::remove_file(&path).unwrap();
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "3: not a log line\n");
+    assert!(stderr.contains("Valid Log Entries Found: 2"));
+    assert!(stderr.contains("Invalid Log Entries: 1"));
+}
+
+#[test]
+fn test_output_streams_ndjson_to_tcp() {
+    let path = write_temp_log("tcp-output.log", SAMPLE_LOG);
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let destination = format!("tcp://{}", listener.local_addr().unwrap());
+    let collector = std::thread::spawn(move || {
+        let mut received = String::new();
+        std::io::Read::read_to_string(&mut listener.accept().unwrap().0, &mut received).unwrap();
+        received
+    });
+
+    let output = run_cruncher(&[path.to_str().unwrap(), "--output", &destination]);
+    let received = collector.join().unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert_eq!(received.lines().count(), 2);
+    assert!(received.starts_with(r#"{"level":"INFO""#));
+}
+
+#[test]
+fn test_threshold_fails_only_when_exceeded() {
+    let path = write_temp_log("threshold.log", SAMPLE_LOG);
+
+    // One of three lines is invalid: 33.33%.
+    let exceeded = run_cruncher(&[path.to_str().unwrap(), "--threshold", "30"]);
+    let within = run_cruncher(&[path.to_str().unwrap(), "--threshold", "40"]);
+    fs::remove_file(&path).unwrap();
+
+    let stderr = String::from_utf8(exceeded.stderr).unwrap();
+    assert!(!exceeded.status.success());
+    assert!(stderr.contains("Run Summary"));
+    assert!(stderr.contains("33.33% of lines were invalid, above the --threshold of 30.00%"));
+    assert!(within.status.success());
+}
+
+#[test]
+fn test_single_format_fails_on_mixed_timestamps() {
+    let mixed = write_temp_log("single-format-mixed.log", SAMPLE_LOG);
+    let uniform = write_temp_log("single-format-uniform.log", "INFO [11-08|10:49:09.123] Started\nINFO [11-08|10:49:10.456] Stopped\n");
+
+    let failing = run_cruncher(&[mixed.to_str().unwrap(), "--single-format"]);
+    let passing = run_cruncher(&[uniform.to_str().unwrap(), "--single-format"]);
+    fs::remove_file(&mixed).unwrap();
+    fs::remove_file(&uniform).unwrap();
+
+    let stderr = String::from_utf8(failing.stderr).unwrap();
+    assert!(!failing.status.success());
+    assert!(stderr.contains("Timestamp Formats: bracket: 1, bracket-millis: 1"));
+    assert!(stderr.contains("Timestamps use 2 different formats (bracket: 1, bracket-millis: 1), but --single-format allows only one"));
+    assert!(passing.status.success());
+    assert!(String::from_utf8(passing.stderr).unwrap().contains("Timestamp Formats: bracket-millis: 2"));
+}
+
+#[test]
+fn test_error_rate_fails_only_when_a_window_exceeds_max() {
+    let log = "ERROR [11-08|10:00:00] Snapshot extension registration failed\n\
+               ERROR [11-08|10:00:20] Snapshot extension registration failed\n\
+               INFO [11-08|10:00:30] Imported new chain segment\n\
+               ERROR [11-08|10:00:40] Snapshot extension registration failed\n\
+               ERROR [11-08|10:02:00] Snapshot extension registration failed\n";
+    let path = write_temp_log("error-rate.log", log);
+
+    let exceeded = run_cruncher(&[path.to_str().unwrap(), "--count-only", "--error-rate-window", "1m", "--error-rate-max", "2"]);
+    let within = run_cruncher(&[path.to_str().unwrap(), "--count-only", "--error-rate-window", "1m", "--error-rate-max", "3"]);
+    fs::remove_file(&path).unwrap();
+
+    let stderr = String::from_utf8(exceeded.stderr).unwrap();
+    assert!(!exceeded.status.success());
+    assert!(stderr.contains("Run Summary"));
+    assert!(stderr.contains("3 ERROR entries between"));
+    assert!(stderr.contains("above the --error-rate-max of 2 per 1m"));
+    assert!(within.status.success());
+}
+
+#[test]
+fn test_group_by_writes_one_rollup_per_value() {
+    let path = write_temp_log("group-by.log", SAMPLE_LOG);
+
+    let output = run_cruncher(&[path.to_str().unwrap(), "--group-by", "id"]);
+    fs::remove_file(&path).unwrap();
+
+    let groups: Vec<serde_json::Value> =
+        String::from_utf8(output.stdout).unwrap().lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    assert!(output.status.success());
+    assert_eq!(groups.len(), 2);
+    assert_eq!(groups[0]["group"], "(none)");
+    assert_eq!(groups[0]["levels"]["INFO"], 1);
+    assert_eq!(groups[1]["group"], "abc");
+    assert_eq!(groups[1]["count"], 1);
+    assert_eq!(groups[1]["first"], groups[1]["last"]);
+}
+
+#[test]
+fn test_latency_stats_in_summary_json() {
+    let path = write_temp_log("latency.log", SAMPLE_LOG);
+    let summary_path = std::env::temp_dir().join(format!("glc-cli-{}-latency.json", std::process::id()));
+
+    let output = run_cruncher(&[
+        path.to_str().unwrap(),
+        "--count-only",
+        "--latency-stats",
+        "--summary-json",
+        summary_path.to_str().unwrap(),
+    ]);
+    let summary: serde_json::Value = serde_json::from_str(&fs::read_to_string(&summary_path).unwrap()).unwrap();
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&summary_path).unwrap();
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success());
+    assert!(stderr.contains("Latency (1 entries with elapsed)"));
+    assert_eq!(summary["latency"]["count"], 1);
+    assert_eq!(summary["latency"]["p99_ms"], 2500);
+}
+
+#[test]
+fn test_level_format_applies_after_filtering() {
+    let path = write_temp_log("level-format.log", SAMPLE_LOG);
+
+    let output = run_cruncher(&[
+        path.to_str().unwrap(),
+        "--level",
+        "WARN",
+        "--level-case",
+        "lower",
+        "--level-style",
+        "short",
+    ]);
+    fs::remove_file(&path).unwrap();
+
+    let entries: Vec<serde_json::Value> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert!(output.status.success());
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0]["level"], "w");
+}
+
+#[test]
+fn test_bench_reports_parser_throughput_as_json() {
+    let path = write_temp_log("bench.log", SAMPLE_LOG);
+
+    let output = run_cruncher(&[path.to_str().unwrap(), "--bench", "--bench-iterations", "3"]);
+    fs::remove_file(&path).unwrap();
+
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(output.status.success());
+    assert_eq!(report["lines"], 3);
+    assert_eq!(report["valid"], 2);
+    assert_eq!(report["iterations"], 3);
+    assert!(report["ns_per_line"].as_f64().unwrap() > 0.0);
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn test_print_schema_needs_no_input() {
+    let output = run_cruncher(&["--print-schema"]);
+
+    let sample: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(output.status.success());
+    assert_eq!(sample["level"], "INFO");
+    assert!(sample["block_number"].is_u64());
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn test_tz_localizes_naive_timestamps() {
+    let path = write_temp_log("tz.log", SAMPLE_LOG);
+    let first_timestamp = |extra: &[&str]| {
+        let mut args = vec![path.to_str().unwrap(), "--year", "2025"];
+        args.extend(extra);
+        let output = run_cruncher(&args);
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let entry: serde_json::Value = serde_json::from_str(stdout.lines().next().unwrap()).unwrap();
+        entry["timestamp"].as_str().unwrap().to_string()
+    };
+
+    let utc = first_timestamp(&["--tz", "+00:00"]);
+    let east = first_timestamp(&["--tz", "+02:00"]);
+    let east_as_utc = first_timestamp(&["--tz", "+02:00", "--utc"]);
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(utc, "2025-11-08T10:49:09.123Z");
+    assert_eq!(east, "2025-11-08T10:49:09.123+02:00");
+    assert_eq!(east_as_utc, "2025-11-08T08:49:09.123Z");
+}
+
+#[test]
+fn test_gzipped_output_decompresses_to_the_plain_output() {
+    use flate2::read::MultiGzDecoder;
+    use std::io::Read;
+
+    let path = write_temp_log("gzip-out.log", SAMPLE_LOG);
+    let dir = std::env::temp_dir();
+    let gz_path = dir.join(format!("glc-cli-{}-out.ndjson.gz", std::process::id()));
+    let csv_path = dir.join(format!("glc-cli-{}-out.csv", std::process::id()));
+    let gz_arg = format!("json={}", gz_path.display());
+    let csv_arg = format!("csv={}", csv_path.display());
+    let gunzip = |bytes: Vec<u8>| {
+        let mut text = String::new();
+        MultiGzDecoder::new(bytes.as_slice()).read_to_string(&mut text).unwrap();
+        text
+    };
+
+    let plain = run_cruncher(&[path.to_str().unwrap()]);
+    let plain_csv = run_cruncher(&[path.to_str().unwrap(), "--format", "csv"]);
+    let by_extension = run_cruncher(&[path.to_str().unwrap(), "--output", &gz_arg]);
+    let ndjson = gunzip(fs::read(&gz_path).unwrap());
+    let by_flag = run_cruncher(&[path.to_str().unwrap(), "--output", &csv_arg, "--compress-output", "gzip"]);
+    let csv = gunzip(fs::read(&csv_path).unwrap());
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&gz_path).unwrap();
+    fs::remove_file(&csv_path).unwrap();
+
+    assert!(by_extension.status.success() && by_flag.status.success());
+    assert_eq!(ndjson.lines().count(), 2);
+    assert_eq!(ndjson, String::from_utf8(plain.stdout).unwrap());
+    assert_eq!(csv, String::from_utf8(plain_csv.stdout).unwrap());
+}
+
+#[test]
+fn test_subcommands_match_their_flags() {
+    let path = write_temp_log("subcommands.log", SAMPLE_LOG);
+    let path = path.to_str().unwrap();
+
+    let stats = run_cruncher(&["stats", path, "--year", "2024", "--top", "1"]);
+    let stats_flag = run_cruncher(&[path, "--year", "2024", "--stats", "--top", "1"]);
+    let tail = run_cruncher(&["tail", "-n", "1", path, "--year", "2024"]);
+    let tail_flag = run_cruncher(&[path, "--year", "2024", "--tail", "1"]);
+    let parse = run_cruncher(&["parse", path, "--year", "2024"]);
+    let implicit = run_cruncher(&[path, "--year", "2024"]);
+    fs::remove_file(path).unwrap();
+
+    assert!(stats.status.success() && tail.status.success() && parse.status.success());
+    assert!(String::from_utf8_lossy(&stats.stdout).contains("\"top_messages\""));
+    assert_eq!(stats.stdout, stats_flag.stdout);
+    assert_eq!(String::from_utf8(tail.stdout.clone()).unwrap().lines().count(), 1);
+    assert_eq!(tail.stdout, tail_flag.stdout);
+    assert_eq!(parse.stdout, implicit.stdout);
+}
+
+#[test]
+fn test_state_dry_run_keeps_offsets_and_skips_unreadable_inputs() {
+    let path = write_temp_log("state-dry-run-2023.log", "INFO [11-08|10:49:09] First\n");
+    let state_path = std::env::temp_dir().join(format!("glc-cli-{}-dry-run-state.json", std::process::id()));
+    let _ = fs::remove_file(&state_path);
+    // Reading the start of /proc/self/mem fails with an I/O error, after the path passed validation.
+    let run = |extra: &[&str]| {
+        let mut args = vec![path.to_str().unwrap(), "/proc/self/mem", "--state", state_path.to_str().unwrap()];
+        args.extend(extra);
+        run_cruncher(&args)
+    };
+
+    let dry_run = run(&["--dry-run", "--year-from-filename"]);
+    let state_after_dry_run = fs::read_to_string(&state_path).ok();
+    let real_run = run(&["--year-from-filename"]);
+    let strict_run = run(&["--strict-io"]);
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&state_path).unwrap();
+
+    let stdout = String::from_utf8(real_run.stdout).unwrap();
+    let stderr = String::from_utf8(real_run.stderr).unwrap();
+    assert!(dry_run.status.success());
+    assert_eq!(state_after_dry_run, None);
+    assert!(real_run.status.success());
+    assert!(stdout.contains("\"timestamp\":\"2023-11-08T10:49:09"));
+    assert!(stderr.contains("Could not read '/proc/self/mem'"));
+    assert!(!strict_run.status.success());
+}